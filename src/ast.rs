@@ -1,42 +1,133 @@
+use std::{cell::Cell, rc::Rc};
+
 use crate::{
     token::{Identifier, Token},
     visitor::{StatementVisitor, Visitor},
 };
 
+#[derive(PartialEq)]
 pub enum Statement {
     Expression(ExpressionStatement),
     If(IfStatement),
     Print(PrintStatement),
     While(WhileStatement),
+    DoWhile(DoWhileStatement),
     Variable(VariableStatement),
     Block(BlockStatement),
+    Function(Rc<FunctionStatement>),
+    Return(ReturnStatement),
+    Break(BreakStatement),
+    Continue(ContinueStatement),
+    Assert(AssertStatement),
+    Class(ClassStatement),
+    TryCatch(TryCatchStatement),
+    Throw(ThrowStatement),
+    ForIn(ForInStatement),
+    With(WithStatement),
 }
 
+#[derive(PartialEq)]
 pub struct ExpressionStatement {
     pub expression: Box<Expr>,
 }
 
+#[derive(PartialEq)]
 pub struct PrintStatement {
+    pub keyword: Box<Token>,
     pub expression: Box<Expr>,
 }
 
+#[derive(PartialEq)]
 pub struct VariableStatement {
     pub name: Box<Identifier>,
     pub value: Box<Expr>,
+    pub mutable: bool,
 }
 
+#[derive(PartialEq)]
 pub struct BlockStatement {
     pub statements: Vec<Statement>,
 }
 
+#[derive(PartialEq)]
 pub struct IfStatement {
+    pub keyword: Box<Token>,
     pub condition: Box<Expr>,
     pub then_branch: Box<Statement>,
     pub else_branch: Option<Box<Statement>>,
 }
+#[derive(PartialEq)]
 pub struct WhileStatement {
     pub condition: Box<Expr>,
     pub body: Box<Statement>,
+    /// Evaluated after each iteration, including those ended by `continue`. Only set for desugared `for` loops.
+    pub increment: Option<Box<Expr>>,
+    /// Runs once the condition becomes false, unless the loop was exited with `break`.
+    pub else_branch: Option<Box<Statement>>,
+}
+
+/// A loop whose body runs once before the condition is first checked.
+#[derive(PartialEq)]
+pub struct DoWhileStatement {
+    pub keyword: Box<Token>,
+    pub body: Box<Statement>,
+    pub condition: Box<Expr>,
+}
+
+#[derive(PartialEq)]
+pub struct ForInStatement {
+    pub name: Box<Identifier>,
+    pub iterable: Box<Expr>,
+    pub body: Box<Statement>,
+}
+
+#[derive(PartialEq)]
+pub struct WithStatement {
+    pub keyword: Box<Token>,
+    pub object: Box<Expr>,
+    pub body: Box<Statement>,
+}
+
+#[derive(PartialEq)]
+pub struct FunctionStatement {
+    pub name: Box<Identifier>,
+    pub params: Vec<Identifier>,
+    pub body: Vec<Statement>,
+    /// Whether the body contains a `yield`, in which case calling the function returns a generator that runs the body
+    /// as it's resumed.
+    pub is_generator: bool,
+    /// Whether the last parameter is a `...rest` parameter, collecting the remaining arguments into an array.
+    pub variadic: bool,
+}
+
+#[derive(PartialEq)]
+pub struct ReturnStatement {
+    pub keyword: Box<Token>,
+    pub value: Option<Box<Expr>>,
+}
+
+#[derive(PartialEq)]
+pub struct BreakStatement {
+    pub keyword: Box<Token>,
+}
+
+#[derive(PartialEq)]
+pub struct ContinueStatement {
+    pub keyword: Box<Token>,
+}
+
+#[derive(PartialEq)]
+pub struct AssertStatement {
+    pub keyword: Box<Token>,
+    pub condition: Box<Expr>,
+    pub message: Option<Box<Expr>>,
+}
+
+#[derive(PartialEq)]
+pub struct ClassStatement {
+    pub name: Box<Identifier>,
+    pub superclass: Option<Box<Variable>>,
+    pub methods: Vec<Rc<FunctionStatement>>,
 }
 
 pub trait Stmt {
@@ -49,6 +140,26 @@ impl Stmt for Statement {
     }
 }
 
+#[derive(PartialEq)]
+pub struct TryCatchStatement {
+    pub body: Vec<Statement>,
+    pub catch: Option<CatchClause>,
+    pub finally: Option<Vec<Statement>>,
+}
+
+#[derive(PartialEq)]
+pub struct CatchClause {
+    pub name: Box<Identifier>,
+    pub body: Vec<Statement>,
+}
+
+#[derive(PartialEq)]
+pub struct ThrowStatement {
+    pub keyword: Box<Token>,
+    pub value: Box<Expr>,
+}
+
+#[derive(PartialEq)]
 pub enum Expr {
     Binary(Binary),
     Grouping(Grouping),
@@ -57,6 +168,20 @@ pub enum Expr {
     Unary(Unary),
     Variable(Variable),
     Assignment(Assignment),
+    Postfix(Postfix),
+    Call(Call),
+    Get(Get),
+    Set(Set),
+    This(This),
+    Super(Super),
+    ArrayLiteral(ArrayLiteral),
+    MapLiteral(MapLiteral),
+    Index(Index),
+    IndexSet(IndexSet),
+    Ternary(Ternary),
+    Yield(Yield),
+    Spread(Spread),
+    Function(FunctionExpr),
 }
 
 pub trait Node {
@@ -73,10 +198,25 @@ impl Node for Expr {
             Expr::Unary(it) => it.accept(visitor),
             Expr::Variable(it) => it.accept(visitor),
             Expr::Assignment(it) => it.accept(visitor),
+            Expr::Postfix(it) => it.accept(visitor),
+            Expr::Call(it) => it.accept(visitor),
+            Expr::Get(it) => it.accept(visitor),
+            Expr::Set(it) => it.accept(visitor),
+            Expr::This(it) => it.accept(visitor),
+            Expr::Super(it) => it.accept(visitor),
+            Expr::ArrayLiteral(it) => it.accept(visitor),
+            Expr::MapLiteral(it) => it.accept(visitor),
+            Expr::Index(it) => it.accept(visitor),
+            Expr::IndexSet(it) => it.accept(visitor),
+            Expr::Ternary(it) => it.accept(visitor),
+            Expr::Yield(it) => it.accept(visitor),
+            Expr::Spread(it) => it.accept(visitor),
+            Expr::Function(it) => it.accept(visitor),
         }
     }
 }
 
+#[derive(PartialEq)]
 pub struct Binary {
     pub left: Box<Expr>,
     pub operator: Box<Token>,
@@ -89,6 +229,7 @@ impl Node for Binary {
     }
 }
 
+#[derive(PartialEq)]
 pub struct Grouping {
     pub expression: Box<Expr>,
 }
@@ -99,13 +240,14 @@ impl Node for Grouping {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum LiteralValue {
     String(String),
     Number(f64),
     Boolean(bool),
     Nil,
 }
+#[derive(PartialEq)]
 pub struct Literal {
     pub value: LiteralValue,
 }
@@ -116,6 +258,20 @@ impl Node for Literal {
     }
 }
 
+#[derive(PartialEq)]
+pub struct Ternary {
+    pub condition: Box<Expr>,
+    pub then_branch: Box<Expr>,
+    pub else_branch: Box<Expr>,
+}
+
+impl Node for Ternary {
+    fn accept<T: Visitor>(&self, visitor: &mut T) -> T::Output {
+        visitor.visit_ternary(self)
+    }
+}
+
+#[derive(PartialEq)]
 pub struct Logical {
     pub left: Box<Expr>,
     pub operator: Box<Token>,
@@ -128,6 +284,7 @@ impl Node for Logical {
     }
 }
 
+#[derive(PartialEq)]
 pub struct Unary {
     pub operator: Box<Token>,
     pub right: Box<Expr>,
@@ -138,8 +295,11 @@ impl Node for Unary {
         visitor.visit_unary(self)
     }
 }
+#[derive(PartialEq)]
 pub struct Variable {
     pub token: Box<Identifier>,
+    /// Number of scopes between the use and the declaration, filled in by the resolver. `None` means global.
+    pub depth: Cell<Option<usize>>,
 }
 
 impl Node for Variable {
@@ -148,9 +308,12 @@ impl Node for Variable {
     }
 }
 
+#[derive(PartialEq)]
 pub struct Assignment {
     pub name: Box<Identifier>,
     pub value: Box<Expr>,
+    /// Number of scopes between the assignment and the declaration, filled in by the resolver. `None` means global.
+    pub depth: Cell<Option<usize>>,
 }
 
 impl Node for Assignment {
@@ -158,3 +321,170 @@ impl Node for Assignment {
         visitor.visit_assignment(self)
     }
 }
+
+/// `name++` or `name--`, which assigns the variable its value plus or minus one and evaluates to the value it had
+/// before.
+#[derive(PartialEq)]
+pub struct Postfix {
+    pub name: Box<Identifier>,
+    pub operator: Box<Token>,
+    /// Number of scopes between the expression and the declaration, filled in by the resolver. `None` means global.
+    pub depth: Cell<Option<usize>>,
+}
+
+impl Node for Postfix {
+    fn accept<T: Visitor>(&self, visitor: &mut T) -> T::Output {
+        visitor.visit_postfix(self)
+    }
+}
+
+#[derive(PartialEq)]
+pub struct Call {
+    pub callee: Box<Expr>,
+    pub paren: Box<Token>,
+    pub arguments: Vec<Expr>,
+}
+
+impl Node for Call {
+    fn accept<T: Visitor>(&self, visitor: &mut T) -> T::Output {
+        visitor.visit_call(self)
+    }
+}
+
+#[derive(PartialEq)]
+pub struct Get {
+    pub object: Box<Expr>,
+    pub name: Box<Identifier>,
+}
+
+impl Node for Get {
+    fn accept<T: Visitor>(&self, visitor: &mut T) -> T::Output {
+        visitor.visit_get(self)
+    }
+}
+
+#[derive(PartialEq)]
+pub struct Set {
+    pub object: Box<Expr>,
+    pub name: Box<Identifier>,
+    pub value: Box<Expr>,
+}
+
+impl Node for Set {
+    fn accept<T: Visitor>(&self, visitor: &mut T) -> T::Output {
+        visitor.visit_set(self)
+    }
+}
+
+#[derive(PartialEq)]
+pub struct This {
+    pub keyword: Box<Token>,
+}
+
+impl Node for This {
+    fn accept<T: Visitor>(&self, visitor: &mut T) -> T::Output {
+        visitor.visit_this(self)
+    }
+}
+
+#[derive(PartialEq)]
+pub struct Super {
+    pub keyword: Box<Token>,
+    pub method: Box<Identifier>,
+}
+
+impl Node for Super {
+    fn accept<T: Visitor>(&self, visitor: &mut T) -> T::Output {
+        visitor.visit_super(self)
+    }
+}
+
+#[derive(PartialEq)]
+pub struct ArrayLiteral {
+    pub bracket: Box<Token>,
+    pub elements: Vec<Expr>,
+}
+
+impl Node for ArrayLiteral {
+    fn accept<T: Visitor>(&self, visitor: &mut T) -> T::Output {
+        visitor.visit_array_literal(self)
+    }
+}
+
+#[derive(PartialEq)]
+pub struct MapLiteral {
+    pub brace: Box<Token>,
+    pub entries: Vec<(Expr, Expr)>,
+}
+
+impl Node for MapLiteral {
+    fn accept<T: Visitor>(&self, visitor: &mut T) -> T::Output {
+        visitor.visit_map_literal(self)
+    }
+}
+
+#[derive(PartialEq)]
+pub struct Index {
+    pub object: Box<Expr>,
+    pub bracket: Box<Token>,
+    pub index: Box<Expr>,
+}
+
+impl Node for Index {
+    fn accept<T: Visitor>(&self, visitor: &mut T) -> T::Output {
+        visitor.visit_index(self)
+    }
+}
+
+#[derive(PartialEq)]
+pub struct IndexSet {
+    pub object: Box<Expr>,
+    pub bracket: Box<Token>,
+    pub index: Box<Expr>,
+    pub value: Box<Expr>,
+}
+
+impl Node for IndexSet {
+    fn accept<T: Visitor>(&self, visitor: &mut T) -> T::Output {
+        visitor.visit_index_set(self)
+    }
+}
+
+#[derive(PartialEq)]
+pub struct Yield {
+    pub keyword: Box<Token>,
+    pub value: Option<Box<Expr>>,
+}
+
+impl Node for Yield {
+    fn accept<T: Visitor>(&self, visitor: &mut T) -> T::Output {
+        visitor.visit_yield(self)
+    }
+}
+
+/// An `...array` call argument, passing each element of the array as its own argument.
+#[derive(PartialEq)]
+pub struct Spread {
+    pub ellipsis: Box<Token>,
+    pub value: Box<Expr>,
+}
+
+impl Node for Spread {
+    fn accept<T: Visitor>(&self, visitor: &mut T) -> T::Output {
+        visitor.visit_spread(self)
+    }
+}
+
+/// An anonymous `fun (params) { body }` expression. Its declaration is named `anonymous` and is never bound to a
+/// variable, evaluating it only creates the closure.
+#[derive(PartialEq)]
+pub struct FunctionExpr {
+    pub keyword: Box<Token>,
+    pub declaration: Rc<FunctionStatement>,
+}
+
+impl Node for FunctionExpr {
+    fn accept<T: Visitor>(&self, visitor: &mut T) -> T::Output {
+        visitor.visit_function(self)
+    }
+}
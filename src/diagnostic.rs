@@ -0,0 +1,80 @@
+/// Renders the line of `source` containing the byte `offset`, with a caret under the offset's column:
+///
+/// ```text
+///   3 | print 1
+///     |        ^
+/// ```
+pub fn render(source: &str, offset: usize) -> String {
+    let offset = offset.min(source.len());
+    let line_start = source[..offset].rfind('\n').map_or(0, |index| index + 1);
+    let line_end = source[offset..].find('\n').map_or(source.len(), |index| offset + index);
+    let line_number = source[..offset].matches('\n').count() + 1;
+
+    // Tabs are kept in the padding so the caret lines up however wide the terminal renders them
+    let padding = source[line_start..offset]
+        .chars()
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect::<String>();
+    let gutter = " ".repeat(line_number.to_string().len());
+
+    format!(
+        "  {} | {}\n  {} | {}^",
+        line_number,
+        source[line_start..line_end].trim_end_matches('\r'),
+        gutter,
+        padding
+    )
+}
+
+/// Renders line `line_number` of `source` without a caret, for errors that only know their line:
+///
+/// ```text
+///   3 | print 1 / 0;
+/// ```
+///
+/// Returns `None` when `source` has no such line.
+pub fn render_line(source: &str, line_number: usize) -> Option<String> {
+    let line = source.split('\n').nth(line_number.checked_sub(1)?)?;
+    Some(format!("  {} | {}", line_number, line.trim_end_matches('\r')))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::Parser, scanner::Scanner};
+
+    #[test]
+    fn test_rendering_a_missing_semicolon() {
+        let source = "var x = 1;\nprint x\nprint 2;";
+        let mut errors = Vec::new();
+        let (tokens, spans) = {
+            let mut scanner = Scanner::new(source, &mut errors);
+            scanner.scan();
+            scanner.into_tokens_with_spans()
+        };
+        let mut parser = Parser::new(&tokens, &mut errors);
+        parser.parse();
+
+        let error = &parser.parse_errors()[0];
+        let offset = error.token.map_or(0, |token| spans[token].end);
+        assert_eq!(
+            format!("{}\n{}", error, render(source, offset)),
+            "[line 2] Error: Expected ';' after value.\n  2 | print x\n    |        ^"
+        );
+    }
+
+    #[test]
+    fn test_rendering_keeps_tabs_and_wide_line_numbers() {
+        let source = format!("{}\tprint (1", "\n".repeat(11));
+        assert_eq!(render(&source, source.len()), "  12 | \tprint (1\n     | \t        ^");
+        assert_eq!(render("", 0), "  1 | \n    | ^");
+    }
+
+    #[test]
+    fn test_rendering_a_line_without_a_caret() {
+        let source = "var a = 1;\r\nprint a / 0;";
+        assert_eq!(render_line(source, 2), Some("  2 | print a / 0;".to_string()));
+        assert_eq!(render_line(source, 3), None);
+        assert_eq!(render_line(source, 0), None);
+    }
+}
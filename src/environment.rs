@@ -1,29 +1,129 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
 
 use crate::vm::{RuntimeError, Value};
 
 pub type Env = Rc<RefCell<Environment>>;
+
+#[derive(Clone)]
 pub struct Environment {
-    values: HashMap<String, Value>,
+    values: HashMap<Rc<str>, Value>,
+    constants: HashSet<Rc<str>>,
     enclosing: Option<Env>,
+    /// The instance or map whose members are exposed as variables by a `with` statement.
+    object: Option<Value>,
+}
+
+/// The bindings of a single environment at some point in time, taken with `Environment::snapshot`.
+pub struct Snapshot {
+    values: HashMap<Rc<str>, Value>,
+    constants: HashSet<Rc<str>>,
 }
 
 impl Environment {
     pub fn new(enclosing: Option<Env>) -> Self {
         Environment {
             values: HashMap::new(),
+            constants: HashSet::new(),
             enclosing,
+            object: None,
+        }
+    }
+
+    /// Creates an environment that resolves names against the members of `object` before its enclosing scopes.
+    pub fn with_object(enclosing: Env, object: Value) -> Self {
+        Environment {
+            object: Some(object),
+            ..Environment::new(Some(enclosing))
+        }
+    }
+
+    /// Reads a member of an object exposed by a `with` statement, searching the environments closer than `distance`,
+    /// or all of them when `distance` is `None`. Variables declared inside the `with` body are further away than the
+    /// object, so they are not shadowed by it.
+    pub fn get_member(&self, distance: Option<usize>, name: &str) -> Option<Value> {
+        if distance == Some(0) {
+            return None;
+        }
+
+        if let Some(value) = self.object.as_ref().and_then(|object| object.member(name)) {
+            return Some(value);
+        }
+
+        let enclosing = self.enclosing.as_ref()?;
+        enclosing.borrow().get_member(distance.map(|d| d - 1), name)
+    }
+
+    /// Assigns a member of an object exposed by a `with` statement, following the same search as `get_member`.
+    /// Returns whether a member was found.
+    pub fn assign_member(&self, distance: Option<usize>, name: &str, value: &Value) -> bool {
+        if distance == Some(0) {
+            return false;
+        }
+
+        if let Some(object) = &self.object
+            && object.set_member(name, value.clone())
+        {
+            return true;
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().assign_member(distance.map(|d| d - 1), name, value),
+            None => false,
         }
     }
 
+    /// Captures this environment's bindings so that `restore` can undo the definitions and assignments made after it.
+    /// The bindings are cloned, which is cheap for scalars but shares arrays, maps and instances, so mutations made to
+    /// their contents are not undone. Enclosing environments are not captured.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            values: self.values.clone(),
+            constants: self.constants.clone(),
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        self.values = snapshot.values;
+        self.constants = snapshot.constants;
+    }
+
     pub fn new_global() -> Env {
         Rc::new(RefCell::new(Environment::new(None)))
     }
 
-    pub fn define(&mut self, name: String, value: Value) {
+    pub fn define(&mut self, name: Rc<str>, value: Value) {
+        self.constants.remove(&name);
         self.values.insert(name, value);
     }
 
+    /// Defines a binding that can't be reassigned. Declaring a new variable with the same name, either in this scope
+    /// or in an inner one, is still allowed.
+    pub fn define_constant(&mut self, name: Rc<str>, value: Value) {
+        self.constants.insert(name.clone());
+        self.values.insert(name, value);
+    }
+
+    /// Removes a binding from this environment, leaving the ones in enclosing environments untouched.
+    pub fn remove(&mut self, name: &str) {
+        self.constants.remove(name);
+        self.values.remove(name);
+    }
+
+    fn check_mutable(&self, name: &str) -> Result<(), RuntimeError> {
+        if self.constants.contains(name) {
+            return Err(RuntimeError::ArgumentError(format!(
+                "Cannot assign to constant '{}'",
+                name
+            )));
+        }
+
+        Ok(())
+    }
+
     pub fn get(&self, name: &str) -> Result<Value, RuntimeError> {
         match self.values.get(name) {
             Some(value) => Ok(value.clone()),
@@ -37,7 +137,57 @@ impl Environment {
         }
     }
 
+    /// Reads a variable from the environment `distance` scopes up the chain, as computed by the resolver.
+    pub fn get_at(&self, distance: usize, name: &str) -> Result<Value, RuntimeError> {
+        if distance == 0 {
+            return match self.values.get(name) {
+                Some(value) => Ok(value.clone()),
+                None => Err(RuntimeError::UndefinedVariable(format!(
+                    "{} variable is not defined",
+                    name
+                ))),
+            };
+        }
+
+        match self.enclosing {
+            Some(ref enclosing) => enclosing.borrow().get_at(distance - 1, name),
+            None => Err(RuntimeError::UndefinedVariable(format!(
+                "{} variable is not defined",
+                name
+            ))),
+        }
+    }
+
+    pub fn assign_at(&mut self, distance: usize, name: &str, value: Value) -> Result<(), RuntimeError> {
+        if distance == 0 {
+            self.check_mutable(name)?;
+
+            return match self.values.get_mut(name) {
+                Some(v) => {
+                    *v = value;
+                    Ok(())
+                }
+                None => Err(RuntimeError::UndefinedVariable(format!(
+                    "{} variable is not defined",
+                    name
+                ))),
+            };
+        }
+
+        match self.enclosing {
+            Some(ref mut enclosing) => enclosing.borrow_mut().assign_at(distance - 1, name, value),
+            None => Err(RuntimeError::UndefinedVariable(format!(
+                "{} variable is not defined",
+                name
+            ))),
+        }
+    }
+
     pub fn assign(&mut self, name: &str, value: Value) -> Result<(), RuntimeError> {
+        if self.values.contains_key(name) {
+            self.check_mutable(name)?;
+        }
+
         if let Some(v) = self.values.get_mut(name) {
             *v = value;
             Ok(())
@@ -60,8 +210,8 @@ mod tests {
     #[test]
     fn test_defining_global_variables() {
         let mut env = Environment::new(None);
-        env.define("x".to_string(), Value::Number(42.0));
-        env.define("y".to_string(), Value::String("Hello".to_string()));
+        env.define("x".into(), Value::Number(42.0));
+        env.define("y".into(), Value::String("Hello".to_string()));
 
         assert_eq!(env.get("x").unwrap(), Value::Number(42.0));
         assert_eq!(env.get("y").unwrap(), Value::String("Hello".to_string()));
@@ -70,8 +220,8 @@ mod tests {
     #[test]
     fn test_getting_variables_from_enclosing_environments() {
         let mut parent_env = Environment::new(None);
-        parent_env.define("x".to_string(), Value::Number(42.0));
-        parent_env.define("y".to_string(), Value::String("Hello".to_string()));
+        parent_env.define("x".into(), Value::Number(42.0));
+        parent_env.define("y".into(), Value::String("Hello".to_string()));
 
         let child_env = Environment::new(Some(Rc::new(RefCell::new(parent_env))));
 
@@ -83,8 +233,8 @@ mod tests {
     #[test]
     fn test_assigning_variables_for_enclosing_environments() {
         let mut parent_env = Environment::new(None);
-        parent_env.define("x".to_string(), Value::Number(42.0));
-        parent_env.define("y".to_string(), Value::String("Hello".to_string()));
+        parent_env.define("x".into(), Value::Number(42.0));
+        parent_env.define("y".into(), Value::String("Hello".to_string()));
 
         let mut child_env = Environment::new(Some(Rc::new(RefCell::new(parent_env))));
         child_env.assign("x", Value::Number(100.0)).unwrap();
@@ -93,4 +243,52 @@ mod tests {
         assert_eq!(child_env.get("y").unwrap(), Value::String("Hello".to_string()));
         assert!(child_env.get("z").is_err());
     }
+
+    #[test]
+    fn test_getting_and_assigning_variables_at_a_distance() {
+        let mut parent_env = Environment::new(None);
+        parent_env.define("x".into(), Value::Number(42.0));
+
+        let mut child_env = Environment::new(Some(Rc::new(RefCell::new(parent_env))));
+        child_env.define("x".into(), Value::Number(1.0));
+
+        assert_eq!(child_env.get_at(0, "x").unwrap(), Value::Number(1.0));
+        assert_eq!(child_env.get_at(1, "x").unwrap(), Value::Number(42.0));
+
+        child_env.assign_at(1, "x", Value::Number(100.0)).unwrap();
+        assert_eq!(child_env.get_at(0, "x").unwrap(), Value::Number(1.0));
+        assert_eq!(child_env.get_at(1, "x").unwrap(), Value::Number(100.0));
+        assert!(child_env.get_at(2, "x").is_err());
+    }
+
+    #[test]
+    fn test_assigning_constants() {
+        let mut parent_env = Environment::new(None);
+        parent_env.define_constant("x".into(), Value::Number(42.0));
+
+        let mut child_env = Environment::new(Some(Rc::new(RefCell::new(parent_env))));
+        let error = child_env.assign("x", Value::Number(100.0)).unwrap_err();
+        assert_eq!(error.to_string(), "Cannot assign to constant 'x'");
+        assert!(child_env.assign_at(1, "x", Value::Number(100.0)).is_err());
+
+        child_env.define("x".into(), Value::Number(1.0));
+        child_env.assign("x", Value::Number(2.0)).unwrap();
+        assert_eq!(child_env.get("x").unwrap(), Value::Number(2.0));
+    }
+
+    #[test]
+    fn test_restoring_snapshots() {
+        let mut env = Environment::new(None);
+        env.define("x".into(), Value::Number(1.0));
+
+        let snapshot = env.snapshot();
+        env.define("y".into(), Value::Number(2.0));
+        env.define_constant("z".into(), Value::Number(3.0));
+        env.assign("x", Value::Number(10.0)).unwrap();
+
+        env.restore(snapshot);
+        assert_eq!(env.get("x").unwrap(), Value::Number(1.0));
+        assert!(env.get("y").is_err());
+        assert!(env.get("z").is_err());
+    }
 }
@@ -0,0 +1,394 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    map::Map,
+    vm::{HashableValue, RuntimeError, Value},
+};
+
+pub fn stringify(value: &Value) -> Result<String, RuntimeError> {
+    let mut output = String::new();
+    write_value(value, &mut output, &mut Vec::new())?;
+    Ok(output)
+}
+
+pub fn parse(source: &str) -> Result<Value, RuntimeError> {
+    let mut parser = JsonParser {
+        chars: source.chars().collect(),
+        current: 0,
+    };
+
+    let value = parser.value()?;
+    parser.skip_whitespace();
+
+    if parser.current < parser.chars.len() {
+        return Err(parser.error("Unexpected trailing characters"));
+    }
+
+    Ok(value)
+}
+
+/// Writes `value` as JSON. `ancestors` holds the addresses of the arrays and maps currently being written, so that a
+/// structure containing itself is reported instead of recursing forever. A structure shared between siblings is fine.
+fn write_value(value: &Value, output: &mut String, ancestors: &mut Vec<usize>) -> Result<(), RuntimeError> {
+    let address = match value {
+        Value::Array(elements) => Some(Rc::as_ptr(elements) as *const () as usize),
+        Value::Map(map) => Some(Rc::as_ptr(map) as *const () as usize),
+        _ => None,
+    };
+
+    if let Some(address) = address {
+        if ancestors.contains(&address) {
+            return Err(RuntimeError::ArgumentError(
+                "Cannot convert circular structure to JSON".to_string(),
+            ));
+        }
+        ancestors.push(address);
+    }
+
+    match value {
+        Value::Nil => output.push_str("null"),
+        Value::Boolean(b) => output.push_str(&b.to_string()),
+        Value::Number(n) if n.is_finite() => output.push_str(&n.to_string()),
+        Value::Number(n) => {
+            return Err(RuntimeError::ArgumentError(format!("Cannot convert {} to JSON", n)));
+        }
+        Value::String(s) => write_string(s, output),
+        Value::Array(elements) => {
+            output.push('[');
+            for (index, element) in elements.borrow().iter().enumerate() {
+                if index > 0 {
+                    output.push(',');
+                }
+                write_value(element, output, ancestors)?;
+            }
+            output.push(']');
+        }
+        Value::Map(entries) => {
+            output.push('{');
+            for (index, (key, value)) in entries.borrow().entries().iter().enumerate() {
+                if index > 0 {
+                    output.push(',');
+                }
+                match key {
+                    Value::String(key) => write_string(key, output),
+                    other => {
+                        return Err(RuntimeError::ArgumentError(format!(
+                            "Cannot convert map key {} to JSON",
+                            other
+                        )));
+                    }
+                }
+                output.push(':');
+                write_value(value, output, ancestors)?;
+            }
+            output.push('}');
+        }
+        other => {
+            return Err(RuntimeError::ArgumentError(format!("Cannot convert {} to JSON", other)));
+        }
+    }
+
+    if address.is_some() {
+        ancestors.pop();
+    }
+    Ok(())
+}
+
+pub(crate) fn write_string(value: &str, output: &mut String) {
+    output.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if c.is_control() => output.push_str(&format!("\\u{:04x}", c as u32)),
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+}
+
+struct JsonParser {
+    chars: Vec<char>,
+    current: usize,
+}
+
+impl JsonParser {
+    fn value(&mut self) -> Result<Value, RuntimeError> {
+        self.skip_whitespace();
+
+        match self.peek() {
+            Some('n') => self.keyword("null", Value::Nil),
+            Some('t') => self.keyword("true", Value::Boolean(true)),
+            Some('f') => self.keyword("false", Value::Boolean(false)),
+            Some('"') => Ok(Value::String(self.string()?)),
+            Some('[') => self.array(),
+            Some('{') => self.object(),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+            Some(c) => Err(self.error(&format!("Unexpected character '{}'", c))),
+            None => Err(self.error("Unexpected end of input")),
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str, value: Value) -> Result<Value, RuntimeError> {
+        for expected in keyword.chars() {
+            if self.advance() != Some(expected) {
+                return Err(self.error(&format!("Expected '{}'", keyword)));
+            }
+        }
+
+        Ok(value)
+    }
+
+    fn number(&mut self) -> Result<Value, RuntimeError> {
+        let start = self.current;
+
+        while let Some(c) = self.peek() {
+            if c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E') {
+                self.advance();
+            } else {
+                break;
+            }
+        }
+
+        let text = self.chars[start..self.current].iter().collect::<String>();
+
+        // JSON doesn't allow leading zeros, which Rust's parser would otherwise accept
+        let integer = text.strip_prefix('-').unwrap_or(&text);
+        if integer.starts_with('0') && integer[1..].starts_with(|c: char| c.is_ascii_digit()) {
+            return Err(self.error(&format!("Invalid number '{}'", text)));
+        }
+
+        text.parse()
+            .map(Value::Number)
+            .map_err(|_| self.error(&format!("Invalid number '{}'", text)))
+    }
+
+    fn string(&mut self) -> Result<String, RuntimeError> {
+        self.advance();
+        let mut value = String::new();
+
+        loop {
+            match self.advance() {
+                Some('"') => return Ok(value),
+                Some('\\') => match self.advance() {
+                    Some('"') => value.push('"'),
+                    Some('\\') => value.push('\\'),
+                    Some('/') => value.push('/'),
+                    Some('b') => value.push('\u{8}'),
+                    Some('f') => value.push('\u{c}'),
+                    Some('n') => value.push('\n'),
+                    Some('r') => value.push('\r'),
+                    Some('t') => value.push('\t'),
+                    Some('u') => value.push(self.unicode_escape()?),
+                    _ => return Err(self.error("Invalid escape sequence")),
+                },
+                Some(c) => value.push(c),
+                None => return Err(self.error("Unterminated string")),
+            }
+        }
+    }
+
+    fn unicode_escape(&mut self) -> Result<char, RuntimeError> {
+        let high = self.hex_code_unit()?;
+
+        let code_point = if (0xD800..0xDC00).contains(&high) {
+            if self.advance() != Some('\\') || self.advance() != Some('u') {
+                return Err(self.error("Expected low surrogate"));
+            }
+            let low = self.hex_code_unit()?;
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err(self.error("Expected low surrogate"));
+            }
+            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+        } else {
+            high
+        };
+
+        char::from_u32(code_point).ok_or_else(|| self.error("Invalid unicode escape"))
+    }
+
+    fn hex_code_unit(&mut self) -> Result<u32, RuntimeError> {
+        let mut code_unit = 0;
+
+        for _ in 0..4 {
+            match self.advance().and_then(|c| c.to_digit(16)) {
+                Some(digit) => code_unit = code_unit * 16 + digit,
+                None => return Err(self.error("Invalid unicode escape")),
+            }
+        }
+
+        Ok(code_unit)
+    }
+
+    fn array(&mut self) -> Result<Value, RuntimeError> {
+        self.advance();
+        let mut elements = Vec::new();
+
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.advance();
+            return Ok(Value::Array(Rc::new(RefCell::new(elements))));
+        }
+
+        loop {
+            elements.push(self.value()?);
+            self.skip_whitespace();
+
+            match self.advance() {
+                Some(',') => {}
+                Some(']') => return Ok(Value::Array(Rc::new(RefCell::new(elements)))),
+                _ => return Err(self.error("Expected ',' or ']' in array")),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Value, RuntimeError> {
+        self.advance();
+        let mut entries = Map::new();
+
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.advance();
+            return Ok(Value::Map(Rc::new(RefCell::new(entries))));
+        }
+
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('"') {
+                return Err(self.error("Expected string key in object"));
+            }
+            let key = self.string()?;
+
+            self.skip_whitespace();
+            if self.advance() != Some(':') {
+                return Err(self.error("Expected ':' after object key"));
+            }
+            let value = self.value()?;
+
+            entries.insert(HashableValue::String(key), value);
+
+            self.skip_whitespace();
+            match self.advance() {
+                Some(',') => {}
+                Some('}') => return Ok(Value::Map(Rc::new(RefCell::new(entries)))),
+                _ => return Err(self.error("Expected ',' or '}' in object")),
+            }
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() {
+                self.advance();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.current).copied()
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let c = self.peek();
+        if c.is_some() {
+            self.current += 1;
+        }
+        c
+    }
+
+    fn error(&self, message: &str) -> RuntimeError {
+        RuntimeError::ArgumentError(format!("Invalid JSON: {} at position {}", message, self.current))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_of_nested_structure() {
+        let source = r#"{"name":"lox","tags":["fast",true,null],"nested":{"pi":3.14,"quote":"say \"hi\"\n"}}"#;
+
+        let value = parse(source).unwrap();
+        assert_eq!(stringify(&value).unwrap(), source);
+
+        match &value {
+            Value::Map(entries) => {
+                let entries = entries.borrow();
+                assert_eq!(entries.len(), 3);
+                assert_eq!(
+                    entries.entries()[0],
+                    (Value::String("name".to_string()), Value::String("lox".to_string()))
+                );
+            }
+            other => panic!("Expected a map, but got {}", other),
+        }
+    }
+
+    #[test]
+    fn test_parsing_scalars_and_whitespace() {
+        assert_eq!(parse(" 42 ").unwrap(), Value::Number(42.0));
+        assert_eq!(parse("-1.5e2").unwrap(), Value::Number(-150.0));
+        assert_eq!(parse("\"\\u00e9\"").unwrap(), Value::String("é".to_string()));
+        assert!(parse("[1, 2").is_err());
+        assert!(parse("1 2").is_err());
+    }
+
+    #[test]
+    fn test_stringifying_a_function_errors() {
+        let native = Value::NativeFunction(Rc::new(crate::vm::NativeFunction {
+            name: "native".to_string(),
+            arity: crate::vm::Arity::Exact(0),
+            function: Rc::new(|_, _| Ok(Value::Nil)),
+        }));
+
+        assert!(stringify(&native).is_err());
+        assert!(stringify(&Value::Number(f64::NAN)).is_err());
+    }
+
+    #[test]
+    fn test_high_surrogate_must_be_followed_by_a_low_surrogate() {
+        assert_eq!(parse("\"\\ud83d\\ude00\"").unwrap(), Value::String("😀".to_string()));
+        assert!(parse("\"\\ud83d\\u0041\"").is_err());
+        assert!(parse("\"\\ud83d\\ud83d\"").is_err());
+    }
+
+    #[test]
+    fn test_stringifying_circular_structures_errors() {
+        let elements = Rc::new(RefCell::new(Vec::new()));
+        let array = Value::Array(elements.clone());
+        elements.borrow_mut().push(array.clone());
+        assert_eq!(
+            stringify(&array).err().unwrap().to_string(),
+            "Cannot convert circular structure to JSON"
+        );
+
+        let map = Rc::new(RefCell::new(Map::new()));
+        map.borrow_mut()
+            .insert(HashableValue::String("self".to_string()), Value::Map(map.clone()));
+        assert!(stringify(&Value::Map(map.clone())).is_err());
+
+        // Break the cycles so the test doesn't leak them
+        elements.borrow_mut().clear();
+        *map.borrow_mut() = Map::new();
+
+        // The same array appearing twice side by side isn't circular
+        let shared = Value::Array(Rc::new(RefCell::new(vec![Value::Number(1.0)])));
+        let outer = Value::Array(Rc::new(RefCell::new(vec![shared.clone(), shared])));
+        assert_eq!(stringify(&outer).unwrap(), "[[1],[1]]");
+    }
+
+    #[test]
+    fn test_numbers_with_leading_zeros_are_rejected() {
+        assert!(parse("01").is_err());
+        assert!(parse("-01").is_err());
+        assert_eq!(parse("0").unwrap(), Value::Number(0.0));
+        assert_eq!(parse("-0.5").unwrap(), Value::Number(-0.5));
+        assert_eq!(parse("10").unwrap(), Value::Number(10.0));
+    }
+}
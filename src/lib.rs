@@ -0,0 +1,21 @@
+pub mod ast;
+pub mod diagnostic;
+pub mod environment;
+mod json;
+pub mod map;
+mod natives;
+pub mod parser;
+pub mod repl;
+pub mod resolver;
+pub mod scanner;
+pub mod token;
+pub mod visitor;
+pub mod vm;
+
+use vm::{RuntimeError, Value, Vm};
+
+/// Runs `source` in a new `Vm`, returning the value of its last statement when that statement is an expression and
+/// `nil` otherwise. Scan, parse and resolve errors are returned as a single error listing all of them.
+pub fn run_source(source: &str) -> Result<Value, RuntimeError> {
+    Vm::new().eval(source)
+}
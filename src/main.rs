@@ -1,35 +1,187 @@
-use std::io::Write;
+use std::{
+    io::Write,
+    time::{Duration, Instant},
+};
 
-use crate::ast::Stmt;
-use ast::Statement;
-use scanner::Scanner;
-use vm::Vm;
+use rlox::{
+    ast::{Statement, Stmt},
+    diagnostic, parser, repl,
+    resolver::Resolver,
+    scanner::Scanner,
+    token, visitor,
+    vm::{RuntimeError, Vm},
+};
 
-mod ast;
-mod environment;
-mod parser;
-mod scanner;
-mod token;
-mod visitor;
-mod vm;
+/// Size of the stack the interpreter runs on. Each Lox call takes several Rust frames, so the VM's default stack limit,
+/// sized for a 2MB thread, is raised to let programs recurse up to the call depth limit.
+const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+const DEFAULT_PROMPT: &str = "ilox> ";
+
+/// Command line flags that affect how each piece of source is run.
+#[derive(Clone, Copy, Default)]
+struct Options {
+    /// Enables the resolver's strict checks, while the `Vm` is made strict about comparing mismatched types.
+    strict: bool,
+    asi: bool,
+    time: bool,
+    /// Report every scan and parse error together, in source order, instead of stopping after the first failing phase.
+    all_errors: bool,
+}
+
+/// A scan, parse or resolve error with the position it's reported at. Resolve errors only know their line, so they
+/// are reported at its first column.
+#[derive(Debug)]
+struct Diagnostic {
+    line: usize,
+    column: usize,
+    /// Where to point the source snippet at. Scan and resolve errors are reported without one, as they are in the
+    /// default mode.
+    offset: Option<usize>,
+    message: String,
+}
+
+/// Why a piece of source didn't run to completion.
+#[derive(Debug)]
+enum RunError {
+    /// Every scan and parse error, sorted by position, when running with `--all-errors`, or every resolve error.
+    Diagnostics(Vec<Diagnostic>),
+    Runtime(RuntimeError),
+}
+
+/// How long each phase of running a piece of source took, reported on stderr with `--time`.
+#[derive(Default)]
+struct Timings {
+    scan: Duration,
+    parse: Duration,
+    execute: Duration,
+}
+
+impl std::fmt::Display for Timings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Scan: {:?}, parse: {:?}, execute: {:?}",
+            self.scan, self.parse, self.execute
+        )
+    }
+}
+
+/// Runs `phase`, adding the time it took to `duration`.
+fn timed<T>(duration: &mut Duration, phase: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = phase();
+    *duration += start.elapsed();
+    result
+}
 
 fn main() {
-    let mut args = std::env::args();
+    let interpreter = std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(interpret)
+        .expect("Failed to start the interpreter thread");
+
+    if interpreter.join().is_err() {
+        std::process::exit(101);
+    }
+}
 
-    match args.nth(1) {
+fn interpret() {
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+    let options = Options {
+        strict: take_flag(&mut args, "--strict"),
+        asi: take_flag(&mut args, "--asi"),
+        time: take_flag(&mut args, "--time"),
+        all_errors: take_flag(&mut args, "--all-errors"),
+    };
+    let quiet = take_flag(&mut args, "--quiet");
+    let mut vm = Vm::new()
+        .with_stack_limit(STACK_SIZE / 2)
+        .strict(options.strict)
+        .sandbox(take_flag(&mut args, "--sandbox"));
+    let eval = take_value(&mut args, "--eval", "source");
+    let prompt = take_value(&mut args, "--prompt", "prompt").unwrap_or_else(|| DEFAULT_PROMPT.to_string());
+    let mut args = args.into_iter();
+
+    if let Some(source) = eval {
+        let mut timings = Timings::default();
+        let result = run(&source, &args.next(), options, false, &mut vm, &mut timings);
+        report_timings(options.time, &timings);
+        result.unwrap_or_else(|err| exit_with_error(&err, &source));
+        return;
+    }
+
+    match args.next() {
         Some(arg) if arg == "--help" => print_help(),
-        Some(arg) if !arg.starts_with("--") => run_file(arg),
-        Some(arg) => run_interactively(Some(arg)),
-        None => run_interactively(None),
+        Some(arg) if !arg.starts_with("--") => run_file(arg, options, vm),
+        arg => {
+            if !quiet {
+                println!("rlox {} (type 'exit' to quit)", env!("CARGO_PKG_VERSION"));
+            }
+            run_interactively(arg, &prompt, options, vm)
+        }
+    }
+}
+
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    let count = args.len();
+    args.retain(|arg| arg != flag);
+    args.len() != count
+}
+
+/// Removes `flag` and the value following it from the arguments, returning the value.
+fn take_value(args: &mut Vec<String>, flag: &str, name: &str) -> Option<String> {
+    let index = args.iter().position(|arg| arg == flag)?;
+
+    if index + 1 >= args.len() {
+        eprintln!("Missing {} after {}", name, flag);
+        std::process::exit(1);
+    }
+
+    args.remove(index);
+    Some(args.remove(index))
+}
+
+fn report_timings(time: bool, timings: &Timings) {
+    if time {
+        eprintln!("{}", timings);
     }
 }
 
+/// Prints the errors that stopped `code` from running, each followed by the part of `code` it points at when known.
+fn report_error(err: &RunError, code: &str) {
+    match err {
+        RunError::Diagnostics(diagnostics) => {
+            for diagnostic in diagnostics {
+                match diagnostic.offset {
+                    Some(offset) => eprintln!("{}\n{}", diagnostic.message, diagnostic::render(code, offset)),
+                    None => eprintln!("{}", diagnostic.message),
+                }
+            }
+        }
+        RunError::Runtime(err) => {
+            eprintln!("Runtime error: {}", err);
+            if let Some(line) = err.line().and_then(|line| diagnostic::render_line(code, line)) {
+                eprintln!("{}", line);
+            }
+        }
+    }
+}
+
+fn exit_with_error(err: &RunError, code: &str) -> ! {
+    report_error(err, code);
+    std::process::exit(1);
+}
+
 fn print_help() {
-    println!("Usage: [file_path] [--print-tokens | --print-ast]");
+    println!(
+        "Usage: [file_path] [--print-tokens | --print-ast | --print-ast=json] \
+         [--strict] [--sandbox] [--asi] [--eval source] [--prompt prompt] [--quiet] [--time] \
+         [--all-errors]"
+    );
 }
 
-fn run_file(path: String) {
-    let mut vm = Vm::new();
+fn run_file(path: String, options: Options, mut vm: Vm) {
     let contents = match std::fs::read_to_string(&path) {
         Ok(contents) => contents,
         Err(err) => {
@@ -37,26 +189,43 @@ fn run_file(path: String) {
             std::process::exit(1);
         }
     };
-    run(contents, &None, &mut vm);
+    let mut timings = Timings::default();
+    let result = run(&contents, &None, options, false, &mut vm, &mut timings);
+    report_timings(options.time, &timings);
+    result.unwrap_or_else(|err| exit_with_error(&err, &contents));
 }
 
-fn run_interactively(arg: Option<String>) {
-    let mut vm = Vm::new();
+fn run_interactively(arg: Option<String>, prompt: &str, options: Options, mut vm: Vm) {
+    let mut input = String::new();
 
     loop {
-        print!("ilox> ");
+        print!("{}", if input.is_empty() { prompt } else { "....> " });
         std::io::stdout().flush().unwrap();
-        let mut input = String::new();
+        let mut line = String::new();
 
-        match std::io::stdin().read_line(&mut input) {
+        match std::io::stdin().read_line(&mut line) {
             Ok(0) => break, // EOF
             Ok(_) => {
-                let command = input.trim();
-                if command == "exit" || command == "quit" {
+                let command = line.trim();
+                if input.is_empty() && (command == "exit" || command == "quit") {
                     break;
                 }
 
-                run(input, &arg, &mut vm);
+                // Keep accumulating lines while the input is incomplete, unless a blank line forces evaluation
+                input.push_str(&line);
+                if !command.is_empty() && repl::needs_more_input(&input) {
+                    continue;
+                }
+
+                // Runtime and resolve errors, and with `--all-errors` syntax errors too, are reported without ending
+                // the session, keeping everything defined so far
+                let mut timings = Timings::default();
+                let code = std::mem::take(&mut input);
+                let result = run(&code, &arg, options, true, &mut vm, &mut timings);
+                report_timings(options.time, &timings);
+                if let Err(err) = result {
+                    report_error(&err, &code);
+                }
             }
             Err(err) => {
                 eprintln!("Error reading input: {}", err);
@@ -66,21 +235,37 @@ fn run_interactively(arg: Option<String>) {
     }
 }
 
-fn run(code: String, arg: &Option<String>, vm: &mut Vm) {
+fn run(
+    code: &str,
+    arg: &Option<String>,
+    options: Options,
+    interactive: bool,
+    vm: &mut Vm,
+    timings: &mut Timings,
+) -> Result<(), RunError> {
     let mut errors: Vec<String> = Vec::new();
 
     // Scanning
-    let tokens = scan(code, &mut errors);
+    let mut scanner = Scanner::new(code, &mut errors);
+    timed(&mut timings.scan, || scanner.scan());
+    let mut diagnostics = scan_diagnostics(&scanner, options.all_errors);
     match arg {
-        Some(arg) if arg == "--print-tokens" => {
-            println!("{:?}", tokens);
-            return;
+        Some(arg) if arg == "--print-tokens" && diagnostics.is_empty() => {
+            println!("{}", token::format_tokens(scanner.tokens()));
+            return Ok(());
         }
         _ => {}
     }
 
-    // Parsing
-    let statements = parse(tokens, errors);
+    // Parsing, which with `--all-errors` goes on after scan errors so both are reported together
+    let statements = timed(&mut timings.parse, || {
+        parse(code, &scanner, options, interactive, &mut diagnostics)
+    });
+    if !diagnostics.is_empty() {
+        diagnostics.sort_by_key(|diagnostic| (diagnostic.line, diagnostic.column));
+        return Err(RunError::Diagnostics(diagnostics));
+    }
+
     match arg {
         Some(arg) if arg == "--print-ast" => {
             let formatted = statements
@@ -90,45 +275,173 @@ fn run(code: String, arg: &Option<String>, vm: &mut Vm) {
                 .join("\n");
 
             println!("=> {}", formatted);
-            return;
+            return Ok(());
+        }
+        Some(arg) if arg == "--print-ast=json" => {
+            let formatted = statements
+                .iter()
+                .map(|stmt| stmt.accept(&mut visitor::JsonPrinter))
+                .collect::<Vec<_>>()
+                .join(",");
+
+            println!("[{}]", formatted);
+            return Ok(());
         }
         _ => {}
     }
 
-    for statement in statements {
-        statement.accept(vm).unwrap_or_else(|err| {
-            eprintln!("Runtime error: {}", err);
-            std::process::exit(1);
-        });
+    // Resolving
+    resolve(&statements, options.strict)?;
+
+    // A failing input leaves no half-applied definitions behind, so the REPL can carry on from the previous state
+    timed(&mut timings.execute, || vm.execute_atomically(&statements)).map_err(RunError::Runtime)
+}
+
+/// Reports scan errors and exits, unless every error is being collected, in which case they are returned instead.
+fn scan_diagnostics(scanner: &Scanner, all_errors: bool) -> Vec<Diagnostic> {
+    if !all_errors && !scanner.scan_errors().is_empty() {
+        for error in scanner.scan_errors() {
+            eprintln!("Scanning error: {}", error);
+        }
+        std::process::exit(1);
     }
+
+    scanner
+        .scan_errors()
+        .iter()
+        .map(|error| Diagnostic {
+            line: error.line,
+            column: error.column,
+            offset: None,
+            message: format!("Scanning error: {}", error),
+        })
+        .collect()
 }
 
-fn parse(tokens: Vec<token::Token>, mut errors: Vec<String>) -> Vec<Statement> {
-    let mut parser = parser::Parser::new(tokens, &mut errors);
+/// Parses the scanned tokens, reporting parse errors and exiting unless every error is being collected, in which case
+/// they are added to `diagnostics` instead.
+fn parse(
+    code: &str,
+    scanner: &Scanner,
+    options: Options,
+    interactive: bool,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Vec<Statement> {
+    let mut errors = Vec::new();
+    let mut parser = parser::Parser::new(scanner.tokens(), &mut errors)
+        .asi(options.asi)
+        .interactive(interactive);
     let statements = parser.parse();
 
-    if !errors.is_empty() {
-        for error in errors {
-            eprintln!("Parse error: {}", error);
+    // Point right after the last token read, which is where the parser expected something else
+    let spans = scanner.spans();
+    for error in parser.parse_errors() {
+        let offset = error.token.map_or(0, |token| spans[token].end);
+
+        if options.all_errors {
+            diagnostics.push(Diagnostic {
+                line: error.line,
+                column: error.token.map_or(1, |token| {
+                    scanner.column(token) + code[spans[token].clone()].chars().count()
+                }),
+                offset: Some(offset),
+                message: format!("Parse error: {}", error),
+            });
+        } else {
+            eprintln!("Parse error: {}\n{}", error, diagnostic::render(code, offset));
         }
+    }
+
+    if !options.all_errors && !parser.parse_errors().is_empty() {
         std::process::exit(1);
     }
     statements
 }
 
-fn scan(code: String, errors: &mut Vec<String>) -> Vec<token::Token> {
-    let tokens = {
-        let mut scanner = Scanner::new(&code, errors);
-        scanner.scan();
-        scanner.into_tokens()
-    };
+/// Prints the resolver's warnings, returning its errors, if any, as diagnostics.
+fn resolve(statements: &[Statement], strict: bool) -> Result<(), RunError> {
+    let mut errors: Vec<String> = Vec::new();
+    let mut resolver = Resolver::new(&mut errors).strict(strict);
+    resolver.resolve(statements);
 
-    if !errors.is_empty() {
-        for error in &*errors {
-            eprintln!("Scanning error: {}", error);
+    for warning in resolver.warnings() {
+        eprintln!("{}", warning);
+    }
+
+    if errors.is_empty() {
+        return Ok(());
+    }
+
+    let diagnostics = errors
+        .into_iter()
+        .map(|error| Diagnostic {
+            line: line_of(&error).unwrap_or(1),
+            column: 1,
+            offset: None,
+            message: format!("Resolve error: {}", error),
+        })
+        .collect();
+    Err(RunError::Diagnostics(diagnostics))
+}
+
+/// The line a resolve error was reported at, taken from the `[line N]` prefix of its message.
+fn line_of(error: &str) -> Option<usize> {
+    let (line, _) = error.strip_prefix("[line ")?.split_once(']')?;
+    line.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timing_each_phase() {
+        let mut timings = Timings::default();
+        let source = "fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); } var x = fib(10);";
+        run(source, &None, Options::default(), false, &mut Vm::new(), &mut timings).unwrap();
+
+        assert!(timings.scan > Duration::ZERO);
+        assert!(timings.parse > Duration::ZERO);
+        assert!(timings.execute > Duration::ZERO);
+        assert!(timings.to_string().starts_with("Scan: "));
+    }
+
+    fn collect_diagnostics(source: &str) -> Vec<Diagnostic> {
+        let options = Options {
+            all_errors: true,
+            ..Options::default()
+        };
+        let result = run(source, &None, options, false, &mut Vm::new(), &mut Timings::default());
+
+        match result {
+            Err(RunError::Diagnostics(diagnostics)) => diagnostics,
+            other => panic!("Expected diagnostics, but got {:?}", other),
         }
-        std::process::exit(1);
     }
 
-    tokens
+    #[test]
+    fn test_collecting_scan_and_parse_errors_in_order() {
+        let diagnostics = collect_diagnostics("var a = 1; @\nvar b = 2;\nprint ;");
+        let messages = diagnostics.iter().map(|d| d.message.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                "Scanning error: Unexpected character '@' at line 1",
+                "Parse error: [line 3] Error at ';': Expected expression."
+            ]
+        );
+        assert_eq!((diagnostics[1].line, diagnostics[1].column), (3, 6));
+
+        // Errors are sorted by position rather than grouped by phase
+        let diagnostics = collect_diagnostics("print 1;\nprint ;\nvar a = 1; @");
+        let positions = diagnostics.iter().map(|d| (d.line, d.column)).collect::<Vec<_>>();
+        assert_eq!(positions, vec![(2, 6), (3, 12)]);
+    }
+
+    #[test]
+    fn test_each_error_is_collected_once() {
+        let diagnostics = collect_diagnostics("print 1");
+        let messages = diagnostics.iter().map(|d| d.message.as_str()).collect::<Vec<_>>();
+        assert_eq!(messages, vec!["Parse error: [line 1] Error: Expected ';' after value."]);
+    }
 }
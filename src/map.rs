@@ -0,0 +1,114 @@
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+use crate::vm::{HashableValue, Value};
+
+/// An insertion-ordered map backing `Value::Map`. Entries live in a vector and their positions are indexed by the hash
+/// of their keys. Hashing and comparing instance keys calls their `hash` and `equals` methods, so lookups by arbitrary
+/// keys go through the `Vm`, while scalar keys can be used directly.
+#[derive(Debug, Default, Clone)]
+pub struct Map {
+    entries: Vec<(Value, Value)>,
+    buckets: HashMap<u64, Vec<usize>>,
+}
+
+impl Map {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Entries in insertion order.
+    pub fn entries(&self) -> &[(Value, Value)] {
+        &self.entries
+    }
+
+    pub fn get(&self, key: &HashableValue) -> Option<&Value> {
+        self.position(key).map(|position| &self.entries[position].1)
+    }
+
+    pub fn get_mut(&mut self, key: &HashableValue) -> Option<&mut Value> {
+        self.position(key).map(|position| &mut self.entries[position].1)
+    }
+
+    /// Inserts a value under a scalar key, replacing the existing value while keeping the entry's position.
+    pub fn insert(&mut self, key: HashableValue, value: Value) {
+        match self.position(&key) {
+            Some(position) => self.entries[position].1 = value,
+            None => self.push(hash_scalar(&key), key.to_value(), value),
+        }
+    }
+
+    fn position(&self, key: &HashableValue) -> Option<usize> {
+        self.candidates(hash_scalar(key))
+            .into_iter()
+            .find(|position| HashableValue::new(&self.entries[*position].0).as_ref() == Some(key))
+    }
+
+    /// Positions of the entries whose keys hash to `hash`, which still have to be compared with the key looked up.
+    pub(crate) fn candidates(&self, hash: u64) -> Vec<usize> {
+        self.buckets.get(&hash).cloned().unwrap_or_default()
+    }
+
+    pub(crate) fn key(&self, position: usize) -> &Value {
+        &self.entries[position].0
+    }
+
+    pub(crate) fn set(&mut self, position: usize, value: Value) {
+        self.entries[position].1 = value;
+    }
+
+    /// Appends an entry for a key that is known not to be in the map yet.
+    pub(crate) fn push(&mut self, hash: u64, key: Value, value: Value) {
+        self.buckets.entry(hash).or_default().push(self.entries.len());
+        self.entries.push((key, value));
+    }
+}
+
+/// Hashes a string, number, boolean or `nil` key. Instances defining `hash` are hashed by passing the number their
+/// method returns through here too, so an instance can share a bucket with a number key without being equal to it.
+pub(crate) fn hash_scalar(key: &HashableValue) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(s: &str) -> HashableValue {
+        HashableValue::String(s.to_string())
+    }
+
+    #[test]
+    fn test_inserting_preserves_order_and_replaces_values() {
+        let mut map = Map::new();
+        map.insert(key("b"), Value::Number(1.0));
+        map.insert(key("a"), Value::Number(2.0));
+        map.insert(HashableValue::Number(0.0), Value::Nil);
+        map.insert(key("b"), Value::Number(3.0));
+
+        assert_eq!(map.len(), 3);
+        assert_eq!(
+            map.entries(),
+            &[
+                (Value::String("b".to_string()), Value::Number(3.0)),
+                (Value::String("a".to_string()), Value::Number(2.0)),
+                (Value::Number(0.0), Value::Nil),
+            ]
+        );
+        assert_eq!(map.get(&key("a")), Some(&Value::Number(2.0)));
+        assert_eq!(map.get(&key("c")), None);
+        assert_eq!(map.get(&HashableValue::Number(-0.0)), Some(&Value::Nil));
+    }
+}
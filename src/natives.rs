@@ -0,0 +1,679 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use crate::{
+    json,
+    map::Map,
+    vm::{Arity, HashableValue, NativeFunction, RuntimeError, Value, Vm},
+};
+
+pub fn all() -> Vec<NativeFunction> {
+    vec![
+        NativeFunction {
+            name: "group".to_string(),
+            arity: Arity::Exact(1),
+            function: Rc::new(group),
+        },
+        NativeFunction {
+            name: "to_map".to_string(),
+            arity: Arity::Exact(1),
+            function: Rc::new(to_map),
+        },
+        NativeFunction {
+            name: "json_stringify".to_string(),
+            arity: Arity::Exact(1),
+            function: Rc::new(json_stringify),
+        },
+        NativeFunction {
+            name: "json_parse".to_string(),
+            arity: Arity::Exact(1),
+            function: Rc::new(json_parse),
+        },
+        NativeFunction {
+            name: "len".to_string(),
+            arity: Arity::Exact(1),
+            function: Rc::new(len),
+        },
+        NativeFunction {
+            name: "eval".to_string(),
+            arity: Arity::Exact(1),
+            function: Rc::new(eval),
+        },
+        NativeFunction {
+            name: "format".to_string(),
+            arity: Arity::AtLeast(1),
+            function: Rc::new(format),
+        },
+        NativeFunction {
+            name: "ord".to_string(),
+            arity: Arity::Exact(1),
+            function: Rc::new(ord),
+        },
+        NativeFunction {
+            name: "chr".to_string(),
+            arity: Arity::Exact(1),
+            function: Rc::new(chr),
+        },
+        NativeFunction {
+            name: "substr".to_string(),
+            arity: Arity::Exact(3),
+            function: Rc::new(substr),
+        },
+        NativeFunction {
+            name: "clone".to_string(),
+            arity: Arity::Exact(1),
+            function: Rc::new(clone),
+        },
+        NativeFunction {
+            name: "str".to_string(),
+            arity: Arity::Exact(1),
+            function: Rc::new(str),
+        },
+        NativeFunction {
+            name: "num".to_string(),
+            arity: Arity::Exact(1),
+            function: Rc::new(num),
+        },
+        NativeFunction {
+            name: "bool".to_string(),
+            arity: Arity::Exact(1),
+            function: Rc::new(bool),
+        },
+        NativeFunction {
+            name: "typeof".to_string(),
+            arity: Arity::Exact(1),
+            function: Rc::new(type_of),
+        },
+        NativeFunction {
+            name: "sqrt".to_string(),
+            arity: Arity::Exact(1),
+            function: Rc::new(sqrt),
+        },
+        NativeFunction {
+            name: "floor".to_string(),
+            arity: Arity::Exact(1),
+            function: Rc::new(floor),
+        },
+        NativeFunction {
+            name: "ceil".to_string(),
+            arity: Arity::Exact(1),
+            function: Rc::new(ceil),
+        },
+        NativeFunction {
+            name: "abs".to_string(),
+            arity: Arity::Exact(1),
+            function: Rc::new(abs),
+        },
+        NativeFunction {
+            name: "max".to_string(),
+            arity: Arity::AtLeast(1),
+            function: Rc::new(max),
+        },
+        NativeFunction {
+            name: "clock".to_string(),
+            arity: Arity::Exact(0),
+            function: Rc::new(clock),
+        },
+        NativeFunction {
+            name: "read_file".to_string(),
+            arity: Arity::Exact(1),
+            function: Rc::new(read_file),
+        },
+        NativeFunction {
+            name: "write_file".to_string(),
+            arity: Arity::Exact(2),
+            function: Rc::new(write_file),
+        },
+    ]
+}
+
+/// Formats a number with comma thousands separators, e.g. `group(1234567.5)` is `"1,234,567.5"`. Only the integer
+/// part is grouped, the fractional part is appended as is.
+fn group(_vm: &mut Vm, arguments: &[Value]) -> Result<Value, RuntimeError> {
+    let number = match &arguments[0] {
+        Value::Number(n) => *n,
+        other => {
+            return Err(RuntimeError::ArgumentError(format!(
+                "Expected number, but got {}",
+                other
+            )));
+        }
+    };
+
+    let formatted = number.abs().to_string();
+    let (integer, fraction) = match formatted.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (formatted.as_str(), None),
+    };
+
+    let mut result = String::new();
+    if number.is_sign_negative() && number != 0.0 {
+        result.push('-');
+    }
+
+    for (index, digit) in integer.chars().enumerate() {
+        if index > 0 && (integer.len() - index) % 3 == 0 {
+            result.push(',');
+        }
+        result.push(digit);
+    }
+
+    if let Some(fraction) = fraction {
+        result.push('.');
+        result.push_str(fraction);
+    }
+
+    Ok(Value::String(result))
+}
+
+/// Returns a map of an instance's field names to their values, sorted by name. Methods are not included.
+fn to_map(_vm: &mut Vm, arguments: &[Value]) -> Result<Value, RuntimeError> {
+    match &arguments[0] {
+        Value::Instance(instance) => {
+            let mut entries = instance
+                .borrow()
+                .fields()
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect::<Vec<_>>();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+            let mut map = Map::new();
+            for (name, value) in entries {
+                map.insert(HashableValue::String(name.to_string()), value);
+            }
+            Ok(Value::Map(Rc::new(RefCell::new(map))))
+        }
+        other => Err(RuntimeError::ArgumentError(format!(
+            "Expected instance, but got {}",
+            other
+        ))),
+    }
+}
+
+fn json_stringify(_vm: &mut Vm, arguments: &[Value]) -> Result<Value, RuntimeError> {
+    json::stringify(&arguments[0]).map(Value::String)
+}
+
+fn json_parse(_vm: &mut Vm, arguments: &[Value]) -> Result<Value, RuntimeError> {
+    match &arguments[0] {
+        Value::String(source) => json::parse(source),
+        other => Err(RuntimeError::ArgumentError(format!(
+            "Expected string, but got {}",
+            other
+        ))),
+    }
+}
+
+/// Returns the number of characters in a string or the number of elements in an array.
+fn len(_vm: &mut Vm, arguments: &[Value]) -> Result<Value, RuntimeError> {
+    match &arguments[0] {
+        Value::String(s) => Ok(Value::Number(s.chars().count() as f64)),
+        Value::Array(elements) => Ok(Value::Number(elements.borrow().len() as f64)),
+        other => Err(RuntimeError::ArgumentError(format!(
+            "Expected string or array, but got {}",
+            other
+        ))),
+    }
+}
+
+/// Runs a string of source with `Vm::eval`, which executes it in the global scope rather than the caller's.
+fn eval(vm: &mut Vm, arguments: &[Value]) -> Result<Value, RuntimeError> {
+    match &arguments[0] {
+        Value::String(source) => vm.eval(source),
+        other => Err(RuntimeError::ArgumentError(format!(
+            "Expected string, but got {}",
+            other
+        ))),
+    }
+}
+
+/// Substitutes the placeholders in a template with the remaining arguments. `{}` takes the next argument in order,
+/// counting only other `{}` placeholders, while `{n}` takes the argument at index `n`, so
+/// `format("{} {0} {}", "a", "b")` is `"a a b"`. Literal braces are written as `{{` and `}}`, and every argument
+/// must be used at least once.
+fn format(_vm: &mut Vm, arguments: &[Value]) -> Result<Value, RuntimeError> {
+    let template = match &arguments[0] {
+        Value::String(template) => template,
+        other => {
+            return Err(RuntimeError::ArgumentError(format!(
+                "Expected string, but got {}",
+                other
+            )));
+        }
+    };
+    let values = &arguments[1..];
+
+    let mut result = String::new();
+    let mut used = vec![false; values.len()];
+    let mut next_index = 0;
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                result.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                result.push('}');
+            }
+            '{' => {
+                let mut specifier = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => specifier.push(c),
+                        None => {
+                            return Err(RuntimeError::ArgumentError(
+                                "Unterminated placeholder in format".to_string(),
+                            ));
+                        }
+                    }
+                }
+
+                let index = if specifier.is_empty() {
+                    next_index += 1;
+                    next_index - 1
+                } else {
+                    specifier.parse::<usize>().map_err(|_| {
+                        RuntimeError::ArgumentError(format!("Invalid placeholder '{{{}}}' in format", specifier))
+                    })?
+                };
+
+                match values.get(index) {
+                    Some(value) => {
+                        result.push_str(&value.to_string());
+                        used[index] = true;
+                    }
+                    None => {
+                        return Err(RuntimeError::ArgumentError(format!(
+                            "Format index {} out of range for {} arguments",
+                            index,
+                            values.len()
+                        )));
+                    }
+                }
+            }
+            '}' => return Err(RuntimeError::ArgumentError("Unmatched '}' in format".to_string())),
+            c => result.push(c),
+        }
+    }
+
+    if let Some(index) = used.iter().position(|used| !used) {
+        return Err(RuntimeError::ArgumentError(format!(
+            "Format argument {} is never used",
+            index
+        )));
+    }
+
+    Ok(Value::String(result))
+}
+
+/// Returns the Unicode code point of a single-character string.
+fn ord(_vm: &mut Vm, arguments: &[Value]) -> Result<Value, RuntimeError> {
+    match &arguments[0] {
+        Value::String(s) => {
+            let mut chars = s.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Ok(Value::Number(c as u32 as f64)),
+                _ => Err(RuntimeError::ArgumentError(format!(
+                    "Expected a single character, but got \"{}\"",
+                    s
+                ))),
+            }
+        }
+        other => Err(RuntimeError::ArgumentError(format!(
+            "Expected string, but got {}",
+            other
+        ))),
+    }
+}
+
+/// Returns the single-character string for a Unicode code point.
+fn chr(_vm: &mut Vm, arguments: &[Value]) -> Result<Value, RuntimeError> {
+    match &arguments[0] {
+        Value::Number(n) => {
+            let c = if n.fract() == 0.0 && *n >= 0.0 && *n <= u32::MAX as f64 {
+                char::from_u32(*n as u32)
+            } else {
+                None
+            };
+
+            c.map(|c| Value::String(c.to_string()))
+                .ok_or_else(|| RuntimeError::ArgumentError(format!("Invalid code point {}", n)))
+        }
+        other => Err(RuntimeError::ArgumentError(format!(
+            "Expected number, but got {}",
+            other
+        ))),
+    }
+}
+
+/// Returns up to `length` characters of a string starting at character `start`. A length reaching past the end of the
+/// string is clamped, while a start past the end is an error.
+fn substr(_vm: &mut Vm, arguments: &[Value]) -> Result<Value, RuntimeError> {
+    let string = match &arguments[0] {
+        Value::String(string) => string,
+        other => {
+            return Err(RuntimeError::ArgumentError(format!(
+                "Expected string, but got {}",
+                other
+            )));
+        }
+    };
+    let start = arguments[1].as_index()?;
+    let length = arguments[2].as_index()?;
+
+    let count = string.chars().count();
+    if start > count {
+        return Err(RuntimeError::ArgumentError(format!(
+            "Start {} out of bounds for string of length {}",
+            start, count
+        )));
+    }
+
+    Ok(Value::String(string.chars().skip(start).take(length).collect()))
+}
+
+/// Copies arrays and maps recursively, so that mutating the copy doesn't affect the original. Other values, including
+/// instances, are returned as they are.
+fn clone(_vm: &mut Vm, arguments: &[Value]) -> Result<Value, RuntimeError> {
+    Ok(deep_clone(&arguments[0], &mut HashMap::new()))
+}
+
+/// Copies `value`, keeping the copies made so far by address in `copies`. An array or map reached more than once,
+/// including through a cycle, is copied once and stays shared within the copy.
+fn deep_clone(value: &Value, copies: &mut HashMap<usize, Value>) -> Value {
+    let address = match value {
+        Value::Array(elements) => Rc::as_ptr(elements) as *const () as usize,
+        Value::Map(map) => Rc::as_ptr(map) as *const () as usize,
+        other => return other.clone(),
+    };
+    if let Some(copy) = copies.get(&address) {
+        return copy.clone();
+    }
+
+    match value {
+        Value::Array(elements) => {
+            let copy = Rc::new(RefCell::new(Vec::new()));
+            copies.insert(address, Value::Array(copy.clone()));
+
+            let elements = elements
+                .borrow()
+                .iter()
+                .map(|element| deep_clone(element, copies))
+                .collect();
+            *copy.borrow_mut() = elements;
+            Value::Array(copy)
+        }
+        Value::Map(map) => {
+            let copy = Rc::new(RefCell::new(map.borrow().clone()));
+            copies.insert(address, Value::Map(copy.clone()));
+
+            let values = map
+                .borrow()
+                .entries()
+                .iter()
+                .map(|(_, value)| value.clone())
+                .collect::<Vec<_>>();
+            for (position, value) in values.iter().enumerate() {
+                let value = deep_clone(value, copies);
+                copy.borrow_mut().set(position, value);
+            }
+            Value::Map(copy)
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Converts any value to the string it prints as.
+fn str(_vm: &mut Vm, arguments: &[Value]) -> Result<Value, RuntimeError> {
+    Ok(Value::String(arguments[0].to_string()))
+}
+
+/// Parses a string into a number, ignoring surrounding whitespace.
+fn num(_vm: &mut Vm, arguments: &[Value]) -> Result<Value, RuntimeError> {
+    match &arguments[0] {
+        Value::String(s) => match s.trim().parse::<f64>() {
+            Ok(number) if number.is_finite() => Ok(Value::Number(number)),
+            _ => Err(RuntimeError::ArgumentError(format!(
+                "Cannot convert \"{}\" to a number",
+                s
+            ))),
+        },
+        other => Err(RuntimeError::ArgumentError(format!(
+            "Expected string, but got {}",
+            other
+        ))),
+    }
+}
+
+/// Returns whether a value is truthy, i.e. anything but `nil` and `false`.
+fn bool(vm: &mut Vm, arguments: &[Value]) -> Result<Value, RuntimeError> {
+    Ok(Value::Boolean(vm.truthy(&arguments[0])))
+}
+
+/// Returns the name of a value's type, such as `"number"` or `"instance"`.
+fn type_of(_vm: &mut Vm, arguments: &[Value]) -> Result<Value, RuntimeError> {
+    Ok(Value::String(arguments[0].type_name().to_string()))
+}
+
+fn number(value: &Value) -> Result<f64, RuntimeError> {
+    match value {
+        Value::Number(n) => Ok(*n),
+        other => Err(RuntimeError::ArgumentError(format!(
+            "Expected number, but got {}",
+            other
+        ))),
+    }
+}
+
+/// Returns the square root of a number. Negative numbers are an error instead of producing NaN.
+fn sqrt(_vm: &mut Vm, arguments: &[Value]) -> Result<Value, RuntimeError> {
+    let n = number(&arguments[0])?;
+    if n < 0.0 {
+        return Err(RuntimeError::ArgumentError(format!(
+            "Cannot take the square root of {}",
+            arguments[0]
+        )));
+    }
+
+    Ok(Value::Number(n.sqrt()))
+}
+
+fn floor(_vm: &mut Vm, arguments: &[Value]) -> Result<Value, RuntimeError> {
+    Ok(Value::Number(number(&arguments[0])?.floor()))
+}
+
+fn ceil(_vm: &mut Vm, arguments: &[Value]) -> Result<Value, RuntimeError> {
+    Ok(Value::Number(number(&arguments[0])?.ceil()))
+}
+
+fn abs(_vm: &mut Vm, arguments: &[Value]) -> Result<Value, RuntimeError> {
+    Ok(Value::Number(number(&arguments[0])?.abs()))
+}
+
+/// Returns the largest of one or more numbers.
+fn max(_vm: &mut Vm, arguments: &[Value]) -> Result<Value, RuntimeError> {
+    let mut result = number(&arguments[0])?;
+    for argument in &arguments[1..] {
+        result = result.max(number(argument)?);
+    }
+
+    Ok(Value::Number(result))
+}
+
+/// Returns the number of seconds elapsed since the Unix epoch, with sub-second precision.
+fn clock(_vm: &mut Vm, _arguments: &[Value]) -> Result<Value, RuntimeError> {
+    let elapsed = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|err| RuntimeError::ArgumentError(err.to_string()))?;
+
+    Ok(Value::Number(elapsed.as_secs_f64()))
+}
+
+/// Reads the whole file at a path into a string.
+fn read_file(vm: &mut Vm, arguments: &[Value]) -> Result<Value, RuntimeError> {
+    vm.check_sandbox("read_file")?;
+
+    match &arguments[0] {
+        Value::String(path) => std::fs::read_to_string(path)
+            .map(Value::String)
+            .map_err(|err| RuntimeError::ArgumentError(format!("Error reading file {}: {}", path, err))),
+        other => Err(RuntimeError::ArgumentError(format!(
+            "Expected string, but got {}",
+            other
+        ))),
+    }
+}
+
+/// Writes a string to the file at a path, replacing any existing contents.
+fn write_file(vm: &mut Vm, arguments: &[Value]) -> Result<Value, RuntimeError> {
+    vm.check_sandbox("write_file")?;
+
+    match (&arguments[0], &arguments[1]) {
+        (Value::String(path), Value::String(contents)) => std::fs::write(path, contents)
+            .map(|_| Value::Nil)
+            .map_err(|err| RuntimeError::ArgumentError(format!("Error writing file {}: {}", path, err))),
+        (Value::String(_), other) | (other, _) => Err(RuntimeError::ArgumentError(format!(
+            "Expected string, but got {}",
+            other
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_large_integer() {
+        let result = group(&mut Vm::new(), &[Value::Number(1234567.0)]).unwrap();
+        assert_eq!(result, Value::String("1,234,567".to_string()));
+
+        let result = group(&mut Vm::new(), &[Value::Number(-1234567.0)]).unwrap();
+        assert_eq!(result, Value::String("-1,234,567".to_string()));
+    }
+
+    #[test]
+    fn test_group_number_with_decimals() {
+        let result = group(&mut Vm::new(), &[Value::Number(1234.5)]).unwrap();
+        assert_eq!(result, Value::String("1,234.5".to_string()));
+    }
+
+    #[test]
+    fn test_group_small_number() {
+        let result = group(&mut Vm::new(), &[Value::Number(123.0)]).unwrap();
+        assert_eq!(result, Value::String("123".to_string()));
+    }
+
+    #[test]
+    fn test_group_non_number() {
+        assert!(group(&mut Vm::new(), &[Value::String("1234".to_string())]).is_err());
+    }
+
+    #[test]
+    fn test_len_of_strings_and_arrays() {
+        let result = len(&mut Vm::new(), &[Value::String("abc".to_string())]).unwrap();
+        assert_eq!(result, Value::Number(3.0));
+
+        let result = len(&mut Vm::new(), &[Value::String("héllo".to_string())]).unwrap();
+        assert_eq!(result, Value::Number(5.0));
+
+        let array = Value::Array(Rc::new(RefCell::new(vec![Value::Nil, Value::Boolean(true)])));
+        assert_eq!(len(&mut Vm::new(), &[array]).unwrap(), Value::Number(2.0));
+    }
+
+    #[test]
+    fn test_len_of_other_values() {
+        assert!(len(&mut Vm::new(), &[Value::Number(42.0)]).is_err());
+    }
+
+    fn string(value: &str) -> Value {
+        Value::String(value.to_string())
+    }
+
+    #[test]
+    fn test_format_with_positional_indices() {
+        let result = format(&mut Vm::new(), &[string("{1} {0}"), string("a"), string("b")]).unwrap();
+        assert_eq!(result, string("b a"));
+
+        let result = format(&mut Vm::new(), &[string("{0}{0}{{}}"), Value::Number(1.0)]).unwrap();
+        assert_eq!(result, string("11{}"));
+    }
+
+    #[test]
+    fn test_format_mixing_automatic_and_positional_placeholders() {
+        // Automatic placeholders only count each other, so positional ones don't advance them
+        let arguments = [string("{} {0} {} {1}"), string("a"), string("b")];
+        let result = format(&mut Vm::new(), &arguments).unwrap();
+        assert_eq!(result, string("a a b b"));
+    }
+
+    #[test]
+    fn test_format_errors() {
+        let result = format(&mut Vm::new(), &[string("{2}"), string("a"), string("b")]);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Format index 2 out of range for 2 arguments"
+        );
+
+        let result = format(&mut Vm::new(), &[string("{}"), string("a"), string("b")]);
+        assert_eq!(result.unwrap_err().to_string(), "Format argument 1 is never used");
+
+        assert!(format(&mut Vm::new(), &[string("{x}")]).is_err());
+        assert!(format(&mut Vm::new(), &[string("{")]).is_err());
+    }
+
+    #[test]
+    fn test_converting_between_characters_and_code_points() {
+        assert_eq!(ord(&mut Vm::new(), &[string("A")]).unwrap(), Value::Number(65.0));
+        assert_eq!(ord(&mut Vm::new(), &[string("é")]).unwrap(), Value::Number(233.0));
+        assert_eq!(chr(&mut Vm::new(), &[Value::Number(65.0)]).unwrap(), string("A"));
+        assert_eq!(chr(&mut Vm::new(), &[Value::Number(128512.0)]).unwrap(), string("😀"));
+    }
+
+    #[test]
+    fn test_invalid_character_conversions() {
+        assert!(ord(&mut Vm::new(), &[string("AB")]).is_err());
+        assert!(ord(&mut Vm::new(), &[string("")]).is_err());
+        assert!(ord(&mut Vm::new(), &[Value::Number(65.0)]).is_err());
+
+        let result = chr(&mut Vm::new(), &[Value::Number(55296.0)]);
+        assert_eq!(result.unwrap_err().to_string(), "Invalid code point 55296");
+        assert!(chr(&mut Vm::new(), &[Value::Number(-1.0)]).is_err());
+        assert!(chr(&mut Vm::new(), &[Value::Number(65.5)]).is_err());
+    }
+
+    #[test]
+    fn test_writing_and_reading_files() {
+        let path = std::env::temp_dir().join(format!("rlox_natives_{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let mut vm = Vm::new();
+        assert_eq!(
+            write_file(&mut vm, &[string(path), string("line 1\nline 2")]).unwrap(),
+            Value::Nil
+        );
+        assert_eq!(read_file(&mut vm, &[string(path)]).unwrap(), string("line 1\nline 2"));
+
+        std::fs::remove_file(path).unwrap();
+        assert!(read_file(&mut vm, &[string(path)]).is_err());
+    }
+
+    #[test]
+    fn test_file_natives_are_rejected_in_sandbox_mode() {
+        let path = std::env::temp_dir().join(format!("rlox_sandbox_{}.txt", std::process::id()));
+        let path = string(path.to_str().unwrap());
+        let mut vm = Vm::new().sandbox(true);
+
+        let result = write_file(&mut vm, &[path.clone(), string("contents")]);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "write_file is disabled in sandbox mode"
+        );
+        let result = read_file(&mut vm, &[path]);
+        assert_eq!(result.unwrap_err().to_string(), "read_file is disabled in sandbox mode");
+    }
+}
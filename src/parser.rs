@@ -1,38 +1,142 @@
+use std::{cell::Cell, rc::Rc};
+
 use crate::{
     ast::{
-        Assignment, Binary, BlockStatement, Expr, ExpressionStatement, Grouping, IfStatement, Literal, LiteralValue,
-        Logical, PrintStatement, Statement, Unary, Variable, VariableStatement, WhileStatement,
+        ArrayLiteral, AssertStatement, Assignment, Binary, BlockStatement, BreakStatement, Call, CatchClause,
+        ClassStatement, ContinueStatement, DoWhileStatement, Expr, ExpressionStatement, ForInStatement, FunctionExpr,
+        FunctionStatement, Get, Grouping, IfStatement, Index, IndexSet, Literal, LiteralValue, Logical, MapLiteral,
+        Postfix, PrintStatement, ReturnStatement, Set, Spread, Statement, Super, Ternary, This, ThrowStatement,
+        TryCatchStatement, Unary, Variable, VariableStatement, WhileStatement, WithStatement, Yield,
     },
-    token::Token,
+    token::{Identifier, Token},
 };
 
-pub enum ParseError {
-    ExpectedTokenError(String),
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParseErrorKind {
+    ExpectedToken,
+    InvalidAssignmentTarget,
+    YieldOutsideFunction,
+    YieldInExpression,
+    RestParameterNotLast,
+    DuplicateParameter,
+    UnclosedDelimiter,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub kind: ParseErrorKind,
+    pub message: String,
+    /// The lexeme the error is reported at, if any.
+    pub at: Option<String>,
+    /// Index of the last token read before the error was found, or `None` if it was found before reading any.
+    pub token: Option<usize>,
+}
+
+impl ParseError {
+    pub fn new(line: usize, kind: ParseErrorKind, message: impl Into<String>) -> Self {
+        ParseError {
+            line,
+            kind,
+            message: message.into(),
+            at: None,
+            token: None,
+        }
+    }
+
+    fn at(mut self, lexeme: &str) -> Self {
+        self.at = Some(lexeme.to_string());
+        self
+    }
 }
 
 impl std::fmt::Display for ParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            ParseError::ExpectedTokenError(msg) => write!(f, "{}", msg),
+        match &self.at {
+            Some(lexeme) => write!(f, "[line {}] Error at '{}': {}", self.line, lexeme, self.message),
+            None => write!(f, "[line {}] Error: {}", self.line, self.message),
         }
     }
 }
 
 pub struct Parser<'a> {
     current: usize,
-    tokens: Vec<Token>,
+    tokens: &'a [Token],
     errors: &'a mut Vec<String>,
+    /// The errors also pushed into `errors`, kept structured for tooling.
+    parse_errors: Vec<ParseError>,
+    asi: bool,
+    print_as_function: bool,
+    interactive: bool,
+    /// One entry per function being parsed, tracking whether its body contains a `yield`.
+    generators: Vec<bool>,
+    /// Set when a `--` was read as a subtraction, so the operand after it still has to be negated, as in `3--1`.
+    pending_negation: Option<Token>,
 }
 
 impl<'a> Parser<'a> {
-    pub fn new(tokens: Vec<Token>, errors: &'a mut Vec<String>) -> Self {
+    pub fn new(tokens: &'a [Token], errors: &'a mut Vec<String>) -> Self {
         Parser {
             current: 0,
             tokens,
             errors,
+            parse_errors: Vec::new(),
+            asi: false,
+            print_as_function: false,
+            interactive: false,
+            generators: Vec::new(),
+            pending_negation: None,
         }
     }
 
+    /// Enables automatic semicolon insertion, where a statement may also be ended by a line break, a `}` or the end
+    /// of the input instead of a `;`.
+    pub fn asi(mut self, asi: bool) -> Self {
+        self.asi = asi;
+        self
+    }
+
+    /// Parses `print` as a reference to a global `print` function instead of the keyword starting a print statement,
+    /// to be used with a `Vm` in the same mode.
+    pub fn print_as_function(mut self, print_as_function: bool) -> Self {
+        self.print_as_function = print_as_function;
+        self
+    }
+
+    /// Parses input typed at the REPL, where an expression ending the input may omit its `;` and is printed instead
+    /// of discarded. Only the input's own last statement qualifies: an expression ending a block, as in `{ 1 }`, still
+    /// needs its `;`.
+    pub fn interactive(mut self, interactive: bool) -> Self {
+        self.interactive = interactive;
+        self
+    }
+
+    /// The errors found while parsing so far, in the order they were reported.
+    pub fn parse_errors(&self) -> &[ParseError] {
+        &self.parse_errors
+    }
+
+    /// Error for a delimiter still open at the end of the input, pointing back at the line it was opened on.
+    fn unclosed(&self, delimiter: &str, opened_at: usize) -> ParseError {
+        ParseError::new(
+            self.previous_line(),
+            ParseErrorKind::UnclosedDelimiter,
+            format!("Unclosed '{}' opened at line {}.", delimiter, opened_at),
+        )
+    }
+
+    /// Adds the message of an error about to be returned to `parse` to `errors`, where it has always been listed
+    /// twice, once here and once when `parse` reports it. `parse_errors` only gets the error from `parse`.
+    fn error_message(&mut self, error: &ParseError) {
+        self.errors.push(error.to_string());
+    }
+
+    fn error(&mut self, mut error: ParseError) {
+        error.token = self.current.checked_sub(1);
+        self.errors.push(error.to_string());
+        self.parse_errors.push(error);
+    }
+
     pub fn parse(&mut self) -> Vec<Statement> {
         let mut statements: Vec<Statement> = Vec::new();
 
@@ -42,7 +146,7 @@ impl<'a> Parser<'a> {
                 _ => match self.declaration() {
                     Ok(statement) => statements.push(statement),
                     Err(e) => {
-                        self.errors.push(format!("{}", e));
+                        self.error(e);
                         self.synchronize();
                     }
                 },
@@ -52,23 +156,257 @@ impl<'a> Parser<'a> {
         statements
     }
 
+    /// Parses like `parse`, but allows the last statement to be an expression without a trailing `;`, as in
+    /// `eval("1 + 2")`.
+    pub fn parse_with_trailing_expression(&mut self) -> Vec<Statement> {
+        let mut statements: Vec<Statement> = Vec::new();
+
+        while let Some(token) = self.peek() {
+            let result = match token {
+                Token::Eof => break,
+                _ if self.starts_declaration() => self.declaration(),
+                _ => match self.trailing_expression() {
+                    Ok((value, terminated)) if terminated || matches!(self.peek(), Some(Token::Eof)) => {
+                        Ok(Statement::Expression(ExpressionStatement {
+                            expression: Box::new(value),
+                        }))
+                    }
+                    Ok(_) => Err(ParseError::new(
+                        self.previous_line(),
+                        ParseErrorKind::ExpectedToken,
+                        "Expected ';' after value.",
+                    )),
+                    Err(e) => Err(e),
+                },
+            };
+
+            match result {
+                Ok(statement) => statements.push(statement),
+                Err(e) => {
+                    self.error(e);
+                    self.synchronize();
+                }
+            }
+        }
+
+        statements
+    }
+
     fn declaration(&mut self) -> Result<Statement, ParseError> {
         match self.peek() {
+            Some(Token::Class { line: _ }) => {
+                self.advance();
+                self.class_declaration()
+            }
+            Some(Token::Fun { line: _ }) if self.starts_function_declaration() => {
+                self.advance();
+                Ok(Statement::Function(Rc::new(self.function("function")?)))
+            }
             Some(Token::Var { line: _ }) => {
                 self.advance();
-                self.var_declaration()
+                self.var_declaration(true)
+            }
+            Some(Token::Const { line: _ }) => {
+                self.advance();
+                self.var_declaration(false)
             }
             _ => self.statement(),
         }
     }
 
-    fn var_declaration(&mut self) -> Result<Statement, ParseError> {
+    fn class_declaration(&mut self) -> Result<Statement, ParseError> {
+        let name = match self.advance() {
+            Some(Token::Identifier(token)) => Ok(token.clone()),
+            other => Err(ParseError::new(
+                other.unwrap().line(),
+                ParseErrorKind::ExpectedToken,
+                "Expected class name.",
+            )),
+        }?;
+
+        let superclass = match self.peek() {
+            Some(Token::Less { line: _ }) => {
+                self.advance();
+                match self.advance() {
+                    Some(Token::Identifier(token)) => Some(Box::new(Variable {
+                        token: Box::new(token.clone()),
+                        depth: Cell::new(None),
+                    })),
+                    other => {
+                        return Err(ParseError::new(
+                            other.unwrap().line(),
+                            ParseErrorKind::ExpectedToken,
+                            "Expected superclass name.",
+                        ));
+                    }
+                }
+            }
+            _ => None,
+        };
+
+        match self.peek() {
+            Some(Token::LeftBrace { line: _ }) => {
+                self.advance();
+            }
+            _ => {
+                return Err(ParseError::new(
+                    name.line,
+                    ParseErrorKind::ExpectedToken,
+                    "Expected '{' before class body.",
+                ));
+            }
+        }
+
+        let mut methods = Vec::new();
+
+        while let Some(token) = self.peek() {
+            match token {
+                Token::RightBrace { line: _ } => {
+                    self.advance();
+                    return Ok(Statement::Class(ClassStatement {
+                        name: Box::new(name),
+                        superclass,
+                        methods,
+                    }));
+                }
+                Token::Eof => break,
+                _ => methods.push(Rc::new(self.function("method")?)),
+            }
+        }
+
+        Err(ParseError::new(
+            self.previous_line(),
+            ParseErrorKind::ExpectedToken,
+            "Expected '}' after class body.",
+        ))
+    }
+
+    fn function(&mut self, kind: &str) -> Result<FunctionStatement, ParseError> {
+        let name = match self.advance() {
+            Some(Token::Identifier(token)) => Ok(token.clone()),
+            other => Err(ParseError::new(
+                other.unwrap().line(),
+                ParseErrorKind::ExpectedToken,
+                format!("Expected {} name.", kind),
+            )),
+        }?;
+
+        self.function_signature_and_body(name, kind)
+    }
+
+    /// Parses the parameters and body of a function, shared by declarations and anonymous function expressions.
+    fn function_signature_and_body(&mut self, name: Identifier, kind: &str) -> Result<FunctionStatement, ParseError> {
+        match self.peek() {
+            Some(Token::LeftParen { line: _ }) => {
+                self.advance();
+            }
+            _ => {
+                return Err(ParseError::new(
+                    name.line,
+                    ParseErrorKind::ExpectedToken,
+                    format!("Expected '(' after {} name.", kind),
+                ));
+            }
+        }
+
+        let mut params: Vec<Identifier> = Vec::new();
+        let mut variadic = false;
+
+        if !matches!(self.peek(), Some(Token::RightParen { line: _ })) {
+            loop {
+                if variadic {
+                    return Err(ParseError::new(
+                        self.previous_line(),
+                        ParseErrorKind::RestParameterNotLast,
+                        "Rest parameter must be the last parameter.",
+                    ));
+                }
+
+                if let Some(Token::Ellipsis { line: _ }) = self.peek() {
+                    self.advance();
+                    variadic = true;
+                }
+
+                match self.advance().cloned() {
+                    Some(Token::Identifier(param)) => {
+                        if params.iter().any(|existing| existing.value == param.value) {
+                            self.error(ParseError::new(
+                                param.line,
+                                ParseErrorKind::DuplicateParameter,
+                                format!("Duplicate parameter name '{}'.", param.value),
+                            ));
+                        }
+                        params.push(param);
+                    }
+                    other => {
+                        return Err(ParseError::new(
+                            other.unwrap().line(),
+                            ParseErrorKind::ExpectedToken,
+                            "Expected parameter name.",
+                        ));
+                    }
+                }
+
+                match self.peek() {
+                    Some(Token::Comma { line: _ }) => {
+                        self.advance();
+                        // A trailing comma is allowed before the closing parenthesis
+                        if matches!(self.peek(), Some(Token::RightParen { line: _ })) {
+                            break;
+                        }
+                    }
+                    _ => break,
+                }
+            }
+        }
+
+        match self.peek() {
+            Some(Token::RightParen { line: _ }) => {
+                self.advance();
+            }
+            _ => {
+                return Err(ParseError::new(
+                    self.previous_line(),
+                    ParseErrorKind::ExpectedToken,
+                    "Expected ')' after parameters.",
+                ));
+            }
+        }
+
+        match self.peek() {
+            Some(Token::LeftBrace { line: _ }) => {
+                self.advance();
+            }
+            _ => {
+                return Err(ParseError::new(
+                    self.previous_line(),
+                    ParseErrorKind::ExpectedToken,
+                    format!("Expected '{{' before {} body.", kind),
+                ));
+            }
+        }
+
+        self.generators.push(false);
+        let body = self.function_body();
+        let is_generator = self.generators.pop().unwrap_or(false);
+
+        Ok(FunctionStatement {
+            name: Box::new(name),
+            params,
+            body: body?,
+            is_generator,
+            variadic,
+        })
+    }
+
+    fn var_declaration(&mut self, mutable: bool) -> Result<Statement, ParseError> {
         let identifier = match self.advance() {
             Some(Token::Identifier(token)) => Ok(token.clone()),
-            other => Err(ParseError::ExpectedTokenError(format!(
-                "[line {}] Error: Expected variable name.",
-                other.unwrap().line()
-            ))),
+            other => Err(ParseError::new(
+                other.unwrap().line(),
+                ParseErrorKind::ExpectedToken,
+                "Expected variable name.",
+            )),
         }?;
 
         let initializer = match self.peek() {
@@ -76,25 +414,26 @@ impl<'a> Parser<'a> {
                 self.advance();
                 Ok(self.expression())
             }
-            _ => Err(ParseError::ExpectedTokenError(format!(
-                "[line {}] Error: Expected '=' after variable name.",
-                identifier.line
-            ))),
+            _ => Err(ParseError::new(
+                identifier.line,
+                ParseErrorKind::ExpectedToken,
+                "Expected '=' after variable name.",
+            )),
         }?;
 
-        match self.peek() {
-            Some(Token::Semicolon { line: _ }) => {
-                self.advance();
-                Ok(Statement::Variable(VariableStatement {
-                    name: Box::new(identifier),
-                    value: Box::new(initializer),
-                }))
-            }
-            _ => Err(ParseError::ExpectedTokenError(format!(
-                "[line {}] Error: Expected ';' after variable declaration.",
-                identifier.line
-            ))),
+        if !self.match_semicolon() {
+            return Err(ParseError::new(
+                identifier.line,
+                ParseErrorKind::ExpectedToken,
+                "Expected ';' after variable declaration.",
+            ));
         }
+
+        Ok(Statement::Variable(VariableStatement {
+            name: Box::new(identifier),
+            value: Box::new(initializer),
+            mutable,
+        }))
     }
 
     fn statement(&mut self) -> Result<Statement, ParseError> {
@@ -107,14 +446,46 @@ impl<'a> Parser<'a> {
                 self.advance();
                 self.if_statement()
             }
-            Some(Token::Print { line: _ }) => {
+            Some(Token::Print { line: _ }) if !self.print_as_function => {
                 self.advance();
                 self.print_statement()
             }
+            Some(Token::Return { line: _ }) => {
+                self.advance();
+                self.return_statement()
+            }
             Some(Token::While { line: _ }) => {
                 self.advance();
                 self.while_statement()
             }
+            Some(Token::Do { line: _ }) => {
+                self.advance();
+                self.do_while_statement()
+            }
+            Some(Token::Break { line: _ }) => {
+                self.advance();
+                self.break_statement()
+            }
+            Some(Token::Continue { line: _ }) => {
+                self.advance();
+                self.continue_statement()
+            }
+            Some(Token::Assert { line: _ }) => {
+                self.advance();
+                self.assert_statement()
+            }
+            Some(Token::With { line: _ }) => {
+                self.advance();
+                self.with_statement()
+            }
+            Some(Token::Try { line: _ }) => {
+                self.advance();
+                self.try_statement()
+            }
+            Some(Token::Throw { line: _ }) => {
+                self.advance();
+                self.throw_statement()
+            }
             Some(Token::LeftBrace { line: _ }) => {
                 self.advance();
                 self.block()
@@ -127,6 +498,12 @@ impl<'a> Parser<'a> {
         if let Some(Token::LeftParen { line: _ }) = self.peek() {
             self.advance();
 
+            if let (Some(Token::Var { line: _ }), Some(Token::In { line: _ })) =
+                (self.peek(), self.tokens.get(self.current + 2))
+            {
+                return self.for_in_statement();
+            }
+
             let initializer = match self.peek() {
                 Some(Token::Semicolon { line: _ }) => {
                     self.advance();
@@ -134,7 +511,7 @@ impl<'a> Parser<'a> {
                 }
                 Some(Token::Var { line: _ }) => {
                     self.advance();
-                    Some(self.var_declaration()?)
+                    Some(self.var_declaration(true)?)
                 }
                 _ => Some(self.expression_statement()?),
             };
@@ -147,12 +524,13 @@ impl<'a> Parser<'a> {
             if let Some(Token::Semicolon { line: _ }) = self.peek() {
                 self.advance();
             } else {
-                let message = format!(
-                    "[line {}] Error: Expected ';' after for condition.",
-                    self.previous().unwrap().line()
+                let error = ParseError::new(
+                    self.previous_line(),
+                    ParseErrorKind::ExpectedToken,
+                    "Expected ';' after for condition.",
                 );
-                self.errors.push(message.clone());
-                return Err(ParseError::ExpectedTokenError(message));
+                self.error_message(&error);
+                return Err(error);
             }
 
             let increment = match self.peek() {
@@ -163,36 +541,29 @@ impl<'a> Parser<'a> {
             if let Some(Token::RightParen { line: _ }) = self.peek() {
                 self.advance();
             } else {
-                let message = format!(
-                    "[line {}] Error: Expected ')' after for loop increment.",
-                    self.previous().unwrap().line()
+                let error = ParseError::new(
+                    self.previous_line(),
+                    ParseErrorKind::ExpectedToken,
+                    "Expected ')' after for loop increment.",
                 );
-                self.errors.push(message.clone());
-                return Err(ParseError::ExpectedTokenError(message));
+                self.error_message(&error);
+                return Err(error);
             }
 
             let mut body = self.statement()?;
 
-            if let Some(stmt) = increment {
-                body = Statement::Block(BlockStatement {
-                    statements: vec![
-                        body,
-                        Statement::Expression(ExpressionStatement {
-                            expression: Box::new(stmt),
-                        }),
-                    ],
-                });
-            }
-
             if condition.is_none() {
                 condition = Some(Expr::Literal(Literal {
                     value: LiteralValue::Boolean(true),
                 }));
             }
 
+            // The increment is kept apart from the body so that `continue` doesn't skip it
             body = Statement::While(WhileStatement {
                 condition: Box::new(condition.unwrap()),
                 body: Box::new(body),
+                increment: increment.map(Box::new),
+                else_branch: None,
             });
 
             if let Some(init) = initializer {
@@ -203,13 +574,47 @@ impl<'a> Parser<'a> {
 
             Ok(body)
         } else {
-            let message = format!(
-                "[line {}] Error: Expected '(' after 'for'.",
-                self.previous().unwrap().line()
+            let error = ParseError::new(
+                self.previous_line(),
+                ParseErrorKind::ExpectedToken,
+                "Expected '(' after 'for'.",
             );
-            self.errors.push(message.clone());
-            Err(ParseError::ExpectedTokenError(message))
+            self.error_message(&error);
+            Err(error)
+        }
+    }
+
+    fn for_in_statement(&mut self) -> Result<Statement, ParseError> {
+        self.advance();
+        let name = match self.advance() {
+            Some(Token::Identifier(name)) => name.clone(),
+            other => {
+                return Err(ParseError::new(
+                    other.unwrap().line(),
+                    ParseErrorKind::ExpectedToken,
+                    "Expected variable name.",
+                ));
+            }
+        };
+        self.advance();
+
+        let iterable = self.expression();
+
+        if !matches!(self.advance(), Some(Token::RightParen { line: _ })) {
+            return Err(ParseError::new(
+                self.previous_line(),
+                ParseErrorKind::ExpectedToken,
+                "Expected ')' after for clauses.",
+            ));
         }
+
+        let body = self.statement()?;
+
+        Ok(Statement::ForIn(ForInStatement {
+            name: Box::new(name),
+            iterable: Box::new(iterable),
+            body: Box::new(body),
+        }))
     }
 
     fn while_statement(&mut self) -> Result<Statement, ParseError> {
@@ -221,30 +626,93 @@ impl<'a> Parser<'a> {
                 self.advance();
                 let body = Box::new(self.statement()?);
 
+                let else_branch = match self.peek() {
+                    Some(Token::Else { line: _ }) => {
+                        self.advance();
+                        Some(Box::new(self.statement()?))
+                    }
+                    _ => None,
+                };
+
                 Ok(Statement::While(WhileStatement {
                     condition: Box::new(condition),
                     body,
+                    increment: None,
+                    else_branch,
                 }))
             } else {
-                let message = format!(
-                    "[line {}] Error: Expected ')' after while condition.",
-                    self.previous().unwrap().line()
+                let error = ParseError::new(
+                    self.previous_line(),
+                    ParseErrorKind::ExpectedToken,
+                    "Expected ')' after while condition.",
                 );
-                self.errors.push(message.clone());
-                Err(ParseError::ExpectedTokenError(message))
+                self.error_message(&error);
+                Err(error)
             }
         } else {
-            let message = format!(
-                "[line {}] Error: Expected '(' after 'while'.",
-                self.previous().unwrap().line()
+            let error = ParseError::new(
+                self.previous_line(),
+                ParseErrorKind::ExpectedToken,
+                "Expected '(' after 'while'.",
             );
-            self.errors.push(message.clone());
-            Err(ParseError::ExpectedTokenError(message))
+            self.error_message(&error);
+            Err(error)
+        }
+    }
+
+    fn do_while_statement(&mut self) -> Result<Statement, ParseError> {
+        let keyword = Box::new(self.previous().unwrap().clone());
+        let body = Box::new(self.statement()?);
+
+        if !matches!(self.advance(), Some(Token::While { line: _ })) {
+            return Err(ParseError::new(
+                self.previous_line(),
+                ParseErrorKind::ExpectedToken,
+                "Expected 'while' after do loop body.",
+            ));
         }
+
+        if !matches!(self.advance(), Some(Token::LeftParen { line: _ })) {
+            return Err(ParseError::new(
+                self.previous_line(),
+                ParseErrorKind::ExpectedToken,
+                "Expected '(' after 'while'.",
+            ));
+        }
+
+        let condition = Box::new(self.expression());
+
+        if !matches!(self.advance(), Some(Token::RightParen { line: _ })) {
+            return Err(ParseError::new(
+                self.previous_line(),
+                ParseErrorKind::ExpectedToken,
+                "Expected ')' after while condition.",
+            ));
+        }
+
+        if !self.match_semicolon() {
+            return Err(ParseError::new(
+                self.previous_line(),
+                ParseErrorKind::ExpectedToken,
+                "Expected ';' after do loop.",
+            ));
+        }
+
+        Ok(Statement::DoWhile(DoWhileStatement {
+            keyword,
+            body,
+            condition,
+        }))
     }
 
     fn block(&mut self) -> Result<Statement, ParseError> {
+        let statements = self.block_statements()?;
+        Ok(Statement::Block(BlockStatement { statements }))
+    }
+
+    fn block_statements(&mut self) -> Result<Vec<Statement>, ParseError> {
         let mut statements = Vec::new();
+        let opened_at = self.previous_line();
 
         while let Some(token) = self.peek() {
             match token {
@@ -253,10 +721,7 @@ impl<'a> Parser<'a> {
                     break;
                 }
                 Token::Eof => {
-                    return Err(ParseError::ExpectedTokenError(format!(
-                        "[line {}] Error: Expected '}}' after block, but found EOF",
-                        self.previous().unwrap().line()
-                    )));
+                    return Err(self.unclosed("{", opened_at));
                 }
                 _ => {
                     let statement = self.declaration()?;
@@ -265,113 +730,507 @@ impl<'a> Parser<'a> {
             }
         }
 
-        Ok(Statement::Block(BlockStatement { statements }))
+        Ok(statements)
     }
 
-    fn print_statement(&mut self) -> Result<Statement, ParseError> {
-        let value = self.expression();
-
-        match self.peek() {
-            Some(Token::Semicolon { line: _ }) => {
-                self.advance();
+    /// Parses a function body like a block, except that a final expression without a trailing `;` is implicitly
+    /// returned, e.g. `fun double(x) { x * 2 }`.
+    fn function_body(&mut self) -> Result<Vec<Statement>, ParseError> {
+        let mut statements = Vec::new();
+        let opened_at = self.previous_line();
 
-                Ok(Statement::Print(PrintStatement {
-                    expression: Box::new(value),
-                }))
-            }
-            _ => {
-                let message = format!(
-                    "[line {}] Error: Expected ';' after value.",
-                    self.previous().unwrap().line()
-                );
-                self.errors.push(message.clone());
-                Err(ParseError::ExpectedTokenError(message))
+        while let Some(token) = self.peek() {
+            match token {
+                Token::RightBrace { line: _ } => {
+                    self.advance();
+                    break;
+                }
+                Token::Eof => {
+                    return Err(self.unclosed("{", opened_at));
+                }
+                _ if self.starts_declaration() => {
+                    let statement = self.declaration()?;
+                    statements.push(statement);
+                }
+                _ => {
+                    let (value, terminated) = self.trailing_expression()?;
+                    let statement = if terminated {
+                        Statement::Expression(ExpressionStatement {
+                            expression: Box::new(value),
+                        })
+                    } else {
+                        Statement::Return(ReturnStatement {
+                            keyword: Box::new(Token::Return {
+                                line: self.previous_line(),
+                            }),
+                            value: Some(Box::new(value)),
+                        })
+                    };
+                    statements.push(statement);
+                }
             }
         }
+
+        Ok(statements)
     }
 
-    fn expression_statement(&mut self) -> Result<Statement, ParseError> {
+    /// Whether `fun` is followed by a name, declaring a function rather than starting an anonymous function expression.
+    fn starts_function_declaration(&self) -> bool {
+        matches!(self.tokens.get(self.current + 1), Some(Token::Identifier(_)))
+    }
+
+    fn starts_declaration(&self) -> bool {
+        match self.peek() {
+            Some(Token::Fun { line: _ }) => return self.starts_function_declaration(),
+            Some(Token::Print { line: _ }) => return !self.print_as_function,
+            _ => {}
+        }
+
+        matches!(
+            self.peek(),
+            Some(
+                Token::Class { line: _ }
+                    | Token::Var { line: _ }
+                    | Token::Const { line: _ }
+                    | Token::For { line: _ }
+                    | Token::If { line: _ }
+                    | Token::Print { line: _ }
+                    | Token::Return { line: _ }
+                    | Token::While { line: _ }
+                    | Token::Do { line: _ }
+                    | Token::Break { line: _ }
+                    | Token::Continue { line: _ }
+                    | Token::Assert { line: _ }
+                    | Token::With { line: _ }
+                    | Token::Try { line: _ }
+                    | Token::Throw { line: _ }
+                    | Token::Yield { line: _ }
+                    | Token::LeftBrace { line: _ }
+            )
+        )
+    }
+
+    /// Parses an expression that is either terminated by `;` or is the last one before a `}` or the end of the input,
+    /// returning whether the `;` was present.
+    fn trailing_expression(&mut self) -> Result<(Expr, bool), ParseError> {
         let value = self.expression();
 
         match self.peek() {
             Some(Token::Semicolon { line: _ }) => {
                 self.advance();
-                Ok(Statement::Expression(ExpressionStatement {
-                    expression: Box::new(value),
-                }))
+                Ok((value, true))
             }
+            Some(Token::RightBrace { line: _ } | Token::Eof) => Ok((value, false)),
+            _ if self.at_implicit_semicolon() => Ok((value, true)),
             _ => {
-                let message = format!(
-                    "[line {}] Error: Expected ';' after value.",
-                    self.previous().unwrap().line()
+                let error = ParseError::new(
+                    self.previous_line(),
+                    ParseErrorKind::ExpectedToken,
+                    "Expected ';' after value.",
                 );
-                self.errors.push(message.clone());
-                Err(ParseError::ExpectedTokenError(message))
+                self.error_message(&error);
+                Err(error)
             }
         }
     }
 
-    fn if_statement(&mut self) -> Result<Statement, ParseError> {
-        if let Some(Token::LeftParen { line: _ }) = self.peek() {
-            self.advance();
-            let condition = self.expression();
+    fn try_statement(&mut self) -> Result<Statement, ParseError> {
+        let line = self.previous_line();
 
-            if let Some(Token::RightParen { line: _ }) = self.peek() {
+        if !matches!(self.advance(), Some(Token::LeftBrace { line: _ })) {
+            return Err(ParseError::new(
+                line,
+                ParseErrorKind::ExpectedToken,
+                "Expected '{' after 'try'.",
+            ));
+        }
+        let body = self.block_statements()?;
+
+        let catch = match self.peek() {
+            Some(Token::Catch { line: _ }) => {
                 self.advance();
-                let then_branch = Box::new(self.statement()?);
-                let else_branch = if let Some(Token::Else { line: _ }) = self.peek() {
-                    self.advance();
-                    Some(Box::new(self.statement()?))
-                } else {
-                    None
-                };
+                Some(self.catch_clause()?)
+            }
+            _ => None,
+        };
 
-                Ok(Statement::If(IfStatement {
-                    condition: Box::new(condition),
-                    then_branch,
-                    else_branch,
-                }))
-            } else {
-                let message = format!(
-                    "[line {}] Error: Expected ')' after if condition.",
-                    self.previous().unwrap().line()
-                );
-                self.errors.push(message.clone());
-                Err(ParseError::ExpectedTokenError(message))
+        let finally = match self.peek() {
+            Some(Token::Finally { line: _ }) => {
+                self.advance();
+                if !matches!(self.advance(), Some(Token::LeftBrace { line: _ })) {
+                    return Err(ParseError::new(
+                        self.previous_line(),
+                        ParseErrorKind::ExpectedToken,
+                        "Expected '{' after 'finally'.",
+                    ));
+                }
+                Some(self.block_statements()?)
             }
-        } else {
-            let message = format!(
-                "[line {}] Error: Expected '(' after 'if'.",
-                self.previous().unwrap().line()
-            );
-            self.errors.push(message.clone());
-            Err(ParseError::ExpectedTokenError(message))
+            _ => None,
+        };
+
+        if catch.is_none() && finally.is_none() {
+            return Err(ParseError::new(
+                self.previous_line(),
+                ParseErrorKind::ExpectedToken,
+                "Expected 'catch' or 'finally' after try block.",
+            ));
         }
+
+        Ok(Statement::TryCatch(TryCatchStatement { body, catch, finally }))
     }
 
-    fn assignment(&mut self) -> Expr {
-        let expression = self.or();
+    fn catch_clause(&mut self) -> Result<CatchClause, ParseError> {
+        if !matches!(self.advance(), Some(Token::LeftParen { line: _ })) {
+            return Err(ParseError::new(
+                self.previous_line(),
+                ParseErrorKind::ExpectedToken,
+                "Expected '(' after 'catch'.",
+            ));
+        }
 
-        if let Some(Token::Equal { line: _ }) = self.peek() {
-            self.advance();
+        let name = match self.advance() {
+            Some(Token::Identifier(name)) => name.clone(),
+            other => {
+                return Err(ParseError::new(
+                    other.unwrap().line(),
+                    ParseErrorKind::ExpectedToken,
+                    "Expected error variable name.",
+                ));
+            }
+        };
+
+        if !matches!(self.advance(), Some(Token::RightParen { line: _ })) {
+            return Err(ParseError::new(
+                name.line,
+                ParseErrorKind::ExpectedToken,
+                "Expected ')' after error variable name.",
+            ));
+        }
+
+        if !matches!(self.advance(), Some(Token::LeftBrace { line: _ })) {
+            return Err(ParseError::new(
+                name.line,
+                ParseErrorKind::ExpectedToken,
+                "Expected '{' after catch clause.",
+            ));
+        }
+        let body = self.block_statements()?;
+
+        Ok(CatchClause {
+            name: Box::new(name),
+            body,
+        })
+    }
+
+    fn throw_statement(&mut self) -> Result<Statement, ParseError> {
+        let keyword = Box::new(self.previous().unwrap().clone());
+        let value = Box::new(self.expression());
+
+        if !self.match_semicolon() {
+            return Err(ParseError::new(
+                keyword.line(),
+                ParseErrorKind::ExpectedToken,
+                "Expected ';' after thrown value.",
+            ));
+        }
+
+        Ok(Statement::Throw(ThrowStatement { keyword, value }))
+    }
+
+    fn with_statement(&mut self) -> Result<Statement, ParseError> {
+        let keyword = Box::new(self.previous().unwrap().clone());
+
+        if !matches!(self.advance(), Some(Token::LeftParen { line: _ })) {
+            return Err(ParseError::new(
+                keyword.line(),
+                ParseErrorKind::ExpectedToken,
+                "Expected '(' after 'with'.",
+            ));
+        }
+
+        let object = Box::new(self.expression());
+
+        if !matches!(self.advance(), Some(Token::RightParen { line: _ })) {
+            return Err(ParseError::new(
+                self.previous_line(),
+                ParseErrorKind::ExpectedToken,
+                "Expected ')' after with object.",
+            ));
+        }
+
+        let body = Box::new(self.statement()?);
+        Ok(Statement::With(WithStatement { keyword, object, body }))
+    }
+
+    fn break_statement(&mut self) -> Result<Statement, ParseError> {
+        let keyword = Box::new(self.previous().unwrap().clone());
+
+        if !self.match_semicolon() {
+            return Err(ParseError::new(
+                keyword.line(),
+                ParseErrorKind::ExpectedToken,
+                "Expected ';' after 'break'.",
+            ));
+        }
+
+        Ok(Statement::Break(BreakStatement { keyword }))
+    }
+
+    fn continue_statement(&mut self) -> Result<Statement, ParseError> {
+        let keyword = Box::new(self.previous().unwrap().clone());
+
+        if !self.match_semicolon() {
+            return Err(ParseError::new(
+                keyword.line(),
+                ParseErrorKind::ExpectedToken,
+                "Expected ';' after 'continue'.",
+            ));
+        }
+
+        Ok(Statement::Continue(ContinueStatement { keyword }))
+    }
+
+    fn assert_statement(&mut self) -> Result<Statement, ParseError> {
+        let keyword = Box::new(self.previous().unwrap().clone());
+        let condition = Box::new(self.assignment());
+
+        let message = match self.peek() {
+            Some(Token::Comma { line: _ }) => {
+                self.advance();
+                Some(Box::new(self.expression()))
+            }
+            _ => None,
+        };
+
+        if !self.match_semicolon() {
+            return Err(ParseError::new(
+                keyword.line(),
+                ParseErrorKind::ExpectedToken,
+                "Expected ';' after assertion.",
+            ));
+        }
+
+        Ok(Statement::Assert(AssertStatement {
+            keyword,
+            condition,
+            message,
+        }))
+    }
+
+    fn return_statement(&mut self) -> Result<Statement, ParseError> {
+        let keyword = Box::new(self.previous().unwrap().clone());
+
+        let value = match self.peek() {
+            Some(Token::Semicolon { line: _ }) => None,
+            _ if self.at_implicit_semicolon() => None,
+            _ => Some(Box::new(self.expression())),
+        };
+
+        if !self.match_semicolon() {
+            return Err(ParseError::new(
+                keyword.line(),
+                ParseErrorKind::ExpectedToken,
+                "Expected ';' after return value.",
+            ));
+        }
+
+        Ok(Statement::Return(ReturnStatement { keyword, value }))
+    }
+
+    fn print_statement(&mut self) -> Result<Statement, ParseError> {
+        let keyword = Box::new(self.previous().unwrap().clone());
+        let value = self.expression();
+
+        if !self.match_semicolon() {
+            let error = ParseError::new(
+                self.previous_line(),
+                ParseErrorKind::ExpectedToken,
+                "Expected ';' after value.",
+            );
+            self.error_message(&error);
+            return Err(error);
+        }
+
+        Ok(Statement::Print(PrintStatement {
+            keyword,
+            expression: Box::new(value),
+        }))
+    }
+
+    fn expression_statement(&mut self) -> Result<Statement, ParseError> {
+        let value = match self.peek() {
+            Some(Token::Yield { line: _ }) => self.yield_expression(),
+            _ => self.expression(),
+        };
+
+        if self.interactive && matches!(self.peek(), Some(Token::Eof) | None) && !self.match_semicolon() {
+            return Ok(Statement::Print(PrintStatement {
+                keyword: Box::new(Token::Print {
+                    line: self.previous_line(),
+                }),
+                expression: Box::new(value),
+            }));
+        }
+
+        if !self.match_semicolon() {
+            let error = ParseError::new(
+                self.previous_line(),
+                ParseErrorKind::ExpectedToken,
+                "Expected ';' after value.",
+            );
+            self.error_message(&error);
+            return Err(error);
+        }
+
+        Ok(Statement::Expression(ExpressionStatement {
+            expression: Box::new(value),
+        }))
+    }
+
+    fn if_statement(&mut self) -> Result<Statement, ParseError> {
+        let keyword = Box::new(self.previous().unwrap().clone());
+
+        if let Some(Token::LeftParen { line: _ }) = self.peek() {
+            self.advance();
+            let condition = self.expression();
+
+            if let Some(Token::RightParen { line: _ }) = self.peek() {
+                self.advance();
+                let then_branch = Box::new(self.statement()?);
+                let else_branch = if let Some(Token::Else { line: _ }) = self.peek() {
+                    self.advance();
+                    Some(Box::new(self.statement()?))
+                } else {
+                    None
+                };
+
+                Ok(Statement::If(IfStatement {
+                    keyword,
+                    condition: Box::new(condition),
+                    then_branch,
+                    else_branch,
+                }))
+            } else {
+                let error = ParseError::new(
+                    self.previous_line(),
+                    ParseErrorKind::ExpectedToken,
+                    "Expected ')' after if condition.",
+                );
+                self.error_message(&error);
+                Err(error)
+            }
+        } else {
+            let error = ParseError::new(
+                self.previous_line(),
+                ParseErrorKind::ExpectedToken,
+                "Expected '(' after 'if'.",
+            );
+            self.error_message(&error);
+            Err(error)
+        }
+    }
+
+    fn assignment(&mut self) -> Expr {
+        // Generators can only suspend between statements, so a `yield` nested in an expression is rejected
+        if let Some(Token::Yield { line }) = self.peek() {
+            self.error(ParseError::new(
+                *line,
+                ParseErrorKind::YieldInExpression,
+                "Can only use 'yield' as a statement.",
+            ));
+            return self.yield_expression();
+        }
+
+        let expression = self.ternary();
+
+        if let Some(Token::Equal { line: _ }) = self.peek() {
+            self.advance();
             let value = self.assignment();
 
-            if let Expr::Variable(variable) = expression {
-                return Expr::Assignment(Assignment {
+            return match expression {
+                Expr::Variable(variable) => Expr::Assignment(Assignment {
                     name: variable.token,
                     value: Box::new(value),
-                });
-            } else {
-                self.errors.push(format!(
-                    "[line {}] Error: Invalid assignment target.",
-                    self.previous().unwrap().line()
-                ));
-            }
+                    depth: Cell::new(None),
+                }),
+                Expr::Get(get) => Expr::Set(Set {
+                    object: get.object,
+                    name: get.name,
+                    value: Box::new(value),
+                }),
+                Expr::Index(index) => Expr::IndexSet(IndexSet {
+                    object: index.object,
+                    bracket: index.bracket,
+                    index: index.index,
+                    value: Box::new(value),
+                }),
+                other => {
+                    self.error(ParseError::new(
+                        self.previous_line(),
+                        ParseErrorKind::InvalidAssignmentTarget,
+                        "Invalid assignment target.",
+                    ));
+                    other
+                }
+            };
         }
 
         expression
     }
 
+    fn yield_expression(&mut self) -> Expr {
+        let keyword = Box::new(self.advance().unwrap().clone());
+
+        match self.generators.last_mut() {
+            Some(is_generator) => *is_generator = true,
+            None => self.error(ParseError::new(
+                keyword.line(),
+                ParseErrorKind::YieldOutsideFunction,
+                "Can't use 'yield' outside of a function.",
+            )),
+        }
+
+        let value = match self.peek() {
+            Some(Token::Semicolon { line: _ } | Token::RightParen { line: _ } | Token::RightBrace { line: _ }) => None,
+            _ => Some(Box::new(self.assignment())),
+        };
+
+        Expr::Yield(Yield { keyword, value })
+    }
+
+    fn ternary(&mut self) -> Expr {
+        let condition = self.or();
+
+        if let Some(Token::Question { line: _ }) = self.peek() {
+            self.advance();
+            let then_branch = self.expression();
+
+            match self.peek() {
+                Some(Token::Colon { line: _ }) => {
+                    self.advance();
+                }
+                _ => {
+                    self.error(ParseError::new(
+                        self.previous_line(),
+                        ParseErrorKind::ExpectedToken,
+                        "Expected ':' after then branch of conditional expression.",
+                    ));
+                }
+            }
+
+            let else_branch = self.ternary();
+
+            return Expr::Ternary(Ternary {
+                condition: Box::new(condition),
+                then_branch: Box::new(then_branch),
+                else_branch: Box::new(else_branch),
+            });
+        }
+
+        condition
+    }
+
     fn or(&mut self) -> Expr {
         let mut expr = self.and();
 
@@ -419,7 +1278,25 @@ impl<'a> Parser<'a> {
     }
 
     fn expression(&mut self) -> Expr {
-        self.assignment()
+        self.comma()
+    }
+
+    /// Parses expressions separated by the comma operator, which evaluates each of them and yields the last. Lists
+    /// separated by commas, like call arguments, parse their items with `assignment` instead.
+    fn comma(&mut self) -> Expr {
+        let mut expr = self.assignment();
+
+        while let Some(Token::Comma { line: _ }) = self.peek() {
+            let operator = Box::new(self.advance().unwrap().clone());
+            let right = self.assignment();
+            expr = Expr::Binary(Binary {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            });
+        }
+
+        expr
     }
 
     fn equality(&mut self) -> Expr {
@@ -445,7 +1322,7 @@ impl<'a> Parser<'a> {
         expr
     }
 
-    fn advance(&mut self) -> Option<&Token> {
+    fn advance(&mut self) -> Option<&'a Token> {
         if let Some(token) = self.peek() {
             match token {
                 Token::Eof => {}
@@ -458,12 +1335,19 @@ impl<'a> Parser<'a> {
         self.previous()
     }
 
-    fn peek(&self) -> Option<&Token> {
+    fn peek(&self) -> Option<&'a Token> {
         self.tokens.get(self.current)
     }
 
-    fn previous(&self) -> Option<&Token> {
-        self.tokens.get(self.current - 1)
+    /// The last token consumed, or `None` when nothing was consumed yet.
+    fn previous(&self) -> Option<&'a Token> {
+        self.tokens.get(self.current.checked_sub(1)?)
+    }
+
+    /// The line errors found after the last consumed token are reported at. When nothing was consumed yet, that's the
+    /// line of the first token.
+    fn previous_line(&self) -> usize {
+        self.previous().or(self.peek()).map_or(1, |token| token.line())
     }
 
     fn comparison(&mut self) -> Expr {
@@ -498,13 +1382,21 @@ impl<'a> Parser<'a> {
 
         while let Some(token) = self.peek() {
             match token {
-                Token::Minus { line: _ } | Token::Plus { line: _ } => {
+                Token::Minus { line: _ } | Token::Plus { line: _ } | Token::DotDot { line: _ } => {
                     self.advance();
                 }
+                // Only a variable can be decremented, so anywhere else `--` is a subtraction of a negated operand
+                Token::MinusMinus { line } => {
+                    self.advance();
+                    self.pending_negation = Some(Token::Minus { line: *line });
+                }
                 _ => break,
             }
 
-            let operator = Box::new(self.previous().unwrap().clone());
+            let operator = match self.previous().unwrap() {
+                Token::MinusMinus { line } => Box::new(Token::Minus { line: *line }),
+                token => Box::new(token.clone()),
+            };
             let right = self.factor();
 
             expr = Expr::Binary(Binary {
@@ -518,7 +1410,7 @@ impl<'a> Parser<'a> {
     }
 
     fn factor(&mut self) -> Expr {
-        let mut expr = self.unary();
+        let mut expr = self.power();
 
         while let Some(token) = self.peek() {
             match token {
@@ -529,7 +1421,7 @@ impl<'a> Parser<'a> {
             }
 
             let operator = Box::new(self.previous().unwrap().clone());
-            let right = self.unary();
+            let right = self.power();
 
             expr = Expr::Binary(Binary {
                 left: Box::new(expr),
@@ -541,7 +1433,31 @@ impl<'a> Parser<'a> {
         expr
     }
 
+    fn power(&mut self) -> Expr {
+        let expr = self.unary();
+
+        if let Some(Token::StarStar { line: _ }) = self.peek() {
+            let operator = Box::new(self.advance().unwrap().clone());
+            let right = self.power();
+
+            return Expr::Binary(Binary {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            });
+        }
+
+        expr
+    }
+
     fn unary(&mut self) -> Expr {
+        if let Some(operator) = self.pending_negation.take() {
+            return Expr::Unary(Unary {
+                operator: Box::new(operator),
+                right: Box::new(self.unary()),
+            });
+        }
+
         match self.peek() {
             Some(Token::Bang { line: _ } | Token::Minus { line: _ }) => {
                 self.advance();
@@ -553,8 +1469,192 @@ impl<'a> Parser<'a> {
                     right: Box::new(right),
                 })
             }
-            _ => self.primary(),
+            // There is no prefix decrement, so a leading `--` negates twice, as in `--1`
+            Some(Token::MinusMinus { line }) => {
+                self.advance();
+                let negation = |right| {
+                    Expr::Unary(Unary {
+                        operator: Box::new(Token::Minus { line: *line }),
+                        right: Box::new(right),
+                    })
+                };
+
+                negation(negation(self.unary()))
+            }
+            _ => self.postfix(),
+        }
+    }
+
+    fn postfix(&mut self) -> Expr {
+        let expr = self.call();
+
+        match self.peek() {
+            Some(Token::MinusMinus { line: _ }) if !matches!(expr, Expr::Variable(_)) => expr,
+            Some(Token::PlusPlus { line: _ } | Token::MinusMinus { line: _ }) => {
+                let operator = Box::new(self.advance().unwrap().clone());
+
+                match expr {
+                    Expr::Variable(variable) => Expr::Postfix(Postfix {
+                        name: variable.token,
+                        operator,
+                        depth: Cell::new(None),
+                    }),
+                    other => {
+                        self.error(ParseError::new(
+                            operator.line(),
+                            ParseErrorKind::InvalidAssignmentTarget,
+                            "Invalid assignment target.",
+                        ));
+                        other
+                    }
+                }
+            }
+            _ => expr,
+        }
+    }
+
+    fn call(&mut self) -> Expr {
+        let mut expr = self.primary();
+
+        while let Some(token) = self.peek() {
+            match token {
+                Token::LeftParen { line: _ } => {
+                    self.advance();
+                    expr = self.finish_call(expr);
+                }
+                Token::Dot { line: _ } => {
+                    self.advance();
+                    match self.advance() {
+                        Some(Token::Identifier(name)) => {
+                            expr = Expr::Get(Get {
+                                object: Box::new(expr),
+                                name: Box::new(name.clone()),
+                            });
+                        }
+                        other => {
+                            let line = other.unwrap().line();
+                            self.error(ParseError::new(
+                                line,
+                                ParseErrorKind::ExpectedToken,
+                                "Expected property name after '.'.",
+                            ));
+                            break;
+                        }
+                    }
+                }
+                Token::LeftBracket { line: _ } => {
+                    let bracket = Box::new(self.advance().unwrap().clone());
+                    let index = self.expression();
+
+                    match self.peek() {
+                        Some(Token::RightBracket { line: _ }) => {
+                            self.advance();
+                        }
+                        _ => {
+                            self.error(ParseError::new(
+                                bracket.line(),
+                                ParseErrorKind::ExpectedToken,
+                                "Expected ']' after index.",
+                            ));
+                        }
+                    }
+
+                    expr = Expr::Index(Index {
+                        object: Box::new(expr),
+                        bracket,
+                        index: Box::new(index),
+                    });
+                }
+                _ => break,
+            }
         }
+
+        expr
+    }
+
+    /// Parses the rest of a string with embedded expressions, starting after the part before the first `${`, into the
+    /// concatenation of its parts with `..`.
+    fn interpolation(&mut self, first: &str) -> Expr {
+        let mut expr = Expr::Literal(Literal {
+            value: LiteralValue::String(first.to_string()),
+        });
+
+        loop {
+            let line = self.previous_line();
+            let embedded = self.expression();
+            expr = concatenate(expr, embedded, line);
+
+            let (part, last) = match self.peek() {
+                Some(Token::Interpolation { value, line: _ }) => (value, false),
+                Some(Token::String { value, line: _ }) => (value, true),
+                _ => {
+                    self.error(ParseError::new(
+                        self.previous_line(),
+                        ParseErrorKind::ExpectedToken,
+                        "Expected '}' after interpolated expression.",
+                    ));
+                    return expr;
+                }
+            };
+            self.advance();
+
+            let part = Expr::Literal(Literal {
+                value: LiteralValue::String(part.clone()),
+            });
+            expr = concatenate(expr, part, self.previous_line());
+            if last {
+                return expr;
+            }
+        }
+    }
+
+    fn finish_call(&mut self, callee: Expr) -> Expr {
+        let mut arguments = Vec::new();
+        let opened_at = self.previous_line();
+
+        if !matches!(self.peek(), Some(Token::RightParen { line: _ })) {
+            loop {
+                match self.peek() {
+                    Some(Token::Ellipsis { line: _ }) => {
+                        let ellipsis = Box::new(self.advance().unwrap().clone());
+                        let value = Box::new(self.assignment());
+                        arguments.push(Expr::Spread(Spread { ellipsis, value }));
+                    }
+                    _ => arguments.push(self.assignment()),
+                }
+
+                match self.peek() {
+                    Some(Token::Comma { line: _ }) => {
+                        self.advance();
+                        // A trailing comma is allowed before the closing parenthesis
+                        if matches!(self.peek(), Some(Token::RightParen { line: _ })) {
+                            break;
+                        }
+                    }
+                    _ => break,
+                }
+            }
+        }
+
+        match self.peek() {
+            Some(Token::RightParen { line: _ }) => {
+                self.advance();
+            }
+            Some(Token::Eof) => self.error(self.unclosed("(", opened_at)),
+            _ => {
+                self.error(ParseError::new(
+                    self.previous_line(),
+                    ParseErrorKind::ExpectedToken,
+                    "Expected ')' after arguments.",
+                ));
+            }
+        }
+
+        Expr::Call(Call {
+            callee: Box::new(callee),
+            paren: Box::new(self.previous().unwrap().clone()),
+            arguments,
+        })
     }
 
     fn primary(&mut self) -> Expr {
@@ -572,7 +1672,54 @@ impl<'a> Parser<'a> {
                     value: LiteralValue::Nil,
                 });
             }
-            Some(Token::Number { value, line: _ }) => {
+            Some(Token::String { value, line: _ }) => {
+                let literal_value = value.clone();
+                self.advance();
+                return Expr::Literal(Literal {
+                    value: LiteralValue::String(literal_value),
+                });
+            }
+            Some(Token::Interpolation { value, line: _ }) => {
+                self.advance();
+                return self.interpolation(value);
+            }
+            Some(Token::This { line: _ }) => {
+                let keyword = Box::new(self.advance().unwrap().clone());
+                return Expr::This(This { keyword });
+            }
+            Some(Token::Super { line: _ }) => {
+                let keyword = Box::new(self.advance().unwrap().clone());
+
+                if let Some(Token::Dot { line: _ }) = self.peek() {
+                    self.advance();
+                } else {
+                    self.error(ParseError::new(
+                        keyword.line(),
+                        ParseErrorKind::ExpectedToken,
+                        "Expected '.' after 'super'.",
+                    ));
+                }
+
+                match self.peek() {
+                    Some(Token::Identifier(method)) => {
+                        let method = Box::new(method.clone());
+                        self.advance();
+                        return Expr::Super(Super { keyword, method });
+                    }
+                    _ => {
+                        self.error(ParseError::new(
+                            keyword.line(),
+                            ParseErrorKind::ExpectedToken,
+                            "Expected superclass method name.",
+                        ));
+                    }
+                }
+            }
+            Some(Token::Number {
+                value,
+                lexeme: _,
+                line: _,
+            }) => {
                 let deref_value = *value;
                 self.advance();
                 return Expr::Literal(Literal {
@@ -582,37 +1729,172 @@ impl<'a> Parser<'a> {
             Some(Token::Identifier(token)) => {
                 let variable_expr = Expr::Variable(Variable {
                     token: Box::new(token.clone()),
+                    depth: Cell::new(None),
                 });
                 self.advance();
                 return variable_expr;
             }
-            Some(Token::LeftParen { line: _ }) => {
-                self.advance();
-                let expr = Box::new(self.expression());
+            Some(Token::LeftBracket { line: _ }) => {
+                let bracket = Box::new(self.advance().unwrap().clone());
+                let mut elements = Vec::new();
+
+                if !matches!(self.peek(), Some(Token::RightBracket { line: _ })) {
+                    loop {
+                        elements.push(self.assignment());
+
+                        match self.peek() {
+                            Some(Token::Comma { line: _ }) => {
+                                self.advance();
+                                // A trailing comma is allowed before the closing bracket
+                                if matches!(self.peek(), Some(Token::RightBracket { line: _ })) {
+                                    break;
+                                }
+                            }
+                            _ => break,
+                        }
+                    }
+                }
 
                 match self.peek() {
-                    Some(token) => match token {
-                        Token::RightParen { line: _ } => {
-                            self.advance();
-                        }
+                    Some(Token::RightBracket { line: _ }) => {
+                        self.advance();
+                    }
+                    Some(Token::Eof) => self.error(self.unclosed("[", bracket.line())),
+                    _ => {
+                        self.error(ParseError::new(
+                            self.previous_line(),
+                            ParseErrorKind::ExpectedToken,
+                            "Expected ']' after array elements.",
+                        ));
+                    }
+                }
+
+                return Expr::ArrayLiteral(ArrayLiteral { bracket, elements });
+            }
+            Some(Token::LeftBrace { line: _ }) => {
+                let brace = Box::new(self.advance().unwrap().clone());
+                let mut entries = Vec::new();
+
+                if !matches!(self.peek(), Some(Token::RightBrace { line: _ })) {
+                    loop {
+                        let key = self.assignment();
+
+                        match self.peek() {
+                            Some(Token::Colon { line: _ }) => {
+                                self.advance();
+                            }
+                            _ => {
+                                self.error(ParseError::new(
+                                    self.previous_line(),
+                                    ParseErrorKind::ExpectedToken,
+                                    "Expected ':' after map key.",
+                                ));
+                            }
+                        }
+
+                        entries.push((key, self.assignment()));
+
+                        match self.peek() {
+                            Some(Token::Comma { line: _ }) => {
+                                self.advance();
+                            }
+                            _ => break,
+                        }
+                    }
+                }
+
+                match self.peek() {
+                    Some(Token::RightBrace { line: _ }) => {
+                        self.advance();
+                    }
+                    Some(Token::Eof) => self.error(self.unclosed("{", brace.line())),
+                    _ => {
+                        self.error(ParseError::new(
+                            self.previous_line(),
+                            ParseErrorKind::ExpectedToken,
+                            "Expected '}' after map entries.",
+                        ));
+                    }
+                }
+
+                return Expr::MapLiteral(MapLiteral { brace, entries });
+            }
+            Some(Token::Print { line }) if self.print_as_function => {
+                let token = Box::new(Identifier {
+                    value: "print".into(),
+                    line: *line,
+                });
+                self.advance();
+                return Expr::Variable(Variable {
+                    token,
+                    depth: Cell::new(None),
+                });
+            }
+            Some(Token::Fun { line }) => {
+                let name = Identifier {
+                    value: "anonymous".into(),
+                    line: *line,
+                };
+                let keyword = Box::new(self.advance().unwrap().clone());
+
+                match self.function_signature_and_body(name, "function") {
+                    Ok(declaration) => {
+                        return Expr::Function(FunctionExpr {
+                            keyword,
+                            declaration: Rc::new(declaration),
+                        });
+                    }
+                    Err(error) => self.error(error),
+                }
+            }
+            Some(Token::LeftParen { line }) => {
+                let opened_at = *line;
+                self.advance();
+                let expr = Box::new(self.expression());
+
+                match self.peek() {
+                    Some(token) => match token {
+                        Token::RightParen { line: _ } => {
+                            self.advance();
+                        }
+                        Token::Eof => self.error(self.unclosed("(", opened_at)),
                         other => {
-                            self.errors.push(format!(
-                                "[line {}] Error at '(': Expect ')' after expression.",
-                                other.line()
-                            ));
+                            self.error(
+                                ParseError::new(
+                                    other.line(),
+                                    ParseErrorKind::ExpectedToken,
+                                    "Expect ')' after expression.",
+                                )
+                                .at("("),
+                            );
                         }
                     },
                     None => {
-                        self.errors.push(format!(
-                            "[line {}] Error: Expected ')' after expression.",
-                            self.previous().unwrap().line()
+                        self.error(ParseError::new(
+                            self.previous_line(),
+                            ParseErrorKind::ExpectedToken,
+                            "Expected ')' after expression.",
                         ));
                     }
                 }
 
                 return Expr::Grouping(Grouping { expression: expr });
             }
-            _ => {}
+            // The end of the input has no line or text of its own, so point after the last token instead
+            Some(Token::Eof) | None => {
+                let line = self.previous_line();
+                self.error(ParseError::new(
+                    line,
+                    ParseErrorKind::ExpectedToken,
+                    "Expected expression.",
+                ));
+            }
+            Some(token) => {
+                let line = token.line();
+                self.error(
+                    ParseError::new(line, ParseErrorKind::ExpectedToken, "Expected expression.").at(&token.lexeme()),
+                );
+            }
         }
 
         Expr::Literal(Literal {
@@ -620,6 +1902,30 @@ impl<'a> Parser<'a> {
         })
     }
 
+    /// Consumes the `;` ending a statement, returning whether the statement was properly terminated.
+    fn match_semicolon(&mut self) -> bool {
+        if let Some(Token::Semicolon { line: _ }) = self.peek() {
+            self.advance();
+            return true;
+        }
+
+        self.at_implicit_semicolon()
+    }
+
+    /// Whether automatic semicolon insertion ends the statement before the next token, which is the case when that
+    /// token starts a new line, closes a block or is the end of the input.
+    fn at_implicit_semicolon(&self) -> bool {
+        if !self.asi {
+            return false;
+        }
+
+        match (self.peek(), self.previous()) {
+            (Some(Token::RightBrace { line: _ } | Token::Eof) | None, _) => true,
+            (Some(next), Some(previous)) => next.line() > previous.line(),
+            (Some(_), None) => false,
+        }
+    }
+
     fn synchronize(&mut self) {
         self.advance();
 
@@ -633,11 +1939,17 @@ impl<'a> Parser<'a> {
                 | Token::Class { line: _ }
                 | Token::Fun { line: _ }
                 | Token::Var { line: _ }
+                | Token::Const { line: _ }
                 | Token::For { line: _ }
                 | Token::If { line: _ }
                 | Token::While { line: _ }
+                | Token::Do { line: _ }
                 | Token::Print { line: _ }
-                | Token::Return { line: _ } => break,
+                | Token::Return { line: _ }
+                | Token::Try { line: _ }
+                | Token::Throw { line: _ }
+                | Token::With { line: _ }
+                | Token::Assert { line: _ } => break,
                 _ => {}
             }
 
@@ -646,23 +1958,53 @@ impl<'a> Parser<'a> {
     }
 }
 
+/// Joins two parts of an interpolated string with `..`, which formats any value the way `print` does.
+fn concatenate(left: Expr, right: Expr, line: usize) -> Expr {
+    Expr::Binary(Binary {
+        left: Box::new(left),
+        operator: Box::new(Token::DotDot { line }),
+        right: Box::new(right),
+    })
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::token::Identifier;
+    use crate::{
+        ast::{Node, Stmt},
+        scanner::Scanner,
+        token::Identifier,
+        visitor::AstPrinter,
+    };
 
     use super::*;
 
+    fn parse_source(source: &str) -> Vec<Statement> {
+        let mut errors = Vec::new();
+        let tokens = {
+            let mut scanner = Scanner::new(source, &mut errors);
+            scanner.scan();
+            scanner.into_tokens()
+        };
+        let statements = Parser::new(&tokens, &mut errors).parse();
+        assert!(errors.is_empty(), "Expected no errors, but got: {:?}", errors);
+        statements
+    }
+
     #[test]
     fn test_parsing_print_statements() {
         let tokens = vec![
             Token::Print { line: 1 },
-            Token::Number { value: 42.0, line: 1 },
+            Token::Number {
+                value: 42.0,
+                lexeme: "42".to_string(),
+                line: 1,
+            },
             Token::Semicolon { line: 1 },
             Token::Eof,
         ];
 
         let mut errors = Vec::new();
-        let mut parser = Parser::new(tokens, &mut errors);
+        let mut parser = Parser::new(&tokens, &mut errors);
         let statements = parser.parse();
 
         assert_eq!(statements.len(), 1);
@@ -677,13 +2019,17 @@ mod tests {
     #[test]
     fn test_parsing_expression_statements() {
         let tokens = vec![
-            Token::Number { value: 42.0, line: 1 },
+            Token::Number {
+                value: 42.0,
+                lexeme: "42".to_string(),
+                line: 1,
+            },
             Token::Semicolon { line: 1 },
             Token::Eof,
         ];
 
         let mut errors = Vec::new();
-        let mut parser = Parser::new(tokens, &mut errors);
+        let mut parser = Parser::new(&tokens, &mut errors);
         let statements = parser.parse();
 
         assert_eq!(statements.len(), 1);
@@ -699,12 +2045,16 @@ mod tests {
     fn test_parsing_errors_on_missing_semi_colons() {
         let tokens = vec![
             Token::Print { line: 1 },
-            Token::Number { value: 42.0, line: 1 },
+            Token::Number {
+                value: 42.0,
+                lexeme: "42".to_string(),
+                line: 1,
+            },
             Token::Eof,
         ];
 
         let mut errors = Vec::new();
-        let mut parser = Parser::new(tokens, &mut errors);
+        let mut parser = Parser::new(&tokens, &mut errors);
         parser.parse();
 
         assert_eq!(errors.len(), 2);
@@ -716,12 +2066,12 @@ mod tests {
         let tokens = vec![
             Token::Print { line: 1 },
             Token::Identifier(Identifier {
-                value: "x".to_string(),
+                value: "x".into(),
                 line: 1,
             }),
             Token::Plus { line: 1 },
             Token::Identifier(Identifier {
-                value: "y".to_string(),
+                value: "y".into(),
                 line: 1,
             }),
             Token::Semicolon { line: 1 },
@@ -729,7 +2079,7 @@ mod tests {
         ];
 
         let mut errors = Vec::new();
-        let mut parser = Parser::new(tokens, &mut errors);
+        let mut parser = Parser::new(&tokens, &mut errors);
         let result = parser.parse();
 
         assert_eq!(errors.len(), 0, "Expected no errors, but got: {:?}", errors);
@@ -743,7 +2093,7 @@ mod tests {
                             assert_eq!(
                                 var.token,
                                 Box::new(Identifier {
-                                    value: "x".to_string(),
+                                    value: "x".into(),
                                     line: 1
                                 })
                             );
@@ -756,7 +2106,7 @@ mod tests {
                             assert_eq!(
                                 var.token,
                                 Box::new(Identifier {
-                                    value: "y".to_string(),
+                                    value: "y".into(),
                                     line: 1
                                 })
                             );
@@ -774,17 +2124,21 @@ mod tests {
     fn test_parsing_assignments() {
         let tokens = vec![
             Token::Identifier(Identifier {
-                value: "x".to_string(),
+                value: "x".into(),
                 line: 1,
             }),
             Token::Equal { line: 1 },
-            Token::Number { value: 42.0, line: 1 },
+            Token::Number {
+                value: 42.0,
+                lexeme: "42".to_string(),
+                line: 1,
+            },
             Token::Semicolon { line: 1 },
             Token::Eof,
         ];
 
         let mut errors = Vec::new();
-        let mut parser = Parser::new(tokens, &mut errors);
+        let mut parser = Parser::new(&tokens, &mut errors);
         let result = parser.parse();
 
         assert_eq!(errors.len(), 0, "Expected no errors, but got: {:?}", errors);
@@ -793,7 +2147,7 @@ mod tests {
         match &result[0] {
             Statement::Expression(expr) => match &*expr.expression {
                 Expr::Assignment(assignment) => {
-                    assert_eq!(assignment.name.value, "x");
+                    assert_eq!(&*assignment.name.value, "x");
 
                     match &*assignment.value {
                         Expr::Literal(literal) => match &literal.value {
@@ -816,30 +2170,702 @@ mod tests {
         let tokens = vec![
             Token::Var { line: 1 },
             Token::Identifier(Identifier {
-                value: "x".to_string(),
+                value: "x".into(),
                 line: 1,
             }),
             Token::Equal { line: 1 },
-            Token::Number { value: 42.0, line: 1 },
+            Token::Number {
+                value: 42.0,
+                lexeme: "42".to_string(),
+                line: 1,
+            },
             Token::Semicolon { line: 1 },
             Token::LeftBrace { line: 2 },
             Token::Var { line: 3 },
             Token::Identifier(Identifier {
-                value: "x".to_string(),
+                value: "x".into(),
                 line: 3,
             }),
             Token::Equal { line: 3 },
-            Token::Number { value: 30.0, line: 3 },
+            Token::Number {
+                value: 30.0,
+                lexeme: "30".to_string(),
+                line: 3,
+            },
             Token::Semicolon { line: 3 },
             Token::RightBrace { line: 4 },
             Token::Eof,
         ];
 
         let mut errors = Vec::new();
-        let mut parser = Parser::new(tokens, &mut errors);
+        let mut parser = Parser::new(&tokens, &mut errors);
         let result = parser.parse();
 
         assert_eq!(errors.len(), 0, "Expected no errors, but got: {:?}", errors);
         assert_eq!(result.len(), 2);
     }
+
+    #[test]
+    fn test_parsing_class_declarations() {
+        let tokens = vec![
+            Token::Class { line: 1 },
+            Token::Identifier(Identifier {
+                value: "Point".into(),
+                line: 1,
+            }),
+            Token::LeftBrace { line: 1 },
+            Token::Identifier(Identifier {
+                value: "sum".into(),
+                line: 2,
+            }),
+            Token::LeftParen { line: 2 },
+            Token::RightParen { line: 2 },
+            Token::LeftBrace { line: 2 },
+            Token::Return { line: 3 },
+            Token::This { line: 3 },
+            Token::Dot { line: 3 },
+            Token::Identifier(Identifier {
+                value: "x".into(),
+                line: 3,
+            }),
+            Token::Semicolon { line: 3 },
+            Token::RightBrace { line: 4 },
+            Token::RightBrace { line: 5 },
+            Token::Eof,
+        ];
+
+        let mut errors = Vec::new();
+        let mut parser = Parser::new(&tokens, &mut errors);
+        let result = parser.parse();
+
+        assert_eq!(errors.len(), 0, "Expected no errors, but got: {:?}", errors);
+        assert_eq!(result.len(), 1);
+
+        match &result[0] {
+            Statement::Class(class) => {
+                assert_eq!(&*class.name.value, "Point");
+                assert_eq!(class.methods.len(), 1);
+                assert_eq!(&*class.methods[0].name.value, "sum");
+
+                match &class.methods[0].body[0] {
+                    Statement::Return(return_stmt) => match return_stmt.value.as_deref() {
+                        Some(Expr::Get(get)) => assert_eq!(&*get.name.value, "x"),
+                        _ => panic!("Expected a property access."),
+                    },
+                    _ => panic!("Expected a return statement."),
+                }
+            }
+            _ => panic!("Expected a class statement."),
+        }
+    }
+
+    #[test]
+    fn test_parsing_constant_declarations() {
+        let tokens = vec![
+            Token::Const { line: 1 },
+            Token::Identifier(Identifier {
+                value: "RATE".into(),
+                line: 1,
+            }),
+            Token::Equal { line: 1 },
+            Token::Number {
+                value: 2.5,
+                lexeme: "2.5".to_string(),
+                line: 1,
+            },
+            Token::Semicolon { line: 1 },
+            Token::Var { line: 2 },
+            Token::Identifier(Identifier {
+                value: "x".into(),
+                line: 2,
+            }),
+            Token::Equal { line: 2 },
+            Token::Number {
+                value: 1.0,
+                lexeme: "1".to_string(),
+                line: 2,
+            },
+            Token::Semicolon { line: 2 },
+            Token::Eof,
+        ];
+
+        let mut errors = Vec::new();
+        let mut parser = Parser::new(&tokens, &mut errors);
+        let result = parser.parse();
+
+        assert_eq!(errors.len(), 0, "Expected no errors, but got: {:?}", errors);
+
+        match (&result[0], &result[1]) {
+            (Statement::Variable(constant), Statement::Variable(variable)) => {
+                assert_eq!(&*constant.name.value, "RATE");
+                assert!(!constant.mutable);
+                assert!(variable.mutable);
+            }
+            _ => panic!("Expected variable statements."),
+        }
+    }
+
+    #[test]
+    fn test_parsing_implicit_return_in_function_body() {
+        let tokens = vec![
+            Token::Fun { line: 1 },
+            Token::Identifier(Identifier {
+                value: "double".into(),
+                line: 1,
+            }),
+            Token::LeftParen { line: 1 },
+            Token::Identifier(Identifier {
+                value: "x".into(),
+                line: 1,
+            }),
+            Token::RightParen { line: 1 },
+            Token::LeftBrace { line: 1 },
+            Token::Identifier(Identifier {
+                value: "x".into(),
+                line: 1,
+            }),
+            Token::Star { line: 1 },
+            Token::Number {
+                value: 2.0,
+                lexeme: "2".to_string(),
+                line: 1,
+            },
+            Token::RightBrace { line: 1 },
+            Token::Eof,
+        ];
+
+        let mut errors = Vec::new();
+        let mut parser = Parser::new(&tokens, &mut errors);
+        let result = parser.parse();
+
+        assert_eq!(errors.len(), 0, "Expected no errors, but got: {:?}", errors);
+
+        match &result[0] {
+            Statement::Function(function) => match &function.body[..] {
+                [Statement::Return(return_stmt)] => assert!(return_stmt.value.is_some()),
+                _ => panic!("Expected a single return statement."),
+            },
+            _ => panic!("Expected a function statement."),
+        }
+    }
+
+    #[test]
+    fn test_parsing_array_literals_and_index_assignment() {
+        let tokens = vec![
+            Token::Identifier(Identifier {
+                value: "list".into(),
+                line: 1,
+            }),
+            Token::LeftBracket { line: 1 },
+            Token::Number {
+                value: 0.0,
+                lexeme: "0".to_string(),
+                line: 1,
+            },
+            Token::RightBracket { line: 1 },
+            Token::Equal { line: 1 },
+            Token::LeftBracket { line: 1 },
+            Token::Number {
+                value: 1.0,
+                lexeme: "1".to_string(),
+                line: 1,
+            },
+            Token::Comma { line: 1 },
+            Token::Number {
+                value: 2.0,
+                lexeme: "2".to_string(),
+                line: 1,
+            },
+            Token::RightBracket { line: 1 },
+            Token::Semicolon { line: 1 },
+            Token::Eof,
+        ];
+
+        let mut errors = Vec::new();
+        let mut parser = Parser::new(&tokens, &mut errors);
+        let result = parser.parse();
+
+        assert_eq!(errors.len(), 0, "Expected no errors, but got: {:?}", errors);
+
+        match &result[0] {
+            Statement::Expression(stmt) => {
+                assert_eq!(stmt.expression.accept(&mut AstPrinter), "(= ([] list 0) (array 1 2))");
+            }
+            _ => panic!("Expected an expression statement."),
+        }
+    }
+
+    #[test]
+    fn test_comparing_parsed_trees() {
+        let source =
+            "class A { init(x) { this.x = [x, 1]; } } fun f(a) { if (a) return -a; else { a = 1; } a } print f(2);";
+        assert!(parse_source(source) == parse_source(source));
+        assert!(parse_source("var x = 1 + 2;") != parse_source("var x = 1 - 2;"));
+        assert!(parse_source("var x = 1;") != parse_source("const x = 1;"));
+        assert!(parse_source("print a.b;") != parse_source("print a.c;"));
+    }
+
+    #[test]
+    fn test_parsing_conditional_expressions() {
+        let print = |source: &str| parse_source(source)[0].accept(&mut AstPrinter);
+
+        assert_eq!(print("true ? 1 : 2 == 1;"), "(?: true 1 (== 2 1))");
+        assert_eq!(print("a ? b : c ? d : e;"), "(?: a b (?: c d e))");
+        assert_eq!(print("x = a or b ? 1 : 2;"), "Identifier(1): x = (?: (or a b) 1 2)");
+    }
+
+    #[test]
+    fn test_parsing_with_automatic_semicolon_insertion() {
+        let parse_asi = |source: &str, asi: bool| {
+            let mut errors = Vec::new();
+            let tokens = {
+                let mut scanner = Scanner::new(source, &mut errors);
+                scanner.scan();
+                scanner.into_tokens()
+            };
+            let statements = Parser::new(&tokens, &mut errors).asi(asi).parse();
+            (statements, errors)
+        };
+
+        let (statements, errors) = parse_asi("print 1\nprint 2", true);
+        assert!(errors.is_empty(), "Expected no errors, but got: {:?}", errors);
+        assert!(statements == parse_source("print 1;\nprint 2;"));
+
+        let (_, errors) = parse_asi("print 1\nprint 2", false);
+        assert!(!errors.is_empty());
+
+        let (statements, errors) = parse_asi("var x = 1 +\n  2\nfun f() {\n  return\n}\n{ x = x * 2 }", true);
+        assert!(errors.is_empty(), "Expected no errors, but got: {:?}", errors);
+        assert!(statements == parse_source("var x = 1 +\n  2;\nfun f() {\n  return;\n}\n{ x = x * 2; }"));
+
+        let (_, errors) = parse_asi("print 1 print 2", true);
+        assert!(!errors.is_empty());
+    }
+
+    #[test]
+    fn test_parsing_power_operator() {
+        let print = |source: &str| parse_source(source)[0].accept(&mut AstPrinter);
+
+        assert_eq!(print("2 ** 3 ** 2;"), "(** 2 (** 3 2))");
+        assert_eq!(print("2 * 3 ** 2;"), "(* 2 (** 3 2))");
+        assert_eq!(print("-2 ** 2;"), "(** (- 2) 2)");
+    }
+
+    #[test]
+    fn test_parsing_generators() {
+        let statements = parse_source("fun gen() { fun inner() { return 1; } yield 1; } fun plain() { return 1; }");
+
+        match (&statements[0], &statements[1]) {
+            (Statement::Function(generator), Statement::Function(plain)) => {
+                assert!(generator.is_generator);
+                assert!(!plain.is_generator);
+
+                match &generator.body[0] {
+                    Statement::Function(inner) => assert!(!inner.is_generator),
+                    _ => panic!("Expected a function statement."),
+                }
+            }
+            _ => panic!("Expected function statements."),
+        }
+
+        let mut errors = Vec::new();
+        let tokens = {
+            let mut scanner = Scanner::new("yield 1;", &mut errors);
+            scanner.scan();
+            scanner.into_tokens()
+        };
+        Parser::new(&tokens, &mut errors).parse();
+        assert_eq!(
+            errors,
+            vec!["[line 1] Error: Can't use 'yield' outside of a function.".to_string()]
+        );
+
+        let mut errors = Vec::new();
+        let tokens = {
+            let mut scanner = Scanner::new("fun gen() { print yield 1; }", &mut errors);
+            scanner.scan();
+            scanner.into_tokens()
+        };
+        Parser::new(&tokens, &mut errors).parse();
+        assert_eq!(
+            errors,
+            vec!["[line 1] Error: Can only use 'yield' as a statement.".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_parsing_rest_parameters() {
+        let statements = parse_source("fun sum(first, ...rest) { return first; } fun plain(a) { return a; }");
+
+        match (&statements[0], &statements[1]) {
+            (Statement::Function(sum), Statement::Function(plain)) => {
+                assert!(sum.variadic);
+                assert_eq!(sum.params.len(), 2);
+                assert_eq!(&*sum.params[1].value, "rest");
+                assert!(!plain.variadic);
+            }
+            _ => panic!("Expected function statements."),
+        }
+
+        let mut errors = Vec::new();
+        let tokens = {
+            let mut scanner = Scanner::new("fun f(...rest, last) { return last; }", &mut errors);
+            scanner.scan();
+            scanner.into_tokens()
+        };
+        Parser::new(&tokens, &mut errors).parse();
+        assert_eq!(errors[0], "[line 1] Error: Rest parameter must be the last parameter.");
+    }
+
+    #[test]
+    fn test_parsing_anonymous_functions() {
+        let statements = parse_source("var double = fun (x) { return x * 2; }; fun named() { return 1; }");
+
+        match (&statements[0], &statements[1]) {
+            (Statement::Variable(variable), Statement::Function(named)) => {
+                match variable.value.as_ref() {
+                    Expr::Function(function) => {
+                        assert_eq!(function.declaration.params.len(), 1);
+                        assert_eq!(&*function.declaration.params[0].value, "x");
+                        assert_eq!(function.declaration.body.len(), 1);
+                    }
+                    _ => panic!("Expected an anonymous function initializer."),
+                }
+                assert_eq!(&*named.name.value, "named");
+            }
+            _ => panic!("Expected a variable and a function statement."),
+        }
+    }
+
+    #[test]
+    fn test_parsing_assert_statements() {
+        let statements = parse_source("assert 1 == 1; assert false, \"message\";");
+
+        match (&statements[0], &statements[1]) {
+            (Statement::Assert(plain), Statement::Assert(with_message)) => {
+                assert!(matches!(*plain.condition, Expr::Binary(_)));
+                assert!(plain.message.is_none());
+                assert!(matches!(with_message.message.as_deref(), Some(Expr::Literal(_))));
+            }
+            _ => panic!("Expected assert statements."),
+        }
+    }
+
+    #[test]
+    fn test_parse_errors_are_structured() {
+        let mut errors = Vec::new();
+        let tokens = {
+            let mut scanner = Scanner::new("var x = 1;\n1 = 2;\nvar = 3;", &mut errors);
+            scanner.scan();
+            scanner.into_tokens()
+        };
+        let mut parser = Parser::new(&tokens, &mut errors);
+        parser.parse();
+
+        let parse_errors = parser.parse_errors().to_vec();
+        assert_eq!(parse_errors.len(), 2);
+        assert_eq!(parse_errors[0].line, 2);
+        assert_eq!(parse_errors[0].kind, ParseErrorKind::InvalidAssignmentTarget);
+        assert_eq!(parse_errors[1].line, 3);
+        assert_eq!(parse_errors[1].kind, ParseErrorKind::ExpectedToken);
+        assert_eq!(parse_errors[1].message, "Expected variable name.");
+        assert_eq!(
+            errors,
+            vec![
+                "[line 2] Error: Invalid assignment target.".to_string(),
+                "[line 3] Error: Expected variable name.".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_statement_errors_are_structured_once() {
+        for source in ["print 1", "1", "with (a print 1;", "if (true print 1;"] {
+            let mut errors = Vec::new();
+            let tokens = {
+                let mut scanner = Scanner::new(source, &mut errors);
+                scanner.scan();
+                scanner.into_tokens()
+            };
+            let mut parser = Parser::new(&tokens, &mut errors);
+            parser.parse();
+
+            assert_eq!(parser.parse_errors().len(), 1, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_parsing_duplicate_parameter_names() {
+        let mut errors = Vec::new();
+        let tokens = {
+            let mut scanner = Scanner::new("fun f(a, b, a) { return a; }\nfun g(a) { return a; }", &mut errors);
+            scanner.scan();
+            scanner.into_tokens()
+        };
+        let mut parser = Parser::new(&tokens, &mut errors);
+        let statements = parser.parse();
+
+        assert_eq!(statements.len(), 2);
+        assert_eq!(parser.parse_errors()[0].kind, ParseErrorKind::DuplicateParameter);
+        assert_eq!(
+            errors,
+            vec!["[line 1] Error: Duplicate parameter name 'a'.".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_parsing_do_while_loops() {
+        let statements = parse_source("do { print i; } while (false);");
+
+        match &statements[0] {
+            Statement::DoWhile(do_while) => {
+                assert!(matches!(do_while.body.as_ref(), Statement::Block(_)));
+                assert!(matches!(
+                    do_while.condition.as_ref(),
+                    Expr::Literal(Literal {
+                        value: LiteralValue::Boolean(false)
+                    })
+                ));
+            }
+            _ => panic!("Expected a do while statement."),
+        }
+
+        let mut errors = Vec::new();
+        let tokens = {
+            let mut scanner = Scanner::new("do print 1; (true);", &mut errors);
+            scanner.scan();
+            scanner.into_tokens()
+        };
+        Parser::new(&tokens, &mut errors).parse();
+        assert_eq!(errors[0], "[line 1] Error: Expected 'while' after do loop body.");
+    }
+
+    #[test]
+    fn test_parsing_print_as_a_function() {
+        let statements = parse_source("print (1);");
+        assert!(matches!(&statements[0], Statement::Print(_)));
+
+        let mut errors = Vec::new();
+        let tokens = {
+            let mut scanner = Scanner::new("var x = print(1);", &mut errors);
+            scanner.scan();
+            scanner.into_tokens()
+        };
+        let statements = Parser::new(&tokens, &mut errors).print_as_function(true).parse();
+        assert!(errors.is_empty(), "Expected no errors, but got: {:?}", errors);
+
+        match &statements[0] {
+            Statement::Variable(variable) => match variable.value.as_ref() {
+                Expr::Call(call) => match call.callee.as_ref() {
+                    Expr::Variable(callee) => assert_eq!(&*callee.token.value, "print"),
+                    _ => panic!("Expected the callee to be a variable."),
+                },
+                _ => panic!("Expected a call."),
+            },
+            _ => panic!("Expected a variable statement."),
+        }
+    }
+
+    #[test]
+    fn test_parsing_large_inputs_matches_parsing_each_part() {
+        let chunk = "class Point { init(x) { this.x = x; } sum(y) { return this.x + y; } }\
+                     fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }\
+                     var total = 0;\
+                     for (var i = 0; i < 10; i = i + 1) { total = total + Point(i).sum(fib(i)) * -2; }\
+                     print [total, {\"key\": !false}, \"done\"]; ";
+        let expected = parse_source(chunk)
+            .iter()
+            .map(|statement| statement.accept(&mut AstPrinter))
+            .collect::<Vec<_>>();
+
+        let statements = parse_source(&chunk.repeat(500));
+        assert_eq!(statements.len(), expected.len() * 500);
+
+        for (index, statement) in statements.iter().enumerate() {
+            assert_eq!(statement.accept(&mut AstPrinter), expected[index % expected.len()]);
+        }
+    }
+
+    #[test]
+    fn test_interactive_input_prints_a_trailing_expression() {
+        let parse_interactive = |source: &str, interactive: bool| {
+            let mut errors = Vec::new();
+            let tokens = {
+                let mut scanner = Scanner::new(source, &mut errors);
+                scanner.scan();
+                scanner.into_tokens()
+            };
+            let statements = Parser::new(&tokens, &mut errors).interactive(interactive).parse();
+            (statements, errors)
+        };
+
+        let (statements, errors) = parse_interactive("var a = 1; a + 1", true);
+        assert!(errors.is_empty(), "Expected no errors, but got: {:?}", errors);
+        assert!(statements == parse_source("var a = 1; print a + 1;"));
+
+        // Only the expression ending the input may omit its semicolon
+        let (_, errors) = parse_interactive("a + 1 print a;", true);
+        assert!(!errors.is_empty());
+
+        let (_, errors) = parse_interactive("{ 1 }", true);
+        assert!(!errors.is_empty());
+
+        let (_, errors) = parse_interactive("a + 1", false);
+        assert!(!errors.is_empty());
+    }
+
+    #[test]
+    fn test_parsing_empty_input() {
+        let tokens = vec![Token::Eof];
+        let mut errors = Vec::new();
+        let statements = Parser::new(&tokens, &mut errors).parse();
+
+        assert!(statements.is_empty());
+        assert!(errors.is_empty(), "Expected no errors, but got: {:?}", errors);
+        assert!(parse_source("  // only a comment\n\n").is_empty());
+    }
+
+    #[test]
+    fn test_errors_before_consuming_any_token() {
+        let tokens = vec![Token::RightParen { line: 1 }, Token::Eof];
+        let mut errors = Vec::new();
+        Parser::new(&tokens, &mut errors).parse();
+
+        assert_eq!(errors[0], "[line 1] Error at ')': Expected expression.");
+        assert!(errors.contains(&"[line 1] Error: Expected ';' after value.".to_string()));
+    }
+
+    #[test]
+    fn test_malformed_leading_input_reports_errors_on_its_line() {
+        for source in [")", "\n\n}", "*", ", x", "else;"] {
+            let mut errors = Vec::new();
+            let tokens = {
+                let mut scanner = Scanner::new(source, &mut errors);
+                scanner.scan();
+                scanner.into_tokens()
+            };
+            Parser::new(&tokens, &mut errors).parse();
+
+            let line = tokens[0].line();
+            assert!(!errors.is_empty(), "Expected errors for {:?}", source);
+            assert!(
+                errors
+                    .iter()
+                    .all(|error| error.starts_with(&format!("[line {}] Error", line))),
+                "Unexpected errors for {:?}: {:?}",
+                source,
+                errors
+            );
+        }
+    }
+
+    #[test]
+    fn test_parsing_postfix_increments() {
+        let print = |source: &str| parse_source(source)[0].accept(&mut AstPrinter);
+
+        assert_eq!(print("i++;"), "i++");
+        assert_eq!(print("x = -i--;"), "Identifier(1): x = (- i--)");
+        assert_eq!(print("for (;; i++) {}"), "while(true; i++) {\n  {}\n}");
+
+        // `--` only decrements a variable, otherwise it is read as two minus signs
+        assert_eq!(print("--1;"), "(- (- 1))");
+        assert_eq!(print("3--1;"), "(- 3 (- 1))");
+        assert_eq!(print("3--2 ** 2;"), "(- 3 (** (- 2) 2))");
+        assert_eq!(print("a - -b;"), "(- a (- b))");
+
+        for source in ["1++;", "a.b++;", "(i)++;"] {
+            let mut errors = Vec::new();
+            let tokens = {
+                let mut scanner = Scanner::new(source, &mut errors);
+                scanner.scan();
+                scanner.into_tokens()
+            };
+            Parser::new(&tokens, &mut errors).parse();
+            assert_eq!(errors, vec!["[line 1] Error: Invalid assignment target."], "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_parsing_interpolated_strings() {
+        let print = |source: &str| parse_source(source)[0].accept(&mut AstPrinter);
+
+        assert_eq!(print("\"a${1+1}b\";"), "(.. (.. a (+ 1 1)) b)");
+        assert_eq!(print("\"${x}, ${y}\";"), "(.. (.. (.. (..  x) , ) y) )");
+
+        // An embedded expression ends at its `}`, so anything else after the expression is an error
+        let mut errors = Vec::new();
+        let tokens = {
+            let mut scanner = Scanner::new("print \"${1;2}\";", &mut errors);
+            scanner.scan();
+            scanner.into_tokens()
+        };
+        Parser::new(&tokens, &mut errors).parse();
+        assert_eq!(
+            errors.first().map(String::as_str),
+            Some("[line 1] Error: Expected '}' after interpolated expression.")
+        );
+    }
+
+    #[test]
+    fn test_parsing_comma_operator() {
+        let print = |source: &str| parse_source(source)[0].accept(&mut AstPrinter);
+
+        assert_eq!(print("(a, b, c);"), "(group (, (, a b) c))");
+        assert_eq!(print("f(a, (b, c));"), "(call f a (group (, b c)))");
+        assert_eq!(print("[a, b];"), "(array a b)");
+    }
+
+    #[test]
+    fn test_parsing_concatenation_at_term_precedence() {
+        let print = |source: &str| parse_source(source)[0].accept(&mut AstPrinter);
+
+        assert_eq!(print("\"n=\" .. 42 == \"n=42\";"), "(== (.. n= 42) n=42)");
+        assert_eq!(print("a .. b * c + d;"), "(+ (.. a (* b c)) d)");
+    }
+
+    #[test]
+    fn test_unclosed_delimiters_report_where_they_were_opened() {
+        for (source, expected) in [
+            (
+                "fun f() {\n  if (x) {\n    print 1;\n  }\n",
+                "[line 4] Error: Unclosed '{' opened at line 1.",
+            ),
+            ("print (1 +\n2", "[line 2] Error: Unclosed '(' opened at line 1."),
+            ("var list = [1,\n2", "[line 2] Error: Unclosed '[' opened at line 1."),
+        ] {
+            let mut errors = Vec::new();
+            let tokens = {
+                let mut scanner = Scanner::new(source, &mut errors);
+                scanner.scan();
+                scanner.into_tokens()
+            };
+            let mut parser = Parser::new(&tokens, &mut errors);
+            parser.parse();
+
+            assert_eq!(parser.parse_errors()[0].kind, ParseErrorKind::UnclosedDelimiter);
+            assert_eq!(errors[0], expected);
+        }
+    }
+
+    #[test]
+    fn test_parsing_trailing_commas() {
+        let print = |source: &str| parse_source(source)[0].accept(&mut AstPrinter);
+
+        assert_eq!(print("f(a, b,);"), print("f(a, b);"));
+        assert_eq!(print("[1, 2,];"), print("[1, 2];"));
+        assert_eq!(print("fun f(a, b,) {}"), print("fun f(a, b) {}"));
+        assert_eq!(print("f();"), print("f( );"));
+        assert_eq!(print("[];"), print("[ ];"));
+
+        for source in ["f(,);", "[,];"] {
+            let mut errors = Vec::new();
+            let tokens = {
+                let mut scanner = Scanner::new(source, &mut errors);
+                scanner.scan();
+                scanner.into_tokens()
+            };
+            Parser::new(&tokens, &mut errors).parse();
+            assert!(!errors.is_empty(), "Expected errors for {}", source);
+        }
+    }
 }
@@ -0,0 +1,43 @@
+use crate::{scanner::Scanner, token::Token};
+
+/// Whether the REPL should keep reading lines before evaluating `source`, which is the case while parentheses,
+/// braces or brackets are left open or a string is left unterminated.
+pub fn needs_more_input(source: &str) -> bool {
+    let mut errors = Vec::new();
+    let mut scanner = Scanner::new(source, &mut errors);
+    scanner.scan();
+
+    let mut depth: i32 = 0;
+    for token in scanner.tokens() {
+        match token {
+            Token::LeftParen { line: _ } | Token::LeftBrace { line: _ } | Token::LeftBracket { line: _ } => depth += 1,
+            Token::RightParen { line: _ } | Token::RightBrace { line: _ } | Token::RightBracket { line: _ } => {
+                depth -= 1
+            }
+            _ => {}
+        }
+    }
+
+    depth > 0 || errors.iter().any(|error| error.starts_with("Unterminated string"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unbalanced_input_needs_more_lines() {
+        assert!(needs_more_input("if (x) {"));
+        assert!(needs_more_input("print foo(1,"));
+        assert!(needs_more_input("var list = [1, 2,"));
+        assert!(needs_more_input("print \"unterminated"));
+    }
+
+    #[test]
+    fn test_balanced_input_is_complete() {
+        assert!(!needs_more_input("print 1;"));
+        assert!(!needs_more_input("if (x) {\n  print 1;\n}"));
+        assert!(!needs_more_input("print \"{ (\"; // {"));
+        assert!(!needs_more_input("}"));
+    }
+}
@@ -0,0 +1,669 @@
+use std::{cell::Cell, collections::HashMap};
+
+use crate::{
+    ast::{
+        ArrayLiteral, Assignment, Binary, Call, Expr, FunctionExpr, FunctionStatement, Get, Grouping, IfStatement,
+        Index, IndexSet, Literal, LiteralValue, Logical, MapLiteral, Node, Postfix, Set, Spread, Statement, Stmt,
+        Super, Ternary, This, Unary, Variable, Yield,
+    },
+    token::{Identifier, Token},
+    visitor::{StatementVisitor, Visitor},
+};
+
+/// Walks the AST once before execution, recording for each variable use how many scopes away its declaration lives.
+/// Scopes mirror the environments created by the `Vm` so that lookups land on the binding visible at declaration
+/// time, even if an enclosing scope later declares a variable with the same name.
+pub struct Resolver<'a> {
+    errors: &'a mut Vec<String>,
+    /// Problems that don't stop the program from running, kept apart from `errors`.
+    warnings: Vec<String>,
+    scopes: Vec<HashMap<String, bool>>,
+    strict: bool,
+    /// Number of loops enclosing the current statement within the current function.
+    loop_depth: usize,
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(errors: &'a mut Vec<String>) -> Self {
+        Resolver {
+            errors,
+            warnings: Vec::new(),
+            scopes: Vec::new(),
+            strict: false,
+            loop_depth: 0,
+        }
+    }
+
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// The warnings found while resolving so far, in the order they were reported.
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    pub fn resolve(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            statement.accept(self);
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Identifier) {
+        if let Some(scope) = self.scopes.last_mut() {
+            // Globals can still be redeclared, as in reference Lox
+            if self.strict && scope.contains_key(&*name.value) {
+                self.errors.push(format!(
+                    "[line {}] Error: Already a variable named '{}' in this scope.",
+                    name.line, name.value
+                ));
+            }
+            scope.insert(name.value.to_string(), false);
+        }
+    }
+
+    fn define(&mut self, name: &str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), true);
+        }
+    }
+
+    /// Reports uses of a local variable, whether reading or incrementing it, inside the initializer declaring it.
+    fn check_not_in_own_initializer(&mut self, name: &Identifier) {
+        if let Some(scope) = self.scopes.last()
+            && scope.get(&*name.value) == Some(&false)
+        {
+            self.errors.push(format!(
+                "[line {}] Error: Can't read local variable in its own initializer.",
+                name.line
+            ));
+        }
+    }
+
+    fn resolve_local(&mut self, name: &str, depth: &Cell<Option<usize>>) {
+        let distance = self.scopes.iter().rev().position(|scope| scope.contains_key(name));
+        depth.set(distance);
+    }
+
+    fn resolve_function(&mut self, function: &FunctionStatement) {
+        let enclosing_loop_depth = std::mem::take(&mut self.loop_depth);
+        self.begin_scope();
+        for param in &function.params {
+            // Duplicate parameters are already reported by the parser
+            self.define(&param.value);
+        }
+        self.check_unreachable_code(&function.body);
+        self.resolve(&function.body);
+        self.end_scope();
+        self.loop_depth = enclosing_loop_depth;
+    }
+
+    /// Warns about statements following one that always returns in the same block, at the first of them.
+    fn check_unreachable_code(&mut self, statements: &[Statement]) {
+        let mut statements = statements.iter();
+        let returning_line = statements.by_ref().find_map(always_returns);
+
+        if let (Some(returning_line), Some(unreachable)) = (returning_line, statements.next()) {
+            let line = statement_line(unreachable).unwrap_or(returning_line);
+            self.warnings
+                .push(format!("[line {}] Warning: Unreachable code.", line));
+        }
+    }
+
+    /// Warns about a condition that is an assignment, which is usually a mistyped `==`. Wrapping the assignment in
+    /// parentheses marks it as intended.
+    fn check_assignment_condition(&mut self, condition: &Expr) {
+        if let Expr::Assignment(assignment) = condition {
+            self.warnings.push(format!(
+                "[line {}] Warning: Assignment in condition; did you mean '=='?",
+                assignment.name.line
+            ));
+        }
+    }
+
+    fn check_dead_branches(&mut self, if_stmt: &IfStatement) {
+        match constant_truthiness(&if_stmt.condition) {
+            Some(true) if if_stmt.else_branch.is_some() => self.warnings.push(format!(
+                "[line {}] Warning: Unreachable 'else' branch, condition is always true.",
+                if_stmt.keyword.line()
+            )),
+            Some(false) => self.warnings.push(format!(
+                "[line {}] Warning: Unreachable 'then' branch, condition is always false.",
+                if_stmt.keyword.line()
+            )),
+            _ => {}
+        }
+    }
+}
+
+/// Returns the line of the `return` ending a statement that always returns, such as a `return` itself, a block ending
+/// in one or an `if` whose branches both return.
+fn always_returns(statement: &Statement) -> Option<usize> {
+    match statement {
+        Statement::Return(return_stmt) => Some(return_stmt.keyword.line()),
+        Statement::Block(block) => block.statements.iter().find_map(always_returns),
+        Statement::If(if_stmt) => {
+            always_returns(if_stmt.else_branch.as_ref()?)?;
+            always_returns(&if_stmt.then_branch)
+        }
+        _ => None,
+    }
+}
+
+/// Returns the line a statement starts on, or `None` when it holds no token to tell, as for a lone literal like `1;`.
+fn statement_line(statement: &Statement) -> Option<usize> {
+    match statement {
+        Statement::Expression(stmt) => expression_line(&stmt.expression),
+        Statement::If(stmt) => Some(stmt.keyword.line()),
+        Statement::Print(stmt) => Some(stmt.keyword.line()),
+        Statement::While(stmt) => expression_line(&stmt.condition),
+        Statement::DoWhile(stmt) => Some(stmt.keyword.line()),
+        Statement::Variable(stmt) => Some(stmt.name.line),
+        Statement::Block(block) => block.statements.iter().find_map(statement_line),
+        Statement::Function(function) => Some(function.name.line),
+        Statement::Return(stmt) => Some(stmt.keyword.line()),
+        Statement::Break(stmt) => Some(stmt.keyword.line()),
+        Statement::Continue(stmt) => Some(stmt.keyword.line()),
+        Statement::Assert(stmt) => Some(stmt.keyword.line()),
+        Statement::Class(class) => Some(class.name.line),
+        Statement::TryCatch(stmt) => stmt.body.iter().find_map(statement_line),
+        Statement::Throw(stmt) => Some(stmt.keyword.line()),
+        Statement::ForIn(stmt) => Some(stmt.name.line),
+        Statement::With(stmt) => Some(stmt.keyword.line()),
+    }
+}
+
+/// Returns the line an expression starts on, or `None` when it is made of literals only.
+fn expression_line(expr: &Expr) -> Option<usize> {
+    match expr {
+        Expr::Binary(binary) => expression_line(&binary.left).or(Some(binary.operator.line())),
+        Expr::Grouping(grouping) => expression_line(&grouping.expression),
+        Expr::Literal(_) => None,
+        Expr::Logical(logical) => expression_line(&logical.left).or(Some(logical.operator.line())),
+        Expr::Unary(unary) => Some(unary.operator.line()),
+        Expr::Variable(variable) => Some(variable.token.line),
+        Expr::Assignment(assignment) => Some(assignment.name.line),
+        Expr::Postfix(postfix) => Some(postfix.name.line),
+        Expr::Call(call) => expression_line(&call.callee).or(Some(call.paren.line())),
+        Expr::Get(get) => expression_line(&get.object).or(Some(get.name.line)),
+        Expr::Set(set) => expression_line(&set.object).or(Some(set.name.line)),
+        Expr::This(this) => Some(this.keyword.line()),
+        Expr::Super(super_expr) => Some(super_expr.keyword.line()),
+        Expr::ArrayLiteral(array) => Some(array.bracket.line()),
+        Expr::MapLiteral(map) => Some(map.brace.line()),
+        Expr::Index(index) => expression_line(&index.object).or(Some(index.bracket.line())),
+        Expr::IndexSet(index_set) => expression_line(&index_set.object).or(Some(index_set.bracket.line())),
+        Expr::Ternary(ternary) => expression_line(&ternary.condition)
+            .or_else(|| expression_line(&ternary.then_branch))
+            .or_else(|| expression_line(&ternary.else_branch)),
+        Expr::Yield(yield_expr) => Some(yield_expr.keyword.line()),
+        Expr::Spread(spread) => Some(spread.ellipsis.line()),
+        Expr::Function(function) => Some(function.keyword.line()),
+    }
+}
+
+/// Evaluates the truthiness of conditions that are known at compile time, such as `true`, `nil` or `!(false)`.
+/// Returns `None` when the condition depends on runtime values.
+fn constant_truthiness(expr: &Expr) -> Option<bool> {
+    match expr {
+        Expr::Literal(literal) => match literal.value {
+            LiteralValue::Nil => Some(false),
+            LiteralValue::Boolean(b) => Some(b),
+            _ => Some(true),
+        },
+        Expr::Grouping(grouping) => constant_truthiness(&grouping.expression),
+        Expr::Unary(unary) => match *unary.operator {
+            Token::Bang { line: _ } => constant_truthiness(&unary.right).map(|b| !b),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+impl Visitor for Resolver<'_> {
+    type Output = ();
+
+    fn visit_binary(&mut self, binary: &Binary) -> Self::Output {
+        binary.left.accept(self);
+        binary.right.accept(self);
+    }
+
+    fn visit_grouping(&mut self, grouping: &Grouping) -> Self::Output {
+        grouping.expression.accept(self);
+    }
+
+    fn visit_literal(&mut self, _literal: &Literal) -> Self::Output {}
+
+    fn visit_logical(&mut self, logical: &Logical) -> Self::Output {
+        logical.left.accept(self);
+        logical.right.accept(self);
+    }
+
+    fn visit_ternary(&mut self, ternary: &Ternary) -> Self::Output {
+        ternary.condition.accept(self);
+        ternary.then_branch.accept(self);
+        ternary.else_branch.accept(self);
+    }
+
+    fn visit_unary(&mut self, unary: &Unary) -> Self::Output {
+        unary.right.accept(self);
+    }
+
+    fn visit_variable(&mut self, variable: &Variable) -> Self::Output {
+        self.check_not_in_own_initializer(&variable.token);
+        self.resolve_local(&variable.token.value, &variable.depth);
+    }
+
+    fn visit_assignment(&mut self, assignment: &Assignment) -> Self::Output {
+        assignment.value.accept(self);
+        self.resolve_local(&assignment.name.value, &assignment.depth);
+    }
+
+    fn visit_postfix(&mut self, postfix: &Postfix) -> Self::Output {
+        self.check_not_in_own_initializer(&postfix.name);
+        self.resolve_local(&postfix.name.value, &postfix.depth);
+    }
+
+    fn visit_call(&mut self, call: &Call) -> Self::Output {
+        call.callee.accept(self);
+        for argument in &call.arguments {
+            argument.accept(self);
+        }
+    }
+
+    fn visit_get(&mut self, get: &Get) -> Self::Output {
+        get.object.accept(self);
+    }
+
+    fn visit_set(&mut self, set: &Set) -> Self::Output {
+        set.value.accept(self);
+        set.object.accept(self);
+    }
+
+    fn visit_this(&mut self, _this: &This) -> Self::Output {}
+
+    fn visit_super(&mut self, _super_expr: &Super) -> Self::Output {}
+
+    fn visit_array_literal(&mut self, array: &ArrayLiteral) -> Self::Output {
+        for element in &array.elements {
+            element.accept(self);
+        }
+    }
+
+    fn visit_map_literal(&mut self, map: &MapLiteral) -> Self::Output {
+        for (key, value) in &map.entries {
+            key.accept(self);
+            value.accept(self);
+        }
+    }
+
+    fn visit_index(&mut self, index: &Index) -> Self::Output {
+        index.object.accept(self);
+        index.index.accept(self);
+    }
+
+    fn visit_index_set(&mut self, index_set: &IndexSet) -> Self::Output {
+        index_set.value.accept(self);
+        index_set.object.accept(self);
+        index_set.index.accept(self);
+    }
+
+    fn visit_yield(&mut self, yield_expr: &Yield) -> Self::Output {
+        if let Some(value) = &yield_expr.value {
+            value.accept(self);
+        }
+    }
+
+    fn visit_spread(&mut self, spread: &Spread) -> Self::Output {
+        spread.value.accept(self);
+    }
+
+    fn visit_function(&mut self, function: &FunctionExpr) -> Self::Output {
+        self.resolve_function(&function.declaration);
+    }
+}
+
+impl StatementVisitor for Resolver<'_> {
+    type Output = ();
+
+    fn visit_statement(&mut self, statement: &Statement) -> Self::Output {
+        match statement {
+            Statement::Expression(stmt) => stmt.expression.accept(self),
+            Statement::Print(stmt) => stmt.expression.accept(self),
+            Statement::Variable(var) => {
+                self.declare(&var.name);
+                var.value.accept(self);
+                self.define(&var.name.value);
+            }
+            Statement::Block(block) => {
+                self.check_unreachable_code(&block.statements);
+
+                self.begin_scope();
+                self.resolve(&block.statements);
+                self.end_scope();
+            }
+            Statement::If(if_stmt) => {
+                if self.strict {
+                    self.check_dead_branches(if_stmt);
+                }
+                self.check_assignment_condition(&if_stmt.condition);
+
+                if_stmt.condition.accept(self);
+                if_stmt.then_branch.accept(self);
+                if let Some(else_branch) = &if_stmt.else_branch {
+                    else_branch.accept(self);
+                }
+            }
+            Statement::While(while_stmt) => {
+                self.check_assignment_condition(&while_stmt.condition);
+
+                while_stmt.condition.accept(self);
+                self.loop_depth += 1;
+                while_stmt.body.accept(self);
+                self.loop_depth -= 1;
+                if let Some(increment) = &while_stmt.increment {
+                    increment.accept(self);
+                }
+                if let Some(else_branch) = &while_stmt.else_branch {
+                    else_branch.accept(self);
+                }
+            }
+            Statement::DoWhile(do_while) => {
+                self.check_assignment_condition(&do_while.condition);
+
+                self.loop_depth += 1;
+                do_while.body.accept(self);
+                self.loop_depth -= 1;
+                do_while.condition.accept(self);
+            }
+            Statement::ForIn(for_in) => {
+                for_in.iterable.accept(self);
+
+                self.begin_scope();
+                self.declare(&for_in.name);
+                self.define(&for_in.name.value);
+                self.loop_depth += 1;
+                for_in.body.accept(self);
+                self.loop_depth -= 1;
+                self.end_scope();
+            }
+            Statement::With(with_stmt) => {
+                with_stmt.object.accept(self);
+
+                // The scope stands for the environment backed by the object, which holds no declarations itself
+                self.begin_scope();
+                with_stmt.body.accept(self);
+                self.end_scope();
+            }
+            Statement::Break(break_stmt) => {
+                if self.loop_depth == 0 {
+                    self.errors.push(format!(
+                        "[line {}] Error: Can't use 'break' outside of a loop.",
+                        break_stmt.keyword.line()
+                    ));
+                }
+            }
+            Statement::Continue(continue_stmt) => {
+                if self.loop_depth == 0 {
+                    self.errors.push(format!(
+                        "[line {}] Error: Can't use 'continue' outside of a loop.",
+                        continue_stmt.keyword.line()
+                    ));
+                }
+            }
+            Statement::Assert(assert_stmt) => {
+                assert_stmt.condition.accept(self);
+                if let Some(message) = &assert_stmt.message {
+                    message.accept(self);
+                }
+            }
+            Statement::Function(function) => {
+                self.declare(&function.name);
+                self.define(&function.name.value);
+                self.resolve_function(function);
+            }
+            Statement::Return(return_stmt) => {
+                if let Some(value) = &return_stmt.value {
+                    value.accept(self);
+                }
+            }
+            Statement::Throw(throw_stmt) => throw_stmt.value.accept(self),
+            Statement::TryCatch(try_catch) => {
+                self.begin_scope();
+                self.resolve(&try_catch.body);
+                self.end_scope();
+
+                if let Some(catch) = &try_catch.catch {
+                    self.begin_scope();
+                    self.declare(&catch.name);
+                    self.define(&catch.name.value);
+                    self.resolve(&catch.body);
+                    self.end_scope();
+                }
+
+                if let Some(finally) = &try_catch.finally {
+                    self.begin_scope();
+                    self.resolve(finally);
+                    self.end_scope();
+                }
+            }
+            Statement::Class(class) => {
+                self.declare(&class.name);
+                self.define(&class.name.value);
+
+                if let Some(superclass) = &class.superclass {
+                    if superclass.token.value == class.name.value {
+                        self.errors.push(format!(
+                            "[line {}] Error: A class can't inherit from itself.",
+                            superclass.token.line
+                        ));
+                    }
+
+                    self.visit_variable(superclass);
+                    self.begin_scope();
+                    self.define("super");
+                }
+
+                self.begin_scope();
+                self.define("this");
+                for method in &class.methods {
+                    self.resolve_function(method);
+                }
+                self.end_scope();
+
+                if class.superclass.is_some() {
+                    self.end_scope();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::Parser, scanner::Scanner};
+
+    fn resolve(source: &str, strict: bool) -> Vec<String> {
+        resolve_with_warnings(source, strict).0
+    }
+
+    /// The warnings for a source resolving without errors.
+    fn warnings(source: &str, strict: bool) -> Vec<String> {
+        let (errors, warnings) = resolve_with_warnings(source, strict);
+        assert!(errors.is_empty(), "Expected no errors, but got: {:?}", errors);
+        warnings
+    }
+
+    fn resolve_with_warnings(source: &str, strict: bool) -> (Vec<String>, Vec<String>) {
+        let mut errors = Vec::new();
+        let tokens = {
+            let mut scanner = Scanner::new(source, &mut errors);
+            scanner.scan();
+            scanner.into_tokens()
+        };
+        let statements = Parser::new(&tokens, &mut errors).parse();
+        assert!(errors.is_empty(), "Expected no errors, but got: {:?}", errors);
+
+        let mut resolver = Resolver::new(&mut errors).strict(strict);
+        resolver.resolve(&statements);
+        let warnings = resolver.warnings().to_vec();
+        (errors, warnings)
+    }
+
+    #[test]
+    fn test_warns_about_dead_else_branch() {
+        let warnings = warnings("if (true) print 1; else print 2;", true);
+
+        assert_eq!(
+            warnings,
+            vec!["[line 1] Warning: Unreachable 'else' branch, condition is always true.".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_warns_about_dead_then_branch() {
+        let warnings = warnings("if (false) print 1; else print 2;", true);
+
+        assert_eq!(
+            warnings,
+            vec!["[line 1] Warning: Unreachable 'then' branch, condition is always false.".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_warns_about_assignments_in_conditions() {
+        assert_eq!(
+            warnings("var x = 0; if (x = 1) {}", false),
+            vec!["[line 1] Warning: Assignment in condition; did you mean '=='?".to_string()]
+        );
+        assert_eq!(
+            warnings("var x = 0;\nwhile (x = nil) {}", false),
+            vec!["[line 2] Warning: Assignment in condition; did you mean '=='?".to_string()]
+        );
+        assert!(warnings("var x = 0; if (x == 1) {}", false).is_empty());
+        assert!(warnings("var x = 0; if ((x = 1)) {}", false).is_empty());
+        assert!(resolve("var x = 0; if (x = 1) {}", false).is_empty());
+    }
+
+    #[test]
+    fn test_warns_about_unreachable_code_after_return() {
+        assert_eq!(
+            warnings("fun f() {\n  return 1;\n  print 2;\n}", false),
+            vec!["[line 3] Warning: Unreachable code.".to_string()]
+        );
+        assert_eq!(
+            warnings("fun f(a) {\n  return 1;\n\n  a.b = 2;\n  print 3;\n}", false),
+            vec!["[line 4] Warning: Unreachable code.".to_string()]
+        );
+        assert_eq!(
+            warnings("fun f(x) { { if (x) return 1; else { return 2; } } print 3; }", true),
+            vec!["[line 1] Warning: Unreachable code.".to_string()]
+        );
+        assert!(warnings("fun f(x) { if (x) return 1; print 2; }", true).is_empty());
+        assert!(resolve("fun f() { return 1; print 2; }", false).is_empty());
+    }
+
+    #[test]
+    fn test_redeclaring_a_local_variable_in_strict_mode() {
+        assert_eq!(
+            resolve("{\n  var x = 1;\n  var x = 2;\n}", true),
+            vec!["[line 3] Error: Already a variable named 'x' in this scope.".to_string()]
+        );
+        assert!(resolve("{ var x = 1; var x = 2; }", false).is_empty());
+        assert!(resolve("var x = 1; var x = 2; { var x = 3; { var x = 4; } }", true).is_empty());
+    }
+
+    #[test]
+    fn test_dead_branches_are_only_reported_in_strict_mode() {
+        assert!(warnings("if (true) print 1; else print 2;", false).is_empty());
+        assert!(warnings("var x = true; if (x) print 1; else print 2;", true).is_empty());
+    }
+
+    #[test]
+    fn test_resolving_variable_depths() {
+        let source = "var a = 1; { var b = 2; { print a + b; } }";
+        let mut errors = Vec::new();
+        let tokens = {
+            let mut scanner = Scanner::new(source, &mut errors);
+            scanner.scan();
+            scanner.into_tokens()
+        };
+        let statements = Parser::new(&tokens, &mut errors).parse();
+        Resolver::new(&mut errors).resolve(&statements);
+
+        let Statement::Block(outer) = &statements[1] else {
+            panic!("Expected a block statement.");
+        };
+        let Statement::Block(inner) = &outer.statements[1] else {
+            panic!("Expected a block statement.");
+        };
+        let Statement::Print(print) = &inner.statements[0] else {
+            panic!("Expected a print statement.");
+        };
+        let Expr::Binary(binary) = &*print.expression else {
+            panic!("Expected a binary expression.");
+        };
+
+        match (&*binary.left, &*binary.right) {
+            (Expr::Variable(a), Expr::Variable(b)) => {
+                assert_eq!(a.depth.get(), None);
+                assert_eq!(b.depth.get(), Some(1));
+            }
+            _ => panic!("Expected variable expressions."),
+        }
+    }
+
+    #[test]
+    fn test_reading_local_variable_in_its_own_initializer() {
+        let errors = resolve("{ var a = 1; { var a = a; } }", false);
+
+        assert_eq!(
+            errors,
+            vec!["[line 1] Error: Can't read local variable in its own initializer.".to_string()]
+        );
+        assert!(resolve("var a = 1; { var b = a; }", false).is_empty());
+
+        assert_eq!(
+            resolve("{ var i = i++; }", false),
+            vec!["[line 1] Error: Can't read local variable in its own initializer.".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_class_inheriting_from_itself() {
+        let errors = resolve("class Oops < Oops {}", false);
+
+        assert_eq!(
+            errors,
+            vec!["[line 1] Error: A class can't inherit from itself.".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_break_outside_of_a_loop() {
+        let error = "[line 1] Error: Can't use 'break' outside of a loop.".to_string();
+
+        assert_eq!(resolve("break;", false), vec![error.clone()]);
+        assert_eq!(resolve("while (true) { fun f() { break; } }", false), vec![error]);
+        assert!(resolve("while (true) { if (true) break; } else { print 1; }", false).is_empty());
+    }
+
+    #[test]
+    fn test_continue_outside_of_a_loop() {
+        let error = "[line 1] Error: Can't use 'continue' outside of a loop.".to_string();
+
+        assert_eq!(resolve("continue;", false), vec![error.clone()]);
+        assert_eq!(resolve("for (;;) { fun f() { continue; } }", false), vec![error]);
+        assert!(resolve("for (var i = 0; i < 3; i = i + 1) { if (i == 1) continue; }", false).is_empty());
+    }
+}
@@ -1,55 +1,232 @@
+use std::{collections::HashSet, ops::Range, rc::Rc};
+
 use crate::token::*;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScanErrorKind {
+    UnexpectedCharacter,
+    InvalidNumber,
+    NumberOutOfRange,
+    UnterminatedString,
+    EmptyInterpolation,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanError {
+    pub line: usize,
+    /// The 1-based column where the offending text starts.
+    pub column: usize,
+    pub kind: ScanErrorKind,
+    pub message: String,
+}
+
+impl std::fmt::Display for ScanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at line {}", self.message, self.line)
+    }
+}
+
 pub struct Scanner<'a> {
     tokens: Vec<Token>,
+    /// Byte range in the source of each token, at the same index as the token.
+    spans: Vec<Range<usize>>,
     errors: &'a mut Vec<String>,
-    source: &'a str,
+    /// The errors also pushed into `errors`, kept structured for tooling.
+    scan_errors: Vec<ScanError>,
+    /// Identifier names seen so far, so repeated uses of a name share one allocation.
+    names: HashSet<Rc<str>>,
+    /// A copy of the source being scanned, so the scanner can be reset to sources that don't live as long as it does.
+    source: Rc<str>,
+    /// How many columns a tab advances, for reporting columns that line up with an editor.
+    tab_width: usize,
+    /// One entry per `${` being scanned, counting the braces opened inside of it that are still open. A `}` closing
+    /// the embedded expression resumes scanning the string.
+    interpolations: Vec<usize>,
     start: usize,
     current: usize,
     line: usize,
 }
 
 impl<'a> Scanner<'a> {
-    pub fn new(source: &'a str, errors: &'a mut Vec<String>) -> Scanner<'a> {
+    pub fn new(source: &str, errors: &'a mut Vec<String>) -> Scanner<'a> {
         Scanner {
             tokens: Vec::new(),
+            spans: Vec::new(),
             errors,
-            source,
+            scan_errors: Vec::new(),
+            names: HashSet::new(),
+            source: source.into(),
+            tab_width: 1,
+            interpolations: Vec::new(),
             start: 0,
             current: 0,
             line: 1,
         }
     }
 
+    pub fn tab_width(mut self, tab_width: usize) -> Self {
+        self.tab_width = tab_width;
+        self
+    }
+
     pub fn scan(&mut self) {
-        let mut chars = self.source.chars().peekable();
+        let source = Rc::clone(&self.source);
+        let mut chars = source.chars().peekable();
 
         while self.current < self.source.len() {
             self.start = self.current;
             self.scan_token(&mut chars);
+            self.spans.resize(self.tokens.len(), self.start..self.current);
+        }
+
+        // An embedded expression that was never closed leaves the string around it unterminated, unless that was
+        // already reported for a string inside the expression
+        let reported = self
+            .scan_errors
+            .last()
+            .is_some_and(|error| error.kind == ScanErrorKind::UnterminatedString);
+        if !self.interpolations.is_empty() && !reported {
+            self.error(ScanErrorKind::UnterminatedString, "Unterminated string".to_string());
         }
+        self.interpolations.clear();
 
         self.tokens.push(Token::Eof);
+        self.spans.push(self.source.len()..self.source.len());
     }
 
     pub fn into_tokens(self) -> Vec<Token> {
         self.tokens
     }
 
+    /// Like `into_tokens`, but also returns the byte range of each token in the source.
+    pub fn into_tokens_with_spans(self) -> (Vec<Token>, Vec<Range<usize>>) {
+        (self.tokens, self.spans)
+    }
+
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    pub fn spans(&self) -> &[Range<usize>] {
+        &self.spans
+    }
+
+    /// The 1-based column where the token at `index` starts, counting each character as one column except tabs, which
+    /// count as `tab_width`.
+    pub fn column(&self, index: usize) -> usize {
+        self.column_at(self.spans[index].start)
+    }
+
+    fn column_at(&self, start: usize) -> usize {
+        let line_start = self.source[..start].rfind('\n').map_or(0, |newline| newline + 1);
+
+        let width = self.source[line_start..start]
+            .chars()
+            .map(|c| if c == '\t' { self.tab_width } else { 1 })
+            .sum::<usize>();
+        width + 1
+    }
+
+    /// The errors found while scanning, in the order they were reported.
+    pub fn scan_errors(&self) -> &[ScanError] {
+        &self.scan_errors
+    }
+
+    fn error(&mut self, kind: ScanErrorKind, message: String) {
+        let error = ScanError {
+            line: self.line,
+            column: self.column_at(self.start),
+            kind,
+            message,
+        };
+        self.errors.push(error.to_string());
+        self.scan_errors.push(error);
+    }
+
+    /// Points the scanner at a new source, discarding the previous tokens and errors while keeping their allocation
+    /// around.
+    pub fn reset(&mut self, new_source: &str) {
+        self.tokens.clear();
+        self.spans.clear();
+        self.errors.clear();
+        self.scan_errors.clear();
+        self.interpolations.clear();
+        self.source = new_source.into();
+        self.start = 0;
+        self.current = 0;
+        self.line = 1;
+    }
+
     fn scan_token(&mut self, chars: &mut std::iter::Peekable<std::str::Chars>) {
         let char = self.advance(chars);
 
         match char {
             Some('(') => self.tokens.push(Token::LeftParen { line: self.line }),
             Some(')') => self.tokens.push(Token::RightParen { line: self.line }),
-            Some('{') => self.tokens.push(Token::LeftBrace { line: self.line }),
-            Some('}') => self.tokens.push(Token::RightBrace { line: self.line }),
+            Some('{') => {
+                if let Some(depth) = self.interpolations.last_mut() {
+                    *depth += 1;
+                }
+                self.tokens.push(Token::LeftBrace { line: self.line });
+            }
+            Some('}') => match self.interpolations.last_mut() {
+                Some(0) => {
+                    self.interpolations.pop();
+                    if let Some(Token::Interpolation { .. }) = self.tokens.last() {
+                        self.error(
+                            ScanErrorKind::EmptyInterpolation,
+                            "Expected expression inside '${}'".to_string(),
+                        );
+                    }
+                    self.string(chars);
+                }
+                Some(depth) => {
+                    *depth -= 1;
+                    self.tokens.push(Token::RightBrace { line: self.line });
+                }
+                None => self.tokens.push(Token::RightBrace { line: self.line }),
+            },
+            Some('[') => self.tokens.push(Token::LeftBracket { line: self.line }),
+            Some(']') => self.tokens.push(Token::RightBracket { line: self.line }),
             Some(',') => self.tokens.push(Token::Comma { line: self.line }),
+            Some('.') if self.source[self.current..].starts_with("..") => {
+                self.advance(chars);
+                self.advance(chars);
+                self.tokens.push(Token::Ellipsis { line: self.line });
+            }
+            Some('.') if self.source[self.current..].starts_with('.') => {
+                self.advance(chars);
+                self.tokens.push(Token::DotDot { line: self.line });
+            }
+            Some('.') if chars.peek().is_some_and(|c| c.is_ascii_digit()) => self.number(chars),
             Some('.') => self.tokens.push(Token::Dot { line: self.line }),
-            Some('-') => self.tokens.push(Token::Minus { line: self.line }),
-            Some('+') => self.tokens.push(Token::Plus { line: self.line }),
+            Some('-') => {
+                let token = if self.match_char('-', chars) {
+                    Token::MinusMinus { line: self.line }
+                } else {
+                    Token::Minus { line: self.line }
+                };
+                self.tokens.push(token);
+            }
+            Some('+') => {
+                let token = if self.match_char('+', chars) {
+                    Token::PlusPlus { line: self.line }
+                } else {
+                    Token::Plus { line: self.line }
+                };
+                self.tokens.push(token);
+            }
             Some(';') => self.tokens.push(Token::Semicolon { line: self.line }),
-            Some('*') => self.tokens.push(Token::Star { line: self.line }),
+            Some(':') => self.tokens.push(Token::Colon { line: self.line }),
+            Some('?') => self.tokens.push(Token::Question { line: self.line }),
+            Some('*') => {
+                let token = if self.match_char('*', chars) {
+                    Token::StarStar { line: self.line }
+                } else {
+                    Token::Star { line: self.line }
+                };
+                self.tokens.push(token);
+            }
             Some('!') => {
                 let token = if self.match_char('=', chars) {
                     Token::BangEqual { line: self.line }
@@ -84,9 +261,10 @@ impl<'a> Scanner<'a> {
             }
             Some('/') => {
                 if self.match_char('/', chars) {
-                    let comment = chars.take_while(|&c| c != '\n');
-                    self.current += comment.map(|c| c.len_utf8()).sum::<usize>();
-                    self.current += 1;
+                    // The line break ending the comment is left to be scanned on its own, so that it's counted
+                    while chars.peek().is_some_and(|&c| c != '\n') {
+                        self.advance(chars);
+                    }
                 } else {
                     self.tokens.push(Token::Slash { line: self.line });
                 }
@@ -99,14 +277,14 @@ impl<'a> Scanner<'a> {
             Some(c) if c.is_ascii_digit() => {
                 self.number(chars);
             }
-            Some(c) if c.is_alphanumeric() || c == '_' => {
+            Some(c) if c.is_alphabetic() || c == '_' => {
                 self.identifier(chars);
             }
             Some(c) => {
-                self.errors.push(format!(
-                    "Unexpected character '{}' at line {}",
-                    c, self.line
-                ));
+                self.error(
+                    ScanErrorKind::UnexpectedCharacter,
+                    format!("Unexpected character '{}'", c),
+                );
             }
             None => {}
         }
@@ -121,12 +299,20 @@ impl<'a> Scanner<'a> {
             }
         }
 
-        let text = &self.source[self.start..self.current];
+        let source = Rc::clone(&self.source);
+        let text = &source[self.start..self.current];
 
         match text {
             "and" => self.tokens.push(Token::And { line: self.line }),
+            "assert" => self.tokens.push(Token::Assert { line: self.line }),
+            "break" => self.tokens.push(Token::Break { line: self.line }),
             "class" => self.tokens.push(Token::Class { line: self.line }),
+            "catch" => self.tokens.push(Token::Catch { line: self.line }),
+            "const" => self.tokens.push(Token::Const { line: self.line }),
+            "continue" => self.tokens.push(Token::Continue { line: self.line }),
+            "do" => self.tokens.push(Token::Do { line: self.line }),
             "else" => self.tokens.push(Token::Else { line: self.line }),
+            "finally" => self.tokens.push(Token::Finally { line: self.line }),
             "false" => self.tokens.push(Token::False {
                 line: self.line,
                 value: false,
@@ -134,26 +320,48 @@ impl<'a> Scanner<'a> {
             "for" => self.tokens.push(Token::For { line: self.line }),
             "fun" => self.tokens.push(Token::Fun { line: self.line }),
             "if" => self.tokens.push(Token::If { line: self.line }),
+            "in" => self.tokens.push(Token::In { line: self.line }),
             "nil" => self.tokens.push(Token::Nil { line: self.line }),
             "or" => self.tokens.push(Token::Or { line: self.line }),
             "print" => self.tokens.push(Token::Print { line: self.line }),
             "return" => self.tokens.push(Token::Return { line: self.line }),
             "super" => self.tokens.push(Token::Super { line: self.line }),
             "this" => self.tokens.push(Token::This { line: self.line }),
+            "throw" => self.tokens.push(Token::Throw { line: self.line }),
+            "try" => self.tokens.push(Token::Try { line: self.line }),
             "true" => self.tokens.push(Token::True {
                 line: self.line,
                 value: true,
             }),
             "var" => self.tokens.push(Token::Var { line: self.line }),
             "while" => self.tokens.push(Token::While { line: self.line }),
-            _ => self.tokens.push(Token::Identifier(Identifier {
-                line: self.line,
-                value: text.to_string(),
-            })),
+            "with" => self.tokens.push(Token::With { line: self.line }),
+            "yield" => self.tokens.push(Token::Yield { line: self.line }),
+            _ => {
+                let value = self.intern(text);
+                self.tokens
+                    .push(Token::Identifier(Identifier { line: self.line, value }))
+            }
+        }
+    }
+
+    fn intern(&mut self, name: &str) -> Rc<str> {
+        if let Some(interned) = self.names.get(name) {
+            return interned.clone();
         }
+
+        let interned: Rc<str> = Rc::from(name);
+        self.names.insert(interned.clone());
+        interned
     }
 
     fn number(&mut self, chars: &mut std::iter::Peekable<std::str::Chars>) {
+        if self.source[self.start..].starts_with('0') && matches!(chars.peek(), Some('x') | Some('X')) {
+            self.advance(chars);
+            self.hex_number(chars);
+            return;
+        }
+
         loop {
             match chars.peek() {
                 Some(c) if c.is_ascii_digit() => {
@@ -163,37 +371,147 @@ impl<'a> Scanner<'a> {
             }
         }
 
-        if self.source[self.current..].starts_with('.') {
+        // Numbers starting with a dot, like `.5`, already consumed their fractional part
+        let leading_dot = self.source[self.start..].starts_with('.');
+        if !leading_dot && self.source[self.current..].starts_with('.') {
             let next_char = self.source[self.current + 1..].chars().next();
             if next_char.is_some() && next_char.unwrap().is_ascii_digit() {
                 self.advance(chars);
-                let digits = chars
-                    .take_while(|&c| c.is_ascii_digit())
-                    .collect::<String>();
-
-                self.current += digits.len();
+                while chars.peek().is_some_and(|c| c.is_ascii_digit()) {
+                    self.advance(chars);
+                }
             }
         }
 
         let number_str = &self.source[self.start..self.current];
-        self.tokens.push(Token::Number {
-            line: self.line,
-            value: number_str.parse().unwrap(),
-        });
+        match number_str.parse::<f64>() {
+            Ok(value) => self.push_number(value),
+            Err(_) => self.error(
+                ScanErrorKind::InvalidNumber,
+                format!("Invalid number literal '{}'", number_str),
+            ),
+        }
     }
 
+    /// Pushes a number token, unless the literal was too large to be represented and became infinite.
+    fn push_number(&mut self, value: f64) {
+        if value.is_finite() {
+            self.tokens.push(Token::Number {
+                line: self.line,
+                value,
+                lexeme: self.source[self.start..self.current].to_string(),
+            });
+        } else {
+            self.error(
+                ScanErrorKind::NumberOutOfRange,
+                "Number literal out of range".to_string(),
+            );
+        }
+    }
+
+    /// Scans hexadecimal literals such as `0xff` and hex floats with a binary exponent such as `0x1.8p3`, which is
+    /// `1.5 * 2^3`. A fractional part is only allowed when followed by an exponent.
+    fn hex_number(&mut self, chars: &mut std::iter::Peekable<std::str::Chars>) {
+        let mut mantissa = 0.0;
+        let mut digits = 0;
+        let mut fraction_digits = 0;
+        let mut exponent: Option<i32> = None;
+
+        while let Some(digit) = chars.peek().and_then(|c| c.to_digit(16)) {
+            mantissa = mantissa * 16.0 + digit as f64;
+            digits += 1;
+            self.advance(chars);
+        }
+
+        let next_char = self.source[self.current..].chars().nth(1);
+        if self.source[self.current..].starts_with('.') && next_char.is_some_and(|c| c.is_ascii_hexdigit()) {
+            self.advance(chars);
+            while let Some(digit) = chars.peek().and_then(|c| c.to_digit(16)) {
+                mantissa = mantissa * 16.0 + digit as f64;
+                fraction_digits += 1;
+                self.advance(chars);
+            }
+        }
+
+        if matches!(chars.peek(), Some('p') | Some('P')) {
+            self.advance(chars);
+            let sign = match chars.peek() {
+                Some('-') => {
+                    self.advance(chars);
+                    -1
+                }
+                Some('+') => {
+                    self.advance(chars);
+                    1
+                }
+                _ => 1,
+            };
+
+            let start = self.current;
+            while chars.peek().is_some_and(|c| c.is_ascii_digit()) {
+                self.advance(chars);
+            }
+            exponent = self.source[start..self.current].parse::<i32>().ok().map(|e| sign * e);
+        }
+
+        let malformed = chars.peek().is_some_and(|c| c.is_alphanumeric() || *c == '_');
+        while chars.peek().is_some_and(|c| c.is_alphanumeric() || *c == '_') {
+            self.advance(chars);
+        }
+
+        let has_exponent = self.source[self.start..self.current].contains(['p', 'P']);
+        let missing_exponent = (has_exponent || fraction_digits > 0) && exponent.is_none();
+        if malformed || missing_exponent || digits + fraction_digits == 0 {
+            let literal = &self.source[self.start..self.current];
+            self.error(
+                ScanErrorKind::InvalidNumber,
+                format!("Invalid hexadecimal literal '{}'", literal),
+            );
+            return;
+        }
+
+        let exponent = exponent.unwrap_or(0) - 4 * fraction_digits;
+        self.push_number(mantissa * 2f64.powi(exponent));
+    }
+
+    /// Scans a string literal, or the part of one up to an embedded `${...}` expression. That part is pushed as an
+    /// `Interpolation`, followed by the tokens of the expression, and the part after the closing `}` is scanned the
+    /// same way. The last part of the literal is a regular `String`.
     fn string(&mut self, chars: &mut std::iter::Peekable<std::str::Chars>) {
         let mut string_value = String::new();
 
         loop {
             match chars.peek() {
                 Some(&'"') => break,
+                Some(&'$') if self.source[self.current..].starts_with("${") => {
+                    self.advance(chars);
+                    self.advance(chars);
+                    self.interpolations.push(0);
+                    self.tokens.push(Token::Interpolation {
+                        line: self.line,
+                        value: string_value,
+                    });
+                    return;
+                }
                 Some(&'\n') => {
                     string_value.push('\n');
                     self.line += 1;
                     self.current += 1;
                     chars.next();
                 }
+                // Windows and old Mac line endings are stored as `\n`. Only the `\n` of a `\r\n` pair counts as a new
+                // line, as outside of strings
+                Some(&'\r') => {
+                    string_value.push('\n');
+                    self.current += 1;
+                    chars.next();
+
+                    if chars.peek() == Some(&'\n') {
+                        self.line += 1;
+                        self.current += 1;
+                        chars.next();
+                    }
+                }
                 Some(_) => {
                     if let Some(c) = chars.next() {
                         string_value.push(c);
@@ -207,16 +525,14 @@ impl<'a> Scanner<'a> {
         let closing_quote = self.advance(chars);
 
         match closing_quote {
-            Some(quote) => {
-                self.current += quote.len_utf8();
+            Some(_) => {
                 self.tokens.push(Token::String {
                     line: self.line,
                     value: string_value,
                 });
             }
             None => {
-                self.errors
-                    .push(format!("Unterminated string at line {}", self.line));
+                self.error(ScanErrorKind::UnterminatedString, "Unterminated string".to_string());
             }
         }
     }
@@ -268,11 +584,15 @@ mod tests {
             (')', Token::RightParen { line: 1 }),
             ('{', Token::LeftBrace { line: 1 }),
             ('}', Token::RightBrace { line: 1 }),
+            ('[', Token::LeftBracket { line: 1 }),
+            (']', Token::RightBracket { line: 1 }),
             (',', Token::Comma { line: 1 }),
             ('.', Token::Dot { line: 1 }),
             ('-', Token::Minus { line: 1 }),
             ('+', Token::Plus { line: 1 }),
             (';', Token::Semicolon { line: 1 }),
+            (':', Token::Colon { line: 1 }),
+            ('?', Token::Question { line: 1 }),
             ('*', Token::Star { line: 1 }),
             ('/', Token::Slash { line: 1 }),
         ];
@@ -296,6 +616,11 @@ mod tests {
             (">=".to_string(), Token::GreaterEqual { line: 1 }),
             ("<".to_string(), Token::Less { line: 1 }),
             ("<=".to_string(), Token::LessEqual { line: 1 }),
+            ("**".to_string(), Token::StarStar { line: 1 }),
+            ("...".to_string(), Token::Ellipsis { line: 1 }),
+            ("..".to_string(), Token::DotDot { line: 1 }),
+            ("++".to_string(), Token::PlusPlus { line: 1 }),
+            ("--".to_string(), Token::MinusMinus { line: 1 }),
         ];
 
         for (source, token) in map {
@@ -313,6 +638,74 @@ mod tests {
         assert_eq!(tokens[0], Token::Eof);
     }
 
+    #[test]
+    fn test_scanning_comments_with_multibyte_characters() {
+        let source = "print 1; // café ☕";
+        let mut errors = Vec::new();
+        let mut scanner = Scanner::new(source, &mut errors);
+        scanner.scan();
+
+        assert_eq!(scanner.current, source.len());
+        assert_eq!(scanner.tokens().len(), 4);
+
+        // The line break after a comment still counts
+        let tokens = scan("// naïve\nprint 1;");
+        assert_eq!(tokens[0], Token::Print { line: 2 });
+    }
+
+    #[test]
+    fn test_scanning_interpolated_strings() {
+        let tokens = scan("\"a${1+1}b\"");
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Interpolation {
+                    value: "a".to_string(),
+                    line: 1
+                },
+                Token::Number {
+                    value: 1.0,
+                    lexeme: "1".to_string(),
+                    line: 1
+                },
+                Token::Plus { line: 1 },
+                Token::Number {
+                    value: 1.0,
+                    lexeme: "1".to_string(),
+                    line: 1
+                },
+                Token::String {
+                    value: "b".to_string(),
+                    line: 1
+                },
+                Token::Eof,
+            ]
+        );
+
+        // Braces inside the embedded expression don't end it, and a `$` without `{` is kept as is
+        let tokens = scan("\"${ {\"k\": 1} }$x\"");
+        assert_eq!(tokens[1], Token::LeftBrace { line: 1 });
+        assert_eq!(tokens[5], Token::RightBrace { line: 1 });
+        assert_eq!(
+            tokens[6],
+            Token::String {
+                value: "$x".to_string(),
+                line: 1
+            }
+        );
+
+        for (source, kind) in [
+            ("\"a${1", ScanErrorKind::UnterminatedString),
+            ("\"a${}\"", ScanErrorKind::EmptyInterpolation),
+        ] {
+            let mut errors = Vec::new();
+            let mut scanner = Scanner::new(source, &mut errors);
+            scanner.scan();
+            let kinds = scanner.scan_errors().iter().map(|error| error.kind).collect::<Vec<_>>();
+            assert_eq!(kinds, vec![kind], "{}", source);
+        }
+    }
+
     #[test]
     fn test_scanning_strings() {
         let source = "\"some string content\"".to_string();
@@ -327,6 +720,85 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_scanning_strings_normalizes_carriage_returns() {
+        let tokens = scan("\"a\r\nb\rc\"\r\nx");
+        assert_eq!(
+            tokens[0],
+            Token::String {
+                value: "a\nb\nc".to_string(),
+                line: 2
+            }
+        );
+        assert_eq!(tokens[1].line(), 3);
+    }
+
+    #[test]
+    fn test_columns_count_tabs_as_the_tab_width() {
+        let mut errors = Vec::new();
+        let mut scanner = Scanner::new("x\n\tvar é = 1;", &mut errors);
+        scanner.scan();
+        assert_eq!(scanner.column(0), 1);
+        assert_eq!(scanner.column(1), 2);
+        assert_eq!(scanner.column(3), 8);
+
+        let mut scanner = Scanner::new("x\n\tvar é = 1;", &mut errors).tab_width(4);
+        scanner.scan();
+        assert_eq!(scanner.column(1), 5);
+        assert_eq!(scanner.column(3), 11);
+    }
+
+    #[test]
+    fn test_scanning_out_of_range_numbers() {
+        for source in [
+            "1".repeat(400),
+            format!("0x{}", "f".repeat(300)),
+            "0x1p5000".to_string(),
+        ] {
+            let mut errors = Vec::new();
+            let mut scanner = Scanner::new(&source, &mut errors);
+            scanner.scan();
+
+            assert_eq!(scanner.tokens(), [Token::Eof]);
+            assert_eq!(scanner.scan_errors()[0].kind, ScanErrorKind::NumberOutOfRange);
+            assert_eq!(errors, vec!["Number literal out of range at line 1".to_string()]);
+        }
+    }
+
+    #[test]
+    fn test_scanning_concatenation() {
+        let tokens = scan("\"n=\" .. 42");
+        assert_eq!(tokens[1], Token::DotDot { line: 1 });
+        assert_eq!(
+            tokens[2],
+            Token::Number {
+                value: 42.0,
+                lexeme: "42".to_string(),
+                line: 1
+            }
+        );
+
+        // A dot right after a number is not part of it unless a digit follows
+        let tokens = scan("1..2");
+        assert_eq!(
+            tokens[0],
+            Token::Number {
+                value: 1.0,
+                lexeme: "1".to_string(),
+                line: 1
+            }
+        );
+        assert_eq!(tokens[1], Token::DotDot { line: 1 });
+        assert_eq!(
+            tokens[2],
+            Token::Number {
+                value: 2.0,
+                lexeme: "2".to_string(),
+                line: 1
+            }
+        );
+    }
+
     #[test]
     fn test_scanning_numbers() {
         let source = "123".to_string();
@@ -336,6 +808,7 @@ mod tests {
             tokens[0],
             Token::Number {
                 value: 123.0,
+                lexeme: "123".to_string(),
                 line: 1
             }
         );
@@ -350,11 +823,118 @@ mod tests {
             tokens[0],
             Token::Number {
                 value: 123.321,
+                lexeme: "123.321".to_string(),
                 line: 1
             }
         );
     }
 
+    #[test]
+    fn test_scanning_fractional_numbers_followed_by_tokens() {
+        let source = "1.5;2".to_string();
+        let tokens = scan(&source);
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(
+            tokens[0],
+            Token::Number {
+                value: 1.5,
+                lexeme: "1.5".to_string(),
+                line: 1
+            }
+        );
+        assert_eq!(tokens[1], Token::Semicolon { line: 1 });
+        assert_eq!(
+            tokens[2],
+            Token::Number {
+                value: 2.0,
+                lexeme: "2".to_string(),
+                line: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_scanning_numbers_with_leading_and_trailing_dots() {
+        // A leading dot starts a number, while a trailing one stays a separate token for method calls such as `5.abs`
+        assert_eq!(
+            scan(".5"),
+            vec![
+                Token::Number {
+                    value: 0.5,
+                    lexeme: ".5".to_string(),
+                    line: 1
+                },
+                Token::Eof
+            ]
+        );
+        assert_eq!(
+            scan("5."),
+            vec![
+                Token::Number {
+                    value: 5.0,
+                    lexeme: "5".to_string(),
+                    line: 1
+                },
+                Token::Dot { line: 1 },
+                Token::Eof
+            ]
+        );
+        assert_eq!(
+            scan(".5.5"),
+            vec![
+                Token::Number {
+                    value: 0.5,
+                    lexeme: ".5".to_string(),
+                    line: 1
+                },
+                Token::Number {
+                    value: 0.5,
+                    lexeme: ".5".to_string(),
+                    line: 1
+                },
+                Token::Eof
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scanning_hexadecimal_numbers() {
+        let map = vec![
+            ("0xff", 255.0),
+            ("0X1A", 26.0),
+            ("0x1.8p3", 12.0),
+            ("0x1p-2", 0.25),
+            ("0xA.4P+1", 20.5),
+        ];
+
+        for (source, value) in map {
+            let tokens = scan(source);
+            assert_eq!(tokens.len(), 2, "Unexpected tokens for {}", source);
+            assert_eq!(
+                tokens[0],
+                Token::Number {
+                    value,
+                    lexeme: source.to_string(),
+                    line: 1
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn test_scanning_malformed_hexadecimal_numbers() {
+        for source in ["0x", "0x1.8", "0x1p", "0x1.8pz", "0xfg"] {
+            let mut errors = Vec::new();
+            let mut scanner = Scanner::new(source, &mut errors);
+            scanner.scan();
+            assert_eq!(scanner.tokens(), &[Token::Eof], "Unexpected tokens for {}", source);
+            assert_eq!(
+                errors,
+                vec![format!("Invalid hexadecimal literal '{}' at line 1", source)]
+            );
+        }
+    }
+
     #[test]
     fn test_scanning_identifiers() {
         let source = "iDentifier_".to_string();
@@ -363,18 +943,114 @@ mod tests {
         assert_eq!(
             tokens[0],
             Token::Identifier(Identifier {
-                value: "iDentifier_".to_string(),
+                value: "iDentifier_".into(),
                 line: 1
             })
         );
     }
 
+    #[test]
+    fn test_scanning_repeated_identifiers_shares_names() {
+        let tokens = scan("x = x + y;");
+        match (&tokens[0], &tokens[2], &tokens[4]) {
+            (Token::Identifier(first), Token::Identifier(second), Token::Identifier(other)) => {
+                assert!(Rc::ptr_eq(&first.value, &second.value));
+                assert!(!Rc::ptr_eq(&first.value, &other.value));
+            }
+            _ => panic!("Expected identifiers"),
+        }
+    }
+
+    #[test]
+    fn test_scanning_unicode_identifiers() {
+        let tokens = scan("café _naïve2");
+        assert_eq!(
+            tokens[..2],
+            [
+                Token::Identifier(Identifier {
+                    value: "café".into(),
+                    line: 1
+                }),
+                Token::Identifier(Identifier {
+                    value: "_naïve2".into(),
+                    line: 1
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scanning_identifiers_starting_with_digits() {
+        let tokens = scan("1abc");
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(
+            tokens[0],
+            Token::Number {
+                value: 1.0,
+                lexeme: "1".to_string(),
+                line: 1
+            }
+        );
+        assert_eq!(
+            tokens[1],
+            Token::Identifier(Identifier {
+                value: "abc".into(),
+                line: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_recording_token_spans() {
+        let mut errors = Vec::new();
+        let mut scanner = Scanner::new("print x;\nvar café = \"a b\";", &mut errors);
+        scanner.scan();
+        let (tokens, spans) = scanner.into_tokens_with_spans();
+
+        assert_eq!(tokens.len(), spans.len());
+        assert_eq!(tokens[0], Token::Print { line: 1 });
+        assert_eq!(spans[0], 0..5);
+        assert_eq!(spans[1], 6..7);
+        assert_eq!(spans[2], 7..8);
+        assert_eq!(spans[4], 13..18);
+        assert_eq!(spans[6], 21..26);
+        assert_eq!(spans[8], 27..27);
+    }
+
+    #[test]
+    fn test_scan_errors_are_structured() {
+        let mut errors = Vec::new();
+        let mut scanner = Scanner::new("@\n  \"open", &mut errors);
+        scanner.scan();
+
+        let scan_errors = scanner.scan_errors().to_vec();
+        assert_eq!(scan_errors.len(), 2);
+        assert_eq!(scan_errors[0].kind, ScanErrorKind::UnexpectedCharacter);
+        assert_eq!((scan_errors[0].line, scan_errors[0].column), (1, 1));
+        assert_eq!(scan_errors[1].kind, ScanErrorKind::UnterminatedString);
+        assert_eq!((scan_errors[1].line, scan_errors[1].column), (2, 3));
+        assert_eq!(
+            errors,
+            vec![
+                "Unexpected character '@' at line 1".to_string(),
+                "Unterminated string at line 2".to_string()
+            ]
+        );
+    }
+
     #[test]
     fn test_scanning_keywords() {
         let keywords = vec![
             ("and".to_string(), Token::And { line: 1 }),
+            ("assert".to_string(), Token::Assert { line: 1 }),
+            ("break".to_string(), Token::Break { line: 1 }),
             ("class".to_string(), Token::Class { line: 1 }),
+            ("catch".to_string(), Token::Catch { line: 1 }),
+            ("const".to_string(), Token::Const { line: 1 }),
+            ("continue".to_string(), Token::Continue { line: 1 }),
+            ("do".to_string(), Token::Do { line: 1 }),
             ("else".to_string(), Token::Else { line: 1 }),
+            ("finally".to_string(), Token::Finally { line: 1 }),
             (
                 "false".to_string(),
                 Token::False {
@@ -385,12 +1061,15 @@ mod tests {
             ("for".to_string(), Token::For { line: 1 }),
             ("fun".to_string(), Token::Fun { line: 1 }),
             ("if".to_string(), Token::If { line: 1 }),
+            ("in".to_string(), Token::In { line: 1 }),
             ("nil".to_string(), Token::Nil { line: 1 }),
             ("or".to_string(), Token::Or { line: 1 }),
             ("print".to_string(), Token::Print { line: 1 }),
             ("return".to_string(), Token::Return { line: 1 }),
             ("super".to_string(), Token::Super { line: 1 }),
             ("this".to_string(), Token::This { line: 1 }),
+            ("throw".to_string(), Token::Throw { line: 1 }),
+            ("try".to_string(), Token::Try { line: 1 }),
             (
                 "true".to_string(),
                 Token::True {
@@ -400,6 +1079,8 @@ mod tests {
             ),
             ("var".to_string(), Token::Var { line: 1 }),
             ("while".to_string(), Token::While { line: 1 }),
+            ("with".to_string(), Token::With { line: 1 }),
+            ("yield".to_string(), Token::Yield { line: 1 }),
         ];
 
         for (keyword, token) in keywords {
@@ -409,4 +1090,55 @@ mod tests {
             assert_eq!(tokens[0], token);
         }
     }
+
+    #[test]
+    fn test_reusing_the_scanner_for_multiple_sources() {
+        let mut errors = Vec::new();
+        // Each source is a heap string dropped right after being handed over, like a request a server is done with
+        let mut scanner = Scanner::new(&String::from("var x\n= 1;"), &mut errors);
+        scanner.scan();
+
+        assert_eq!(
+            scanner.tokens(),
+            &[
+                Token::Var { line: 1 },
+                Token::Identifier(Identifier {
+                    value: "x".into(),
+                    line: 1
+                }),
+                Token::Equal { line: 2 },
+                Token::Number {
+                    value: 1.0,
+                    lexeme: "1".to_string(),
+                    line: 2
+                },
+                Token::Semicolon { line: 2 },
+                Token::Eof,
+            ]
+        );
+
+        scanner.reset(&String::from("print @;"));
+        scanner.scan();
+        assert_eq!(scanner.scan_errors().len(), 1);
+
+        // Errors from the previous source are discarded along with its tokens
+        scanner.reset(&String::from("print y;"));
+        scanner.scan();
+
+        assert_eq!(
+            scanner.tokens(),
+            &[
+                Token::Print { line: 1 },
+                Token::Identifier(Identifier {
+                    value: "y".into(),
+                    line: 1
+                }),
+                Token::Semicolon { line: 1 },
+                Token::Eof,
+            ]
+        );
+        assert!(scanner.scan_errors().is_empty());
+        drop(scanner);
+        assert!(errors.is_empty());
+    }
 }
@@ -1,16 +1,27 @@
+use std::rc::Rc;
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Token {
     LeftParen { line: usize },
     RightParen { line: usize },
     LeftBrace { line: usize },
     RightBrace { line: usize },
+    LeftBracket { line: usize },
+    RightBracket { line: usize },
     Comma { line: usize },
     Dot { line: usize },
+    Ellipsis { line: usize },
+    DotDot { line: usize },
     Minus { line: usize },
+    MinusMinus { line: usize },
     Plus { line: usize },
+    PlusPlus { line: usize },
     Semicolon { line: usize },
+    Colon { line: usize },
+    Question { line: usize },
     Slash { line: usize },
     Star { line: usize },
+    StarStar { line: usize },
     Bang { line: usize },
     BangEqual { line: usize },
     Equal { line: usize },
@@ -21,29 +32,43 @@ pub enum Token {
     LessEqual { line: usize },
     Identifier(Identifier),
     String { value: String, line: usize },
-    Number { value: f64, line: usize },
+    Interpolation { value: String, line: usize },
+    Number { value: f64, lexeme: String, line: usize },
     And { line: usize },
+    Assert { line: usize },
+    Break { line: usize },
     Class { line: usize },
+    Catch { line: usize },
+    Const { line: usize },
+    Continue { line: usize },
+    Do { line: usize },
     Else { line: usize },
+    Finally { line: usize },
     False { value: bool, line: usize },
     Fun { line: usize },
     For { line: usize },
     If { line: usize },
+    In { line: usize },
     Nil { line: usize },
     Or { line: usize },
     Print { line: usize },
     Return { line: usize },
     Super { line: usize },
     This { line: usize },
+    Throw { line: usize },
+    Try { line: usize },
     True { value: bool, line: usize },
     Var { line: usize },
     While { line: usize },
+    With { line: usize },
+    Yield { line: usize },
     Eof,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Identifier {
-    pub value: String,
+    /// The name, shared by every token for the same identifier in a source so that cloning it is cheap.
+    pub value: Rc<str>,
     pub line: usize,
 }
 
@@ -60,13 +85,22 @@ impl std::fmt::Display for Token {
             Token::RightParen { line } => write!(f, "RightParen({})", line),
             Token::LeftBrace { line } => write!(f, "LeftBrace({})", line),
             Token::RightBrace { line } => write!(f, "RightBrace({})", line),
+            Token::LeftBracket { line } => write!(f, "LeftBracket({})", line),
+            Token::RightBracket { line } => write!(f, "RightBracket({})", line),
             Token::Comma { line } => write!(f, "Comma({})", line),
             Token::Dot { line } => write!(f, "Dot({})", line),
+            Token::Ellipsis { line } => write!(f, "Ellipsis({})", line),
+            Token::DotDot { line } => write!(f, "DotDot({})", line),
             Token::Minus { line } => write!(f, "Minus({})", line),
+            Token::MinusMinus { line } => write!(f, "MinusMinus({})", line),
             Token::Plus { line } => write!(f, "Plus({})", line),
+            Token::PlusPlus { line } => write!(f, "PlusPlus({})", line),
             Token::Semicolon { line } => write!(f, "Semicolon({})", line),
+            Token::Colon { line } => write!(f, "Colon({})", line),
+            Token::Question { line } => write!(f, "Question({})", line),
             Token::Slash { line } => write!(f, "Slash({})", line),
             Token::Star { line } => write!(f, "Star({})", line),
+            Token::StarStar { line } => write!(f, "StarStar({})", line),
             Token::Bang { line } => write!(f, "Bang({})", line),
             Token::BangEqual { line } => write!(f, "BangEqual({})", line),
             Token::Equal { line } => write!(f, "Equal({})", line),
@@ -79,23 +113,36 @@ impl std::fmt::Display for Token {
                 write!(f, "Identifier({}): {}", identifier.line, identifier.value)
             }
             Token::String { value, line } => write!(f, "String({}): {}", line, value),
-            Token::Number { value, line } => write!(f, "Number({}): {}", line, value),
+            Token::Interpolation { value, line } => write!(f, "Interpolation({}): {}", line, value),
+            Token::Number { value, lexeme: _, line } => write!(f, "Number({}): {}", line, value),
             Token::And { line } => write!(f, "And({})", line),
+            Token::Assert { line } => write!(f, "Assert({})", line),
+            Token::Break { line } => write!(f, "Break({})", line),
             Token::Class { line } => write!(f, "Class({})", line),
+            Token::Catch { line } => write!(f, "Catch({})", line),
+            Token::Const { line } => write!(f, "Const({})", line),
+            Token::Continue { line } => write!(f, "Continue({})", line),
+            Token::Do { line } => write!(f, "Do({})", line),
             Token::Else { line } => write!(f, "Else({})", line),
+            Token::Finally { line } => write!(f, "Finally({})", line),
             Token::False { value, line } => write!(f, "False({}): {}", line, value),
             Token::Fun { line } => write!(f, "Fun({})", line),
             Token::For { line } => write!(f, "For({})", line),
             Token::If { line } => write!(f, "If({})", line),
+            Token::In { line } => write!(f, "In({})", line),
             Token::Nil { line } => write!(f, "Nil({})", line),
             Token::Or { line } => write!(f, "Or({})", line),
             Token::Print { line } => write!(f, "Print({})", line),
             Token::Return { line } => write!(f, "Return({})", line),
             Token::Super { line } => write!(f, "Super({})", line),
             Token::This { line } => write!(f, "This({})", line),
+            Token::Throw { line } => write!(f, "Throw({})", line),
+            Token::Try { line } => write!(f, "Try({})", line),
             Token::True { value, line } => write!(f, "True({}): {}", value, line),
             Token::Var { line } => write!(f, "Var({})", line),
             Token::While { line } => write!(f, "While({})", line),
+            Token::With { line } => write!(f, "With({})", line),
+            Token::Yield { line } => write!(f, "Yield({})", line),
             Token::Eof => write!(f, "Eof"),
         }
     }
@@ -108,13 +155,22 @@ impl Token {
             Token::RightParen { line } => *line,
             Token::LeftBrace { line } => *line,
             Token::RightBrace { line } => *line,
+            Token::LeftBracket { line } => *line,
+            Token::RightBracket { line } => *line,
             Token::Comma { line } => *line,
             Token::Dot { line } => *line,
+            Token::Ellipsis { line } => *line,
+            Token::DotDot { line } => *line,
             Token::Minus { line } => *line,
+            Token::MinusMinus { line } => *line,
             Token::Plus { line } => *line,
+            Token::PlusPlus { line } => *line,
             Token::Semicolon { line } => *line,
+            Token::Colon { line } => *line,
+            Token::Question { line } => *line,
             Token::Slash { line } => *line,
             Token::Star { line } => *line,
+            Token::StarStar { line } => *line,
             Token::Bang { line } => *line,
             Token::BangEqual { line } => *line,
             Token::Equal { line } => *line,
@@ -125,40 +181,135 @@ impl Token {
             Token::LessEqual { line } => *line,
             Token::Identifier(identifier) => identifier.line,
             Token::String { value: _, line } => *line,
-            Token::Number { value: _, line } => *line,
+            Token::Interpolation { value: _, line } => *line,
+            Token::Number {
+                value: _,
+                lexeme: _,
+                line,
+            } => *line,
             Token::And { line } => *line,
+            Token::Assert { line } => *line,
+            Token::Break { line } => *line,
             Token::Class { line } => *line,
+            Token::Catch { line } => *line,
+            Token::Const { line } => *line,
+            Token::Continue { line } => *line,
+            Token::Do { line } => *line,
             Token::Else { line } => *line,
+            Token::Finally { line } => *line,
             Token::False { value: _, line } => *line,
             Token::Fun { line } => *line,
             Token::For { line } => *line,
             Token::If { line } => *line,
+            Token::In { line } => *line,
             Token::Nil { line } => *line,
             Token::Or { line } => *line,
             Token::Print { line } => *line,
             Token::Return { line } => *line,
             Token::Super { line } => *line,
             Token::This { line } => *line,
+            Token::Throw { line } => *line,
+            Token::Try { line } => *line,
             Token::True { value: _, line } => *line,
             Token::Var { line } => *line,
             Token::While { line } => *line,
+            Token::With { line } => *line,
+            Token::Yield { line } => *line,
             Token::Eof => 0,
         }
     }
 
+    /// The name of the token's kind, e.g. `Print` or `Identifier`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Token::LeftParen { .. } => "LeftParen",
+            Token::RightParen { .. } => "RightParen",
+            Token::LeftBrace { .. } => "LeftBrace",
+            Token::RightBrace { .. } => "RightBrace",
+            Token::LeftBracket { .. } => "LeftBracket",
+            Token::RightBracket { .. } => "RightBracket",
+            Token::Comma { .. } => "Comma",
+            Token::Dot { .. } => "Dot",
+            Token::Ellipsis { .. } => "Ellipsis",
+            Token::DotDot { .. } => "DotDot",
+            Token::Minus { .. } => "Minus",
+            Token::MinusMinus { .. } => "MinusMinus",
+            Token::Plus { .. } => "Plus",
+            Token::PlusPlus { .. } => "PlusPlus",
+            Token::Semicolon { .. } => "Semicolon",
+            Token::Colon { .. } => "Colon",
+            Token::Question { .. } => "Question",
+            Token::Slash { .. } => "Slash",
+            Token::Star { .. } => "Star",
+            Token::StarStar { .. } => "StarStar",
+            Token::Bang { .. } => "Bang",
+            Token::BangEqual { .. } => "BangEqual",
+            Token::Equal { .. } => "Equal",
+            Token::EqualEqual { .. } => "EqualEqual",
+            Token::Greater { .. } => "Greater",
+            Token::GreaterEqual { .. } => "GreaterEqual",
+            Token::Less { .. } => "Less",
+            Token::LessEqual { .. } => "LessEqual",
+            Token::Identifier(_) => "Identifier",
+            Token::String { .. } => "String",
+            Token::Interpolation { .. } => "Interpolation",
+            Token::Number { .. } => "Number",
+            Token::And { .. } => "And",
+            Token::Assert { .. } => "Assert",
+            Token::Break { .. } => "Break",
+            Token::Class { .. } => "Class",
+            Token::Catch { .. } => "Catch",
+            Token::Const { .. } => "Const",
+            Token::Continue { .. } => "Continue",
+            Token::Do { .. } => "Do",
+            Token::Else { .. } => "Else",
+            Token::Finally { .. } => "Finally",
+            Token::False { .. } => "False",
+            Token::Fun { .. } => "Fun",
+            Token::For { .. } => "For",
+            Token::If { .. } => "If",
+            Token::In { .. } => "In",
+            Token::Nil { .. } => "Nil",
+            Token::Or { .. } => "Or",
+            Token::Print { .. } => "Print",
+            Token::Return { .. } => "Return",
+            Token::Super { .. } => "Super",
+            Token::This { .. } => "This",
+            Token::Throw { .. } => "Throw",
+            Token::Try { .. } => "Try",
+            Token::True { .. } => "True",
+            Token::Var { .. } => "Var",
+            Token::While { .. } => "While",
+            Token::With { .. } => "With",
+            Token::Yield { .. } => "Yield",
+            Token::Eof => "Eof",
+        }
+    }
+
+    /// The text the token is written as. Numbers keep the form they were written in (`1.50` stays `1.50`), while
+    /// strings come out without their quotes. The end of the input has no text.
     pub fn lexeme(&self) -> String {
         match self {
             Token::LeftParen { line: _ } => "(".to_string(),
             Token::RightParen { line: _ } => ")".to_string(),
             Token::LeftBrace { line: _ } => "{".to_string(),
             Token::RightBrace { line: _ } => "}".to_string(),
+            Token::LeftBracket { line: _ } => "[".to_string(),
+            Token::RightBracket { line: _ } => "]".to_string(),
             Token::Comma { line: _ } => ",".to_string(),
             Token::Dot { line: _ } => ".".to_string(),
+            Token::Ellipsis { line: _ } => "...".to_string(),
+            Token::DotDot { line: _ } => "..".to_string(),
             Token::Minus { line: _ } => "-".to_string(),
+            Token::MinusMinus { line: _ } => "--".to_string(),
             Token::Plus { line: _ } => "+".to_string(),
+            Token::PlusPlus { line: _ } => "++".to_string(),
             Token::Semicolon { line: _ } => ";".to_string(),
+            Token::Colon { line: _ } => ":".to_string(),
+            Token::Question { line: _ } => "?".to_string(),
             Token::Slash { line: _ } => "/".to_string(),
             Token::Star { line: _ } => "*".to_string(),
+            Token::StarStar { line: _ } => "**".to_string(),
             Token::Bang { line: _ } => "!".to_string(),
             Token::BangEqual { line: _ } => "!=".to_string(),
             Token::Equal { line: _ } => "=".to_string(),
@@ -167,26 +318,104 @@ impl Token {
             Token::GreaterEqual { line: _ } => ">=".to_string(),
             Token::Less { line: _ } => "<".to_string(),
             Token::LessEqual { line: _ } => "<=".to_string(),
-            Token::Identifier(identifier) => identifier.value.clone(),
+            Token::Identifier(identifier) => identifier.value.to_string(),
             Token::String { value, line: _ } => value.clone(),
-            Token::Number { value, line: _ } => value.to_string(),
+            Token::Interpolation { value, line: _ } => value.clone(),
+            Token::Number {
+                value: _,
+                lexeme,
+                line: _,
+            } => lexeme.clone(),
             Token::And { line: _ } => "and".to_string(),
+            Token::Assert { line: _ } => "assert".to_string(),
+            Token::Break { line: _ } => "break".to_string(),
             Token::Class { line: _ } => "class".to_string(),
+            Token::Catch { line: _ } => "catch".to_string(),
+            Token::Const { line: _ } => "const".to_string(),
+            Token::Continue { line: _ } => "continue".to_string(),
+            Token::Do { line: _ } => "do".to_string(),
             Token::Else { line: _ } => "else".to_string(),
+            Token::Finally { line: _ } => "finally".to_string(),
             Token::False { value: _, line: _ } => "false".to_string(),
             Token::Fun { line: _ } => "fun".to_string(),
             Token::For { line: _ } => "for".to_string(),
             Token::If { line: _ } => "if".to_string(),
+            Token::In { line: _ } => "in".to_string(),
             Token::Nil { line: _ } => "nil".to_string(),
             Token::Or { line: _ } => "or".to_string(),
             Token::Print { line: _ } => "print".to_string(),
             Token::Return { line: _ } => "return".to_string(),
             Token::Super { line: _ } => "super".to_string(),
             Token::This { line: _ } => "this".to_string(),
+            Token::Throw { line: _ } => "throw".to_string(),
+            Token::Try { line: _ } => "try".to_string(),
             Token::True { value: _, line: _ } => "true".to_string(),
             Token::Var { line: _ } => "var".to_string(),
             Token::While { line: _ } => "while".to_string(),
+            Token::With { line: _ } => "with".to_string(),
+            Token::Yield { line: _ } => "yield".to_string(),
             Token::Eof => "".to_string(),
         }
     }
 }
+
+/// Formats tokens one per line with their line number, kind and lexeme, as printed by `--print-tokens`. `Eof` has no
+/// line of its own, so it is shown on the line of the token before it.
+pub fn format_tokens(tokens: &[Token]) -> String {
+    let mut line = 1;
+
+    tokens
+        .iter()
+        .map(|token| {
+            if *token != Token::Eof {
+                line = token.line();
+            }
+
+            format!("{:<5}{:<14}{:?}", line, token.name(), token.lexeme())
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::Scanner;
+
+    #[test]
+    fn test_lexemes() {
+        assert_eq!(Token::While { line: 1 }.lexeme(), "while");
+        assert_eq!(Token::DotDot { line: 1 }.lexeme(), "..");
+        assert_eq!(
+            Token::Identifier(Identifier {
+                value: "count".into(),
+                line: 1
+            })
+            .lexeme(),
+            "count"
+        );
+        assert_eq!(Token::Eof.lexeme(), "");
+
+        let mut errors = Vec::new();
+        let mut scanner = Scanner::new("42 1.50 .5 0xFF", &mut errors);
+        scanner.scan();
+        let lexemes: Vec<String> = scanner.into_tokens().iter().map(Token::lexeme).collect();
+        assert_eq!(lexemes, vec!["42", "1.50", ".5", "0xFF", ""]);
+    }
+
+    #[test]
+    fn test_formatting_tokens() {
+        let mut errors = Vec::new();
+        let mut scanner = Scanner::new("print 1;", &mut errors);
+        scanner.scan();
+
+        assert_eq!(
+            format_tokens(&scanner.into_tokens()),
+            "1    Print         \"print\"\n1    Number        \"1\"\n1    Semicolon     \";\"\n1    Eof           \"\""
+        );
+
+        let mut scanner = Scanner::new("print 1;\n\nprint 2;", &mut errors);
+        scanner.scan();
+        assert!(format_tokens(&scanner.into_tokens()).ends_with("\n3    Eof           \"\""));
+    }
+}
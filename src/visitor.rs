@@ -1,5 +1,10 @@
-use crate::ast::{
-    Assignment, Binary, Grouping, Literal, LiteralValue, Logical, Node, Statement, Stmt, Unary, Variable,
+use crate::{
+    ast::{
+        ArrayLiteral, Assignment, Binary, Call, FunctionExpr, FunctionStatement, Get, Grouping, Index, IndexSet,
+        Literal, LiteralValue, Logical, MapLiteral, Node, Postfix, Set, Spread, Statement, Stmt, Super, Ternary, This,
+        Unary, Variable, Yield,
+    },
+    json,
 };
 
 pub trait Visitor {
@@ -8,9 +13,23 @@ pub trait Visitor {
     fn visit_grouping(&mut self, grouping: &Grouping) -> Self::Output;
     fn visit_literal(&mut self, literal: &Literal) -> Self::Output;
     fn visit_logical(&mut self, logical: &Logical) -> Self::Output;
+    fn visit_ternary(&mut self, ternary: &Ternary) -> Self::Output;
     fn visit_unary(&mut self, unary: &Unary) -> Self::Output;
     fn visit_variable(&mut self, variable: &Variable) -> Self::Output;
     fn visit_assignment(&mut self, assignment: &Assignment) -> Self::Output;
+    fn visit_postfix(&mut self, postfix: &Postfix) -> Self::Output;
+    fn visit_call(&mut self, call: &Call) -> Self::Output;
+    fn visit_get(&mut self, get: &Get) -> Self::Output;
+    fn visit_set(&mut self, set: &Set) -> Self::Output;
+    fn visit_this(&mut self, this: &This) -> Self::Output;
+    fn visit_super(&mut self, super_expr: &Super) -> Self::Output;
+    fn visit_array_literal(&mut self, array: &ArrayLiteral) -> Self::Output;
+    fn visit_map_literal(&mut self, map: &MapLiteral) -> Self::Output;
+    fn visit_index(&mut self, index: &Index) -> Self::Output;
+    fn visit_index_set(&mut self, index_set: &IndexSet) -> Self::Output;
+    fn visit_yield(&mut self, yield_expr: &Yield) -> Self::Output;
+    fn visit_spread(&mut self, spread: &Spread) -> Self::Output;
+    fn visit_function(&mut self, function: &FunctionExpr) -> Self::Output;
 }
 
 pub trait StatementVisitor {
@@ -20,6 +39,29 @@ pub trait StatementVisitor {
 
 pub struct AstPrinter;
 
+impl AstPrinter {
+    /// Prints a function declaration, or an anonymous function when `name` is empty.
+    fn function(&mut self, name: &str, function: &FunctionStatement) -> String {
+        let mut params = function
+            .params
+            .iter()
+            .map(|param| param.value.to_string())
+            .collect::<Vec<_>>();
+        if let Some(rest) = params.last_mut().filter(|_| function.variadic) {
+            rest.insert_str(0, "...");
+        }
+        let params = params.join(", ");
+
+        let mut result = format!("fun {}({}) {{", name, params);
+        for stmt in &function.body {
+            result.push_str(&self.visit_statement(stmt));
+            result.push_str(";\n");
+        }
+        result.push('}');
+        result
+    }
+}
+
 impl Visitor for AstPrinter {
     type Output = String;
 
@@ -27,6 +69,10 @@ impl Visitor for AstPrinter {
         format!("{} = {}", assignment.name, assignment.value.accept(self))
     }
 
+    fn visit_postfix(&mut self, postfix: &Postfix) -> Self::Output {
+        format!("{}{}", postfix.name.value, postfix.operator.lexeme())
+    }
+
     fn visit_binary(&mut self, binary: &Binary) -> Self::Output {
         format!(
             "({} {} {})",
@@ -37,7 +83,7 @@ impl Visitor for AstPrinter {
     }
 
     fn visit_variable(&mut self, variable: &Variable) -> Self::Output {
-        variable.token.value.clone()
+        variable.token.value.to_string()
     }
 
     fn visit_grouping(&mut self, grouping: &Grouping) -> Self::Output {
@@ -62,9 +108,96 @@ impl Visitor for AstPrinter {
         )
     }
 
+    fn visit_ternary(&mut self, ternary: &Ternary) -> Self::Output {
+        format!(
+            "(?: {} {} {})",
+            ternary.condition.accept(self),
+            ternary.then_branch.accept(self),
+            ternary.else_branch.accept(self)
+        )
+    }
+
     fn visit_unary(&mut self, unary: &Unary) -> Self::Output {
         format!("({} {})", unary.operator.lexeme(), unary.right.accept(self))
     }
+
+    fn visit_call(&mut self, call: &Call) -> Self::Output {
+        let mut result = format!("(call {}", call.callee.accept(self));
+        for argument in &call.arguments {
+            result.push(' ');
+            result.push_str(&argument.accept(self));
+        }
+        result.push(')');
+        result
+    }
+
+    fn visit_get(&mut self, get: &Get) -> Self::Output {
+        format!("(. {} {})", get.object.accept(self), get.name.value)
+    }
+
+    fn visit_set(&mut self, set: &Set) -> Self::Output {
+        format!(
+            "(= (. {} {}) {})",
+            set.object.accept(self),
+            set.name.value,
+            set.value.accept(self)
+        )
+    }
+
+    fn visit_this(&mut self, _this: &This) -> Self::Output {
+        "this".to_string()
+    }
+
+    fn visit_super(&mut self, super_expr: &Super) -> Self::Output {
+        format!("(super {})", super_expr.method.value)
+    }
+
+    fn visit_array_literal(&mut self, array: &ArrayLiteral) -> Self::Output {
+        let mut result = "(array".to_string();
+        for element in &array.elements {
+            result.push(' ');
+            result.push_str(&element.accept(self));
+        }
+        result.push(')');
+        result
+    }
+
+    fn visit_map_literal(&mut self, map: &MapLiteral) -> Self::Output {
+        let mut result = "(map".to_string();
+        for (key, value) in &map.entries {
+            result.push_str(&format!(" (: {} {})", key.accept(self), value.accept(self)));
+        }
+        result.push(')');
+        result
+    }
+
+    fn visit_index(&mut self, index: &Index) -> Self::Output {
+        format!("([] {} {})", index.object.accept(self), index.index.accept(self))
+    }
+
+    fn visit_index_set(&mut self, index_set: &IndexSet) -> Self::Output {
+        format!(
+            "(= ([] {} {}) {})",
+            index_set.object.accept(self),
+            index_set.index.accept(self),
+            index_set.value.accept(self)
+        )
+    }
+
+    fn visit_yield(&mut self, yield_expr: &Yield) -> Self::Output {
+        match &yield_expr.value {
+            Some(value) => format!("(yield {})", value.accept(self)),
+            None => "(yield)".to_string(),
+        }
+    }
+
+    fn visit_spread(&mut self, spread: &Spread) -> Self::Output {
+        format!("(... {})", spread.value.accept(self))
+    }
+
+    fn visit_function(&mut self, function: &FunctionExpr) -> Self::Output {
+        self.function("", &function.declaration)
+    }
 }
 
 impl StatementVisitor for AstPrinter {
@@ -93,6 +226,7 @@ impl StatementVisitor for AstPrinter {
                     if_stmt.then_branch.accept(self)
                 );
 
+                // An `if` in the else branch starts right after `else`, so chains print flat as `else if` lines
                 if let Some(else_branch) = &if_stmt.else_branch {
                     result.push_str(&format!("\nelse {}", else_branch.accept(self)));
                 }
@@ -100,10 +234,446 @@ impl StatementVisitor for AstPrinter {
                 result
             }
             Statement::While(while_stmt) => {
-                format!(
-                    "while({}) {{\n  {}\n}}",
+                let increment = match &while_stmt.increment {
+                    Some(increment) => format!("; {}", increment.accept(self)),
+                    None => String::new(),
+                };
+                let mut result = format!(
+                    "while({}{}) {{\n  {}\n}}",
                     while_stmt.condition.accept(self),
+                    increment,
                     while_stmt.body.accept(self)
+                );
+
+                if let Some(else_branch) = &while_stmt.else_branch {
+                    result.push_str(&format!(" else {}", else_branch.accept(self)));
+                }
+
+                result
+            }
+            Statement::DoWhile(do_while) => format!(
+                "do {{\n  {}\n}} while({})",
+                do_while.body.accept(self),
+                do_while.condition.accept(self)
+            ),
+            Statement::ForIn(for_in) => {
+                format!(
+                    "for ({} in {}) {{\n  {}\n}}",
+                    for_in.name.value,
+                    for_in.iterable.accept(self),
+                    for_in.body.accept(self)
+                )
+            }
+            Statement::With(with_stmt) => {
+                format!(
+                    "with ({}) {{\n  {}\n}}",
+                    with_stmt.object.accept(self),
+                    with_stmt.body.accept(self)
+                )
+            }
+            Statement::Function(function) => self.function(&function.name.value, function),
+            Statement::Return(return_stmt) => match &return_stmt.value {
+                Some(value) => format!("{} {}", return_stmt.keyword.lexeme(), value.accept(self)),
+                None => return_stmt.keyword.lexeme(),
+            },
+            Statement::Break(break_stmt) => break_stmt.keyword.lexeme(),
+            Statement::Continue(continue_stmt) => continue_stmt.keyword.lexeme(),
+            Statement::Assert(assert_stmt) => match &assert_stmt.message {
+                Some(message) => format!(
+                    "{} {}, {}",
+                    assert_stmt.keyword.lexeme(),
+                    assert_stmt.condition.accept(self),
+                    message.accept(self)
+                ),
+                None => format!(
+                    "{} {}",
+                    assert_stmt.keyword.lexeme(),
+                    assert_stmt.condition.accept(self)
+                ),
+            },
+            Statement::Throw(throw_stmt) => {
+                format!("{} {}", throw_stmt.keyword.lexeme(), throw_stmt.value.accept(self))
+            }
+            Statement::TryCatch(try_catch) => {
+                let mut result = "try {".to_string();
+                for stmt in &try_catch.body {
+                    result.push_str(&self.visit_statement(stmt));
+                    result.push_str(";\n");
+                }
+                result.push('}');
+
+                if let Some(catch) = &try_catch.catch {
+                    result.push_str(&format!(" catch ({}) {{", catch.name.value));
+                    for stmt in &catch.body {
+                        result.push_str(&self.visit_statement(stmt));
+                        result.push_str(";\n");
+                    }
+                    result.push('}');
+                }
+
+                if let Some(finally) = &try_catch.finally {
+                    result.push_str(" finally {");
+                    for stmt in finally {
+                        result.push_str(&self.visit_statement(stmt));
+                        result.push_str(";\n");
+                    }
+                    result.push('}');
+                }
+
+                result
+            }
+            Statement::Class(class) => {
+                let mut result = match &class.superclass {
+                    Some(superclass) => format!("class {} < {} {{", class.name.value, superclass.token.value),
+                    None => format!("class {} {{", class.name.value),
+                };
+                for method in &class.methods {
+                    result.push_str(&self.visit_statement(&Statement::Function(method.clone())));
+                    result.push_str(";\n");
+                }
+                result.push('}');
+                result
+            }
+        }
+    }
+}
+
+/// Prints the AST as JSON, with one object per node tagged by its `type`.
+pub struct JsonPrinter;
+
+impl JsonPrinter {
+    fn object(node_type: &str, fields: &[(&str, String)]) -> String {
+        let mut result = format!("{{\"type\":{}", Self::string(node_type));
+        for (key, value) in fields {
+            result.push_str(&format!(",{}:{}", Self::string(key), value));
+        }
+        result.push('}');
+        result
+    }
+
+    fn string(value: &str) -> String {
+        let mut result = String::new();
+        json::write_string(value, &mut result);
+        result
+    }
+
+    fn array(values: impl IntoIterator<Item = String>) -> String {
+        format!("[{}]", values.into_iter().collect::<Vec<_>>().join(","))
+    }
+
+    fn optional(value: Option<String>) -> String {
+        value.unwrap_or_else(|| "null".to_string())
+    }
+
+    fn statements(&mut self, statements: &[Statement]) -> String {
+        Self::array(statements.iter().map(|stmt| self.visit_statement(stmt)))
+    }
+}
+
+impl Visitor for JsonPrinter {
+    type Output = String;
+
+    fn visit_assignment(&mut self, assignment: &Assignment) -> Self::Output {
+        Self::object(
+            "Assignment",
+            &[
+                ("name", Self::string(&assignment.name.value)),
+                ("value", assignment.value.accept(self)),
+            ],
+        )
+    }
+
+    fn visit_postfix(&mut self, postfix: &Postfix) -> Self::Output {
+        Self::object(
+            "Postfix",
+            &[
+                ("operator", Self::string(&postfix.operator.lexeme())),
+                ("name", Self::string(&postfix.name.value)),
+            ],
+        )
+    }
+
+    fn visit_binary(&mut self, binary: &Binary) -> Self::Output {
+        Self::object(
+            "Binary",
+            &[
+                ("operator", Self::string(&binary.operator.lexeme())),
+                ("left", binary.left.accept(self)),
+                ("right", binary.right.accept(self)),
+            ],
+        )
+    }
+
+    fn visit_variable(&mut self, variable: &Variable) -> Self::Output {
+        Self::object("Variable", &[("name", Self::string(&variable.token.value))])
+    }
+
+    fn visit_grouping(&mut self, grouping: &Grouping) -> Self::Output {
+        Self::object("Grouping", &[("expression", grouping.expression.accept(self))])
+    }
+
+    fn visit_literal(&mut self, literal: &Literal) -> Self::Output {
+        let value = match literal.value {
+            LiteralValue::String(ref s) => Self::string(s),
+            LiteralValue::Number(ref n) => n.to_string(),
+            LiteralValue::Boolean(ref b) => b.to_string(),
+            LiteralValue::Nil => "null".to_string(),
+        };
+        Self::object("Literal", &[("value", value)])
+    }
+
+    fn visit_logical(&mut self, logical: &Logical) -> Self::Output {
+        Self::object(
+            "Logical",
+            &[
+                ("operator", Self::string(&logical.operator.lexeme())),
+                ("left", logical.left.accept(self)),
+                ("right", logical.right.accept(self)),
+            ],
+        )
+    }
+
+    fn visit_ternary(&mut self, ternary: &Ternary) -> Self::Output {
+        Self::object(
+            "Ternary",
+            &[
+                ("condition", ternary.condition.accept(self)),
+                ("then", ternary.then_branch.accept(self)),
+                ("else", ternary.else_branch.accept(self)),
+            ],
+        )
+    }
+
+    fn visit_unary(&mut self, unary: &Unary) -> Self::Output {
+        Self::object(
+            "Unary",
+            &[
+                ("operator", Self::string(&unary.operator.lexeme())),
+                ("right", unary.right.accept(self)),
+            ],
+        )
+    }
+
+    fn visit_call(&mut self, call: &Call) -> Self::Output {
+        let arguments = Self::array(call.arguments.iter().map(|argument| argument.accept(self)));
+        Self::object(
+            "Call",
+            &[("callee", call.callee.accept(self)), ("arguments", arguments)],
+        )
+    }
+
+    fn visit_get(&mut self, get: &Get) -> Self::Output {
+        Self::object(
+            "Get",
+            &[
+                ("object", get.object.accept(self)),
+                ("name", Self::string(&get.name.value)),
+            ],
+        )
+    }
+
+    fn visit_set(&mut self, set: &Set) -> Self::Output {
+        Self::object(
+            "Set",
+            &[
+                ("object", set.object.accept(self)),
+                ("name", Self::string(&set.name.value)),
+                ("value", set.value.accept(self)),
+            ],
+        )
+    }
+
+    fn visit_this(&mut self, _this: &This) -> Self::Output {
+        Self::object("This", &[])
+    }
+
+    fn visit_super(&mut self, super_expr: &Super) -> Self::Output {
+        Self::object("Super", &[("method", Self::string(&super_expr.method.value))])
+    }
+
+    fn visit_array_literal(&mut self, array: &ArrayLiteral) -> Self::Output {
+        let elements = Self::array(array.elements.iter().map(|element| element.accept(self)));
+        Self::object("ArrayLiteral", &[("elements", elements)])
+    }
+
+    fn visit_map_literal(&mut self, map: &MapLiteral) -> Self::Output {
+        let entries = Self::array(map.entries.iter().map(|(key, value)| {
+            let key = key.accept(self);
+            let value = value.accept(self);
+            format!("{{\"key\":{},\"value\":{}}}", key, value)
+        }));
+        Self::object("MapLiteral", &[("entries", entries)])
+    }
+
+    fn visit_index(&mut self, index: &Index) -> Self::Output {
+        Self::object(
+            "Index",
+            &[
+                ("object", index.object.accept(self)),
+                ("index", index.index.accept(self)),
+            ],
+        )
+    }
+
+    fn visit_index_set(&mut self, index_set: &IndexSet) -> Self::Output {
+        Self::object(
+            "IndexSet",
+            &[
+                ("object", index_set.object.accept(self)),
+                ("index", index_set.index.accept(self)),
+                ("value", index_set.value.accept(self)),
+            ],
+        )
+    }
+
+    fn visit_yield(&mut self, yield_expr: &Yield) -> Self::Output {
+        let value = Self::optional(yield_expr.value.as_ref().map(|value| value.accept(self)));
+        Self::object("Yield", &[("value", value)])
+    }
+
+    fn visit_spread(&mut self, spread: &Spread) -> Self::Output {
+        Self::object("Spread", &[("value", spread.value.accept(self))])
+    }
+
+    fn visit_function(&mut self, function: &FunctionExpr) -> Self::Output {
+        let declaration = self.visit_statement(&Statement::Function(function.declaration.clone()));
+        Self::object("FunctionExpr", &[("declaration", declaration)])
+    }
+}
+
+impl StatementVisitor for JsonPrinter {
+    type Output = String;
+
+    fn visit_statement(&mut self, statement: &Statement) -> Self::Output {
+        match statement {
+            Statement::Expression(expr) => Self::object("Expression", &[("expression", expr.expression.accept(self))]),
+            Statement::Print(print_stmt) => {
+                Self::object("Print", &[("expression", print_stmt.expression.accept(self))])
+            }
+            Statement::Variable(variable) => Self::object(
+                "Var",
+                &[
+                    ("name", Self::string(&variable.name.value)),
+                    ("value", variable.value.accept(self)),
+                    ("mutable", variable.mutable.to_string()),
+                ],
+            ),
+            Statement::Block(block) => Self::object("Block", &[("statements", self.statements(&block.statements))]),
+            Statement::If(if_stmt) => {
+                let else_branch = Self::optional(if_stmt.else_branch.as_ref().map(|branch| branch.accept(self)));
+                Self::object(
+                    "If",
+                    &[
+                        ("condition", if_stmt.condition.accept(self)),
+                        ("then", if_stmt.then_branch.accept(self)),
+                        ("else", else_branch),
+                    ],
+                )
+            }
+            Statement::While(while_stmt) => {
+                let increment = Self::optional(while_stmt.increment.as_ref().map(|increment| increment.accept(self)));
+                let else_branch = Self::optional(while_stmt.else_branch.as_ref().map(|branch| branch.accept(self)));
+                Self::object(
+                    "While",
+                    &[
+                        ("condition", while_stmt.condition.accept(self)),
+                        ("body", while_stmt.body.accept(self)),
+                        ("increment", increment),
+                        ("else", else_branch),
+                    ],
+                )
+            }
+            Statement::DoWhile(do_while) => Self::object(
+                "DoWhile",
+                &[
+                    ("body", do_while.body.accept(self)),
+                    ("condition", do_while.condition.accept(self)),
+                ],
+            ),
+            Statement::ForIn(for_in) => Self::object(
+                "ForIn",
+                &[
+                    ("name", Self::string(&for_in.name.value)),
+                    ("iterable", for_in.iterable.accept(self)),
+                    ("body", for_in.body.accept(self)),
+                ],
+            ),
+            Statement::With(with_stmt) => Self::object(
+                "With",
+                &[
+                    ("object", with_stmt.object.accept(self)),
+                    ("body", with_stmt.body.accept(self)),
+                ],
+            ),
+            Statement::Function(function) => Self::object(
+                "Function",
+                &[
+                    ("name", Self::string(&function.name.value)),
+                    (
+                        "params",
+                        Self::array(function.params.iter().map(|param| Self::string(&param.value))),
+                    ),
+                    ("variadic", function.variadic.to_string()),
+                    ("body", self.statements(&function.body)),
+                    ("generator", function.is_generator.to_string()),
+                ],
+            ),
+            Statement::Return(return_stmt) => {
+                let value = Self::optional(return_stmt.value.as_ref().map(|value| value.accept(self)));
+                Self::object("Return", &[("value", value)])
+            }
+            Statement::Break(_) => Self::object("Break", &[]),
+            Statement::Continue(_) => Self::object("Continue", &[]),
+            Statement::Assert(assert_stmt) => {
+                let message = Self::optional(assert_stmt.message.as_ref().map(|message| message.accept(self)));
+                Self::object(
+                    "Assert",
+                    &[("condition", assert_stmt.condition.accept(self)), ("message", message)],
+                )
+            }
+            Statement::Throw(throw_stmt) => Self::object("Throw", &[("value", throw_stmt.value.accept(self))]),
+            Statement::TryCatch(try_catch) => {
+                let catch = Self::optional(try_catch.catch.as_ref().map(|catch| {
+                    Self::object(
+                        "Catch",
+                        &[
+                            ("name", Self::string(&catch.name.value)),
+                            ("body", self.statements(&catch.body)),
+                        ],
+                    )
+                }));
+                let finally = Self::optional(try_catch.finally.as_ref().map(|finally| self.statements(finally)));
+
+                Self::object(
+                    "Try",
+                    &[
+                        ("body", self.statements(&try_catch.body)),
+                        ("catch", catch),
+                        ("finally", finally),
+                    ],
+                )
+            }
+            Statement::Class(class) => {
+                let superclass = Self::optional(
+                    class
+                        .superclass
+                        .as_ref()
+                        .map(|superclass| Self::string(&superclass.token.value)),
+                );
+                let methods = Self::array(
+                    class
+                        .methods
+                        .iter()
+                        .map(|method| self.visit_statement(&Statement::Function(method.clone()))),
+                );
+
+                Self::object(
+                    "Class",
+                    &[
+                        ("name", Self::string(&class.name.value)),
+                        ("superclass", superclass),
+                        ("methods", methods),
+                    ],
                 )
             }
         }
@@ -114,8 +684,27 @@ impl StatementVisitor for AstPrinter {
 mod tests {
     use super::*;
     use crate::ast::Expr;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
     use crate::token::Token;
 
+    fn print_source(source: &str) -> String {
+        let mut errors = Vec::new();
+        let tokens = {
+            let mut scanner = Scanner::new(source, &mut errors);
+            scanner.scan();
+            scanner.into_tokens()
+        };
+        let statements = Parser::new(&tokens, &mut errors).parse();
+        assert!(errors.is_empty(), "Expected no errors, but got: {:?}", errors);
+
+        statements
+            .iter()
+            .map(|statement| statement.accept(&mut AstPrinter))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
     #[test]
     fn test_ast_printer() {
         let binary = Binary {
@@ -152,4 +741,98 @@ mod tests {
         let mut printer = AstPrinter;
         assert_eq!(printer.visit_binary(&expr), "(* (- 123) (group 45.67))".to_string());
     }
+
+    #[test]
+    fn test_ast_printer_if_statements() {
+        assert_eq!(
+            print_source("if (a > 1) print a; else { print b; }"),
+            "if (> a 1)\n  print a\nelse {print b;\n}"
+        );
+        assert_eq!(print_source("if (a) print a;"), "if a\n  print a");
+    }
+
+    #[test]
+    fn test_ast_printer_else_if_chains() {
+        assert_eq!(
+            print_source("if (a) print 1; else if (b) print 2; else print 3;"),
+            "if a\n  print 1\nelse if b\n  print 2\nelse print 3"
+        );
+    }
+
+    #[test]
+    fn test_ast_printer_do_while_statements() {
+        assert_eq!(
+            print_source("do print i; while (i < 3);"),
+            "do {\n  print i\n} while((< i 3))"
+        );
+    }
+
+    #[test]
+    fn test_ast_printer_while_statements() {
+        assert_eq!(
+            print_source("while (i < 3) i = i + 1;"),
+            "while((< i 3)) {\n  Identifier(1): i = (+ i 1)\n}"
+        );
+        assert_eq!(
+            print_source("for (; i < 3; i = i + 1) continue;"),
+            "while((< i 3); Identifier(1): i = (+ i 1)) {\n  continue\n}"
+        );
+    }
+
+    #[test]
+    fn test_json_printer() {
+        let mut errors = Vec::new();
+        let tokens = {
+            let mut scanner = Scanner::new("-a + \"hi\" * 2;", &mut errors);
+            scanner.scan();
+            scanner.into_tokens()
+        };
+        let statements = Parser::new(&tokens, &mut errors).parse();
+        assert!(errors.is_empty(), "Expected no errors, but got: {:?}", errors);
+
+        assert_eq!(
+            statements[0].accept(&mut JsonPrinter),
+            concat!(
+                r#"{"type":"Expression","expression":{"type":"Binary","operator":"+","#,
+                r#""left":{"type":"Unary","operator":"-","right":{"type":"Variable","name":"a"}},"#,
+                r#""right":{"type":"Binary","operator":"*","#,
+                r#""left":{"type":"Literal","value":"hi"},"right":{"type":"Literal","value":2}}}}"#
+            )
+        );
+
+        let literal = Literal {
+            value: LiteralValue::String("say \"hi\"\n\\".to_string()),
+        };
+        assert_eq!(
+            JsonPrinter.visit_literal(&literal),
+            r#"{"type":"Literal","value":"say \"hi\"\n\\"}"#
+        );
+    }
+
+    #[test]
+    fn test_ast_printer_with_statements() {
+        assert_eq!(print_source("with (point) print x;"), "with (point) {\n  print x\n}");
+    }
+
+    #[test]
+    fn test_ast_printer_map_literals() {
+        assert_eq!(
+            print_source("var m = {\"a\": 1, b: [2]};"),
+            "Identifier(1): m=(map (: a 1) (: b (array 2)))"
+        );
+        assert_eq!(print_source("m[\"a\"] = {};"), "(= ([] m a) (map))");
+    }
+
+    #[test]
+    fn test_ast_printer_spread_arguments() {
+        assert_eq!(print_source("f(1, ...rest);"), "(call f 1 (... rest))");
+    }
+
+    #[test]
+    fn test_ast_printer_anonymous_functions() {
+        assert_eq!(
+            print_source("var f = fun (a, ...b) { return a; };"),
+            "Identifier(1): f=fun (a, ...b) {return a;\n}"
+        );
+    }
 }
@@ -1,17 +1,47 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    hash::{Hash, Hasher},
+    rc::Rc,
+};
 
 use crate::{
     ast::{
-        Assignment, Binary, BlockStatement, Grouping, Literal, LiteralValue, Logical, Node, Statement, Stmt, Unary,
-        Variable,
+        ArrayLiteral, Assignment, Binary, Call, Expr, ForInStatement, FunctionExpr, FunctionStatement, Get, Grouping,
+        Index, IndexSet, Literal, LiteralValue, Logical, MapLiteral, Node, Postfix, Set, Spread, Statement, Stmt,
+        Super, Ternary, This, Unary, Variable, WithStatement, Yield,
     },
     environment::{Env, Environment},
+    map::{self, Map},
+    natives,
+    parser::Parser,
+    resolver::Resolver,
+    scanner::Scanner,
     token::Token,
     visitor::{StatementVisitor, Visitor},
 };
 
 pub struct Vm {
     environment: Env,
+    globals: Env,
+    sandbox: bool,
+    strict: bool,
+    boolean_logic: bool,
+    print_as_function: bool,
+    /// Number of calls in progress, limited to `max_depth` so that runaway recursion errors instead of overflowing the
+    /// Rust stack.
+    depth: usize,
+    max_depth: usize,
+    /// Position of the Rust stack when the outermost call in progress started, and how far past it nested calls can
+    /// go. Each call takes a varying amount of stack, so counting calls alone can't keep them within the stack.
+    stack_base: usize,
+    stack_limit: usize,
+    /// Line of the call to the native function currently running, for natives reporting errors.
+    call_line: usize,
+    /// Top-level statements loaded for stepping through one at a time.
+    pending: VecDeque<Statement>,
+    /// Value of the most recently evaluated expression statement.
+    last_value: Option<Value>,
 }
 
 #[derive(Debug)]
@@ -20,6 +50,10 @@ pub enum RuntimeError {
     UnknownOperatorError(String),
     ZeroDivision(String),
     UndefinedVariable(String),
+    UserError(Value),
+    Return(Value),
+    Break,
+    Continue,
 }
 
 impl std::fmt::Display for RuntimeError {
@@ -29,16 +63,221 @@ impl std::fmt::Display for RuntimeError {
             RuntimeError::UnknownOperatorError(s) => write!(f, "{}", s),
             RuntimeError::ZeroDivision(s) => write!(f, "{}", s),
             RuntimeError::UndefinedVariable(s) => write!(f, "{}", s),
+            RuntimeError::UserError(value) => write!(f, "{}", value),
+            RuntimeError::Return(_) => write!(f, "Can't return from top-level code."),
+            RuntimeError::Break => write!(f, "Can't use 'break' outside of a loop."),
+            RuntimeError::Continue => write!(f, "Can't use 'continue' outside of a loop."),
+        }
+    }
+}
+
+impl RuntimeError {
+    /// Prefixes the error message with the line it was raised at. Errors that carry a value are left unchanged.
+    fn at_line(self, line: usize) -> Self {
+        match self {
+            RuntimeError::ArgumentError(s) => RuntimeError::ArgumentError(format!("[line {}] {}", line, s)),
+            RuntimeError::UnknownOperatorError(s) => {
+                RuntimeError::UnknownOperatorError(format!("[line {}] {}", line, s))
+            }
+            RuntimeError::ZeroDivision(s) => RuntimeError::ZeroDivision(format!("[line {}] {}", line, s)),
+            RuntimeError::UndefinedVariable(s) => RuntimeError::UndefinedVariable(format!("[line {}] {}", line, s)),
+            other => other,
         }
     }
+
+    /// The line the error was raised at, taken from the `[line N]` prefix runtime errors start their message with, or
+    /// `None` for errors without one, such as thrown values.
+    pub fn line(&self) -> Option<usize> {
+        let message = match self {
+            RuntimeError::ArgumentError(s)
+            | RuntimeError::UnknownOperatorError(s)
+            | RuntimeError::ZeroDivision(s)
+            | RuntimeError::UndefinedVariable(s) => s,
+            _ => return None,
+        };
+
+        let (line, _) = message.strip_prefix("[line ")?.split_once(']')?;
+        line.parse().ok()
+    }
 }
 
+/// Arrays, maps and instances are shared by reference, so cloning a `Value` (or assigning it to another variable)
+/// aliases them. The `clone` native makes independent copies of arrays and maps.
 #[derive(Debug, Clone)]
 pub enum Value {
     Number(f64),
     String(String),
     Boolean(bool),
     Nil,
+    Function(Rc<Function>),
+    NativeFunction(Rc<NativeFunction>),
+    Class(Rc<Class>),
+    Instance(Rc<RefCell<Instance>>),
+    Array(Rc<RefCell<Vec<Value>>>),
+    Map(Rc<RefCell<Map>>),
+    Generator(Rc<RefCell<Generator>>),
+}
+
+pub struct Function {
+    pub declaration: Rc<FunctionStatement>,
+    pub closure: Env,
+    pub is_initializer: bool,
+}
+
+impl Function {
+    pub fn arity(&self) -> Arity {
+        match self.declaration.params.len() {
+            count if self.declaration.variadic => Arity::AtLeast(count - 1),
+            count => Arity::Exact(count),
+        }
+    }
+
+    pub fn bind(&self, instance: Value) -> Function {
+        let mut environment = Environment::new(Some(self.closure.clone()));
+        environment.define("this".into(), instance);
+
+        Function {
+            declaration: self.declaration.clone(),
+            closure: Rc::new(RefCell::new(environment)),
+            is_initializer: self.is_initializer,
+        }
+    }
+}
+
+impl std::fmt::Debug for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn {}>", self.declaration.name.value)
+    }
+}
+
+/// The number of arguments a native function accepts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arity {
+    Exact(usize),
+    AtLeast(usize),
+}
+
+/// The Rust implementation of a native function, receiving the already arity-checked arguments.
+pub type NativeFn = Rc<dyn Fn(&mut Vm, &[Value]) -> Result<Value, RuntimeError>>;
+
+pub struct NativeFunction {
+    pub name: String,
+    pub arity: Arity,
+    pub function: NativeFn,
+}
+
+impl std::fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NativeFunction")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug)]
+pub struct Class {
+    pub name: String,
+    pub superclass: Option<Rc<Class>>,
+    pub methods: HashMap<String, Rc<Function>>,
+}
+
+impl Class {
+    pub fn find_method(&self, name: &str) -> Option<Rc<Function>> {
+        match self.methods.get(name) {
+            Some(method) => Some(method.clone()),
+            None => self
+                .superclass
+                .as_ref()
+                .and_then(|superclass| superclass.find_method(name)),
+        }
+    }
+
+    pub fn arity(&self) -> Arity {
+        self.find_method("init")
+            .map_or(Arity::Exact(0), |initializer| initializer.arity())
+    }
+}
+
+pub struct Instance {
+    pub class: Rc<Class>,
+    /// Keyed by the interned names of identifiers, so that writing a field doesn't allocate a new key.
+    fields: HashMap<Rc<str>, Value>,
+}
+
+impl Instance {
+    pub fn new(class: Rc<Class>) -> Self {
+        Instance {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+
+    /// Fields stored on the instance. Methods live on the class and are not included.
+    pub fn fields(&self) -> &HashMap<Rc<str>, Value> {
+        &self.fields
+    }
+}
+
+impl std::fmt::Debug for Instance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} instance", self.class.name)
+    }
+}
+
+/// A call to a generator function. Its body doesn't run when the function is called, but a piece at a time each time
+/// the generator is resumed, up to the next `yield`.
+pub struct Generator {
+    declaration: Rc<FunctionStatement>,
+    /// The environment holding the call's arguments.
+    environment: Env,
+    state: GeneratorState,
+}
+
+enum GeneratorState {
+    /// Waiting to be resumed where the frames say it stopped. No frames means the body hasn't started yet.
+    Suspended(Vec<Frame>),
+    Running,
+    Done,
+}
+
+impl std::fmt::Debug for Generator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<generator {}>", self.declaration.name.value)
+    }
+}
+
+/// Where a statement enclosing a suspended `yield` stopped, so that resuming can pick up from there. A generator keeps
+/// one frame per enclosing statement, innermost first.
+enum Frame {
+    /// At a `yield` statement, which is complete once resumed.
+    Yield,
+    /// At the statement at `index` of a block.
+    Block { index: usize, environment: Env },
+    /// In the branch of an `if` the condition picked.
+    If { then_branch: bool },
+    /// In the body of a `while` or `do`/`while` loop, or the `else` branch once a `while` condition turned false.
+    Loop { else_branch: bool },
+    /// In the body of a `for`/`in` loop, with the elements left to iterate over.
+    ForIn { iteration: Iteration },
+    /// In the body of a `with` statement.
+    With,
+    /// In one of the blocks of a `try` statement.
+    Try { section: TrySection },
+}
+
+enum TrySection {
+    Body,
+    /// Running the `catch` block, starting with the value caught if it hasn't started yet.
+    Catch(Option<Value>),
+    /// Running the `finally` block, which completes with the result of the blocks before it unless it fails itself.
+    Finally(Result<(), RuntimeError>),
+}
+
+/// What a `for`/`in` loop iterates over: a snapshot of an array's elements, or a generator resumed for each value.
+enum Iteration {
+    Elements(std::vec::IntoIter<Value>),
+    Generator(Rc<RefCell<Generator>>),
 }
 
 impl std::fmt::Display for Value {
@@ -48,10 +287,59 @@ impl std::fmt::Display for Value {
             Value::String(s) => write!(f, "{}", s),
             Value::Boolean(b) => write!(f, "{}", b),
             Value::Nil => write!(f, "nil"),
+            Value::Function(function) => write!(f, "<fn {}>", function.declaration.name.value),
+            Value::NativeFunction(native) => write!(f, "<native fn {}>", native.name),
+            Value::Class(class) => write!(f, "{}", class.name),
+            Value::Instance(instance) => write!(f, "{} instance", instance.borrow().class.name),
+            Value::Array(elements) => {
+                write!(f, "[")?;
+                for (index, element) in elements.borrow().iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                write!(f, "]")
+            }
+            Value::Map(entries) => {
+                write!(f, "{{")?;
+                for (index, (key, value)) in entries.borrow().entries().iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    match key {
+                        Value::String(key) => write!(f, "\"{}\": {}", key, value)?,
+                        key => write!(f, "{}: {}", key, value)?,
+                    }
+                }
+                write!(f, "}}")
+            }
+            Value::Generator(generator) => write!(f, "{:?}", generator.borrow()),
         }
     }
 }
 
+/// Whether `n` is a zero divisor. Negative zero compares equal to zero, so `10 / -0.0` is a division by zero error
+/// rather than negative infinity.
+fn is_zero(n: f64) -> bool {
+    n == 0.0
+}
+
+/// Checks the result of an arithmetic operation, so that overflowing to infinity or producing NaN is an error instead
+/// of a value silently spreading through the program.
+fn finite(left: f64, operator: &str, right: f64, result: f64) -> Result<Value, RuntimeError> {
+    if result.is_finite() {
+        Ok(Value::Number(result))
+    } else {
+        Err(RuntimeError::ArgumentError(format!(
+            "Result of {} {} {} is not a finite number",
+            Value::Number(left),
+            operator,
+            Value::Number(right)
+        )))
+    }
+}
+
 impl std::ops::Neg for Value {
     type Output = Result<Value, RuntimeError>;
 
@@ -71,7 +359,7 @@ impl std::ops::Sub for Value {
 
     fn sub(self, other: Self) -> Self::Output {
         match (self, other) {
-            (Value::Number(l), Value::Number(r)) => Ok(Value::Number(l - r)),
+            (Value::Number(l), Value::Number(r)) => finite(l, "-", r, l - r),
             (Value::Number(_l), other) => Err(RuntimeError::ArgumentError(format!(
                 "Expected number, but got {}",
                 other
@@ -89,10 +377,10 @@ impl std::ops::Div for Value {
 
     fn div(self, other: Self) -> Self::Output {
         match (self, other) {
-            (Value::Number(l), Value::Number(0.0)) => {
+            (Value::Number(l), Value::Number(r)) if is_zero(r) => {
                 Err(RuntimeError::ZeroDivision(format!("Cannot divide {} by zero", l)))
             }
-            (Value::Number(l), Value::Number(r)) => Ok(Value::Number(l / r)),
+            (Value::Number(l), Value::Number(r)) => finite(l, "/", r, l / r),
             (Value::Number(_l), other) => Err(RuntimeError::ArgumentError(format!(
                 "Expected number, but got {}",
                 other
@@ -110,7 +398,7 @@ impl std::ops::Mul for Value {
 
     fn mul(self, other: Self) -> Self::Output {
         match (self, other) {
-            (Value::Number(l), Value::Number(r)) => Ok(Value::Number(l * r)),
+            (Value::Number(l), Value::Number(r)) => finite(l, "*", r, l * r),
             (Value::Number(_l), other) => Err(RuntimeError::ArgumentError(format!(
                 "Expected number, but got {}",
                 other
@@ -123,12 +411,86 @@ impl std::ops::Mul for Value {
     }
 }
 
+impl Value {
+    /// The name of the value's type, as shown in error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Boolean(_) => "boolean",
+            Value::Nil => "nil",
+            Value::Function(_) | Value::NativeFunction(_) => "function",
+            Value::Class(_) => "class",
+            Value::Instance(_) => "instance",
+            Value::Array(_) => "array",
+            Value::Map(_) => "map",
+            Value::Generator(_) => "generator",
+        }
+    }
+
+    /// Converts the value into an array index. Numbers stay `f64` internally, so only those holding a whole,
+    /// non-negative value are accepted.
+    pub fn as_index(&self) -> Result<usize, RuntimeError> {
+        match self {
+            Value::Number(n) if n.fract() == 0.0 && *n >= 0.0 => Ok(*n as usize),
+            other => Err(RuntimeError::ArgumentError(format!(
+                "Array index must be a non-negative integer, but got {}.",
+                other
+            ))),
+        }
+    }
+
+    /// Reads a field of an instance or an entry of a map.
+    pub fn member(&self, name: &str) -> Option<Value> {
+        match self {
+            Value::Instance(instance) => instance.borrow().fields.get(name).cloned(),
+            Value::Map(entries) => entries.borrow().get(&HashableValue::String(name.to_string())).cloned(),
+            _ => None,
+        }
+    }
+
+    /// Replaces an existing field of an instance or entry of a map, returning whether it was found.
+    pub fn set_member(&self, name: &str, value: Value) -> bool {
+        match self {
+            Value::Instance(instance) => match instance.borrow_mut().fields.get_mut(name) {
+                Some(field) => {
+                    *field = value;
+                    true
+                }
+                None => false,
+            },
+            Value::Map(entries) => match entries.borrow_mut().get_mut(&HashableValue::String(name.to_string())) {
+                Some(entry) => {
+                    *entry = value;
+                    true
+                }
+                None => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn pow(self, other: Self) -> Result<Value, RuntimeError> {
+        match (self, other) {
+            (Value::Number(l), Value::Number(r)) => finite(l, "**", r, l.powf(r)),
+            (Value::Number(_l), other) => Err(RuntimeError::ArgumentError(format!(
+                "Expected number, but got {}",
+                other
+            ))),
+            (left, right) => Err(RuntimeError::ArgumentError(format!(
+                "Invalid operands for **: {} and {}",
+                left, right
+            ))),
+        }
+    }
+}
+
 impl std::ops::Add for Value {
     type Output = Result<Value, RuntimeError>;
 
     fn add(self, other: Self) -> Self::Output {
         match (self, other) {
-            (Value::Number(l), Value::Number(r)) => Ok(Value::Number(l + r)),
+            (Value::Number(l), Value::Number(r)) => finite(l, "+", r, l + r),
             (Value::String(l), Value::String(r)) => Ok(Value::String(l + &r)),
             (Value::String(_l), other) => Err(RuntimeError::ArgumentError(format!(
                 "Expected string, but got {}",
@@ -150,21 +512,66 @@ impl std::cmp::PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (Value::Number(l), Value::Number(r)) => l == r,
-            (Value::Number(_l), Value::String(_r)) => false,
-            (Value::Number(_l), Value::Boolean(_r)) => false,
-            (Value::Number(_l), Value::Nil) => false,
             (Value::String(l), Value::String(r)) => l == r,
-            (Value::String(_l), Value::Number(_r)) => false,
-            (Value::String(_l), Value::Boolean(_r)) => false,
-            (Value::String(_l), Value::Nil) => false,
             (Value::Boolean(l), Value::Boolean(r)) => l == r,
-            (Value::Boolean(_l), Value::Number(_r)) => false,
-            (Value::Boolean(_l), Value::String(_r)) => false,
-            (Value::Boolean(_l), Value::Nil) => false,
             (Value::Nil, Value::Nil) => true,
-            (Value::Nil, Value::Number(_r)) => false,
-            (Value::Nil, Value::String(_r)) => false,
-            (Value::Nil, Value::Boolean(_r)) => false,
+            (Value::Function(l), Value::Function(r)) => Rc::ptr_eq(l, r),
+            (Value::NativeFunction(l), Value::NativeFunction(r)) => Rc::ptr_eq(l, r),
+            (Value::Class(l), Value::Class(r)) => Rc::ptr_eq(l, r),
+            (Value::Instance(l), Value::Instance(r)) => Rc::ptr_eq(l, r),
+            (Value::Array(l), Value::Array(r)) => Rc::ptr_eq(l, r),
+            (Value::Map(l), Value::Map(r)) => Rc::ptr_eq(l, r),
+            (Value::Generator(l), Value::Generator(r)) => Rc::ptr_eq(l, r),
+            _ => false,
+        }
+    }
+}
+
+/// A value that can key a `HashMap`. Only strings, numbers, booleans and `nil` are hashable. Numbers hash by their bit
+/// pattern, with `-0` treated as `0` since the two compare equal, while NaN is never equal to any value, itself
+/// included, so a NaN key can be inserted but never found again.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HashableValue {
+    Number(f64),
+    String(String),
+    Boolean(bool),
+    Nil,
+}
+
+impl HashableValue {
+    /// Converts `value`, or returns `None` when it isn't hashable.
+    pub fn new(value: &Value) -> Option<Self> {
+        match value {
+            Value::Number(n) => Some(HashableValue::Number(*n)),
+            Value::String(s) => Some(HashableValue::String(s.clone())),
+            Value::Boolean(b) => Some(HashableValue::Boolean(*b)),
+            Value::Nil => Some(HashableValue::Nil),
+            _ => None,
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        match self {
+            HashableValue::Number(n) => Value::Number(*n),
+            HashableValue::String(s) => Value::String(s.clone()),
+            HashableValue::Boolean(b) => Value::Boolean(*b),
+            HashableValue::Nil => Value::Nil,
+        }
+    }
+}
+
+impl Eq for HashableValue {}
+
+impl Hash for HashableValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+
+        match self {
+            // Adding zero turns -0 into 0
+            HashableValue::Number(n) => (n + 0.0).to_bits().hash(state),
+            HashableValue::String(s) => s.hash(state),
+            HashableValue::Boolean(b) => b.hash(state),
+            HashableValue::Nil => {}
         }
     }
 }
@@ -178,776 +585,3566 @@ impl std::cmp::PartialOrd for Value {
     }
 }
 
+/// How deeply calls can nest by default. Depending on the build and the code being run, the stack limit can be
+/// reached first.
+const DEFAULT_MAX_DEPTH: usize = 1000;
+
+/// How many bytes of Rust stack nested calls can use by default. A debug build takes up to tens of kilobytes per call,
+/// so this keeps well within the 2MB stack of a thread spawned with the default size.
+const DEFAULT_STACK_LIMIT: usize = 1024 * 1024;
+
+/// The address of a local variable, which moves as the Rust stack grows and shrinks.
+#[inline(never)]
+fn stack_position() -> usize {
+    let marker = 0u8;
+    std::hint::black_box(&marker) as *const u8 as usize
+}
+
 impl Vm {
     pub fn new() -> Self {
-        Vm {
-            environment: Environment::new_global(),
+        let environment = Environment::new_global();
+
+        let mut vm = Vm {
+            globals: environment.clone(),
+            environment,
+            sandbox: false,
+            strict: false,
+            boolean_logic: false,
+            print_as_function: false,
+            call_line: 0,
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
+            stack_base: 0,
+            stack_limit: DEFAULT_STACK_LIMIT,
+            pending: VecDeque::new(),
+            last_value: None,
+        };
+
+        for native in natives::all() {
+            vm.define_native(native);
         }
+        vm
     }
 
-    fn truthy(&self, value: &Value) -> bool {
-        match value {
-            Value::Nil => false,
-            Value::Boolean(b) => *b,
-            _ => true,
-        }
+    /// Defines a global native function taking exactly `arity` arguments. Calls with any other number of arguments
+    /// error before `function` runs, like they do for the built-in natives.
+    pub fn register_native(
+        &mut self,
+        name: &str,
+        arity: usize,
+        function: impl Fn(&[Value]) -> Result<Value, RuntimeError> + 'static,
+    ) {
+        self.define_native(NativeFunction {
+            name: name.to_string(),
+            arity: Arity::Exact(arity),
+            function: Rc::new(move |_vm, arguments| function(arguments)),
+        });
     }
 
-    fn execute_block(&mut self, block: &BlockStatement) -> Result<(), RuntimeError> {
-        let previous = self.environment.clone();
-        let inner = Rc::new(RefCell::new(Environment::new(Some(previous.clone()))));
-        self.environment = inner;
+    /// Defines a global variable holding `value`, so that the host can pass data to the scripts run afterwards.
+    /// Scripts can reassign it like any other global.
+    pub fn define_global(&mut self, name: &str, value: Value) {
+        self.globals.borrow_mut().define(name.into(), value);
+    }
 
-        let result = block
-            .statements
-            .iter()
-            .try_for_each(|statement| self.visit_statement(statement));
+    /// Removes a global variable or native function, so that scripts referencing it error as if it was never defined.
+    pub fn remove_global(&mut self, name: &str) {
+        self.globals.borrow_mut().remove(name);
+    }
 
-        self.environment = previous;
-        result
+    fn define_native(&mut self, native: NativeFunction) {
+        self.globals
+            .borrow_mut()
+            .define(native.name.as_str().into(), Value::NativeFunction(Rc::new(native)));
     }
-}
 
-impl Visitor for Vm {
-    type Output = Result<Value, RuntimeError>;
+    /// Makes `==` and `!=` error when comparing values of different types, including `nil`, instead of evaluating to
+    /// unequal.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
 
-    fn visit_binary(&mut self, binary: &Binary) -> Self::Output {
-        let left = binary.left.accept(self)?;
-        let right = binary.right.accept(self)?;
+    /// Makes `and` and `or` evaluate to `true` or `false` instead of the operand that decided the result, so `1 and 2`
+    /// is `true` rather than `2`. Short-circuiting is unaffected.
+    pub fn boolean_logic(mut self, boolean_logic: bool) -> Self {
+        self.boolean_logic = boolean_logic;
+        self
+    }
 
-        match *binary.operator {
-            Token::Minus { line: _ } => Ok((left - right)?),
-            Token::Slash { line: _ } => Ok((left / right)?),
-            Token::Star { line: _ } => Ok((left * right)?),
-            Token::Plus { line: _ } => Ok((left + right)?),
-            Token::Greater { line: _ } => Ok(Value::Boolean(left > right)),
-            Token::GreaterEqual { line: _ } => Ok(Value::Boolean(left >= right)),
-            Token::Less { line: _ } => Ok(Value::Boolean(left < right)),
-            Token::LessEqual { line: _ } => Ok(Value::Boolean(left <= right)),
-            Token::BangEqual { line: _ } => Ok(Value::Boolean(left != right)),
-            Token::EqualEqual { line: _ } => Ok(Value::Boolean(left == right)),
-            _ => Err(RuntimeError::UnknownOperatorError(format!(
-                "Unknown binary operator: {:?}",
-                binary.operator
-            ))),
+    /// Limits how deeply calls can nest before erroring with a stack overflow.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Limits how many bytes of Rust stack nested calls can use before erroring with a stack overflow. The limit must
+    /// leave room for the stack used before the outermost call and by the deepest call itself, so it should be well
+    /// below the size of the stack of the thread running the `Vm`.
+    pub fn with_stack_limit(mut self, stack_limit: usize) -> Self {
+        self.stack_limit = stack_limit;
+        self
+    }
+
+    /// Replaces the `print` statement with a global `print` function that prints its argument and returns it, so that
+    /// it can be used within expressions. Statements parsed for this `Vm` must use `Parser::print_as_function` too.
+    pub fn print_as_function(mut self, print_as_function: bool) -> Self {
+        self.print_as_function = print_as_function;
+        if print_as_function {
+            self.define_native(NativeFunction {
+                name: "print".to_string(),
+                arity: Arity::Exact(1),
+                function: Rc::new(|vm, arguments| {
+                    let line = vm.call_line;
+                    println!("{}", vm.stringify(&arguments[0], line)?);
+                    Ok(arguments[0].clone())
+                }),
+            });
         }
+        self
     }
 
-    fn visit_variable(&mut self, variable: &Variable) -> Self::Output {
-        match self.environment.borrow().get(&variable.token.value) {
-            Ok(value) => Ok(value.clone()),
-            Err(err) => Err(err),
+    /// Disables natives that can run arbitrary code or touch the file system, such as `eval` and `read_file`, and
+    /// removes nondeterministic ones like `clock`, so that scripts referencing them fail as undefined variables.
+    pub fn sandbox(mut self, sandbox: bool) -> Self {
+        self.sandbox = sandbox;
+        if sandbox {
+            self.remove_global("clock");
         }
+        self
     }
 
-    fn visit_assignment(&mut self, assignment: &Assignment) -> Self::Output {
-        let value = assignment.value.accept(self)?;
-        self.environment
-            .borrow_mut()
-            .assign(&assignment.name.value, value.clone())?;
-        Ok(value)
+    /// Reads a variable `depth` scopes up the chain, or from the globals when `None`, unless a `with` statement
+    /// exposes a member with the same name.
+    fn read_variable(&self, name: &str, depth: Option<usize>) -> Result<Value, RuntimeError> {
+        if let Some(value) = self.environment.borrow().get_member(depth, name) {
+            return Ok(value);
+        }
+
+        match depth {
+            Some(distance) => self.environment.borrow().get_at(distance, name),
+            None => self.globals.borrow().get(name),
+        }
     }
 
-    fn visit_grouping(&mut self, grouping: &Grouping) -> Self::Output {
-        grouping.expression.accept(self)
+    /// Assigns a variable found the same way as in `read_variable`.
+    fn assign_variable(&mut self, name: &str, depth: Option<usize>, value: Value) -> Result<(), RuntimeError> {
+        if self.environment.borrow().assign_member(depth, name, &value) {
+            return Ok(());
+        }
+
+        match depth {
+            Some(distance) => self.environment.borrow_mut().assign_at(distance, name, value),
+            None => self.globals.borrow_mut().assign(name, value),
+        }
     }
 
-    fn visit_literal(&mut self, literal: &Literal) -> Self::Output {
-        match literal.value {
-            LiteralValue::String(ref s) => Ok(Value::String(s.clone())),
-            LiteralValue::Number(n) => Ok(Value::Number(n)),
-            LiteralValue::Boolean(b) => Ok(Value::Boolean(b)),
-            LiteralValue::Nil => Ok(Value::Nil),
+    fn check_comparable(&self, left: &Value, right: &Value) -> Result<(), RuntimeError> {
+        if self.strict && left.type_name() != right.type_name() {
+            return Err(RuntimeError::ArgumentError(format!(
+                "Cannot compare {} with {}.",
+                left.type_name(),
+                right.type_name()
+            )));
         }
+        Ok(())
     }
 
-    fn visit_logical(&mut self, logical: &Logical) -> Self::Output {
-        let left = logical.left.accept(self)?;
+    /// Converts a value to the string shown by `print`, calling the `to_string` method of instances that define one.
+    fn stringify(&mut self, value: &Value, line: usize) -> Result<String, RuntimeError> {
+        match self.find_instance_method(value, "to_string") {
+            Some(method) => match self.call(method, Vec::new(), line)? {
+                Value::String(s) => Ok(s),
+                other => Err(RuntimeError::ArgumentError(format!(
+                    "[line {}] Expected to_string to return a string, but got {}.",
+                    line,
+                    other.type_name()
+                ))),
+            },
+            None => Ok(value.to_string()),
+        }
+    }
 
-        match *logical.operator {
-            Token::Or { line: _ } => {
-                if self.truthy(&left) {
-                    Ok(left)
-                } else {
-                    logical.right.accept(self)
-                }
+    /// Looks up a method on an instance's class, bound to the instance. Any other value has no methods.
+    fn find_instance_method(&self, object: &Value, name: &str) -> Option<Value> {
+        match object {
+            Value::Instance(instance) => {
+                let method = instance.borrow().class.find_method(name)?;
+                Some(Value::Function(Rc::new(method.bind(object.clone()))))
             }
-            _ => {
-                if !self.truthy(&left) {
-                    Ok(left)
-                } else {
-                    logical.right.accept(self)
+            _ => None,
+        }
+    }
+
+    /// Hashes a value used as a map key. Strings, numbers, booleans and `nil` hash by value, while instances must
+    /// define a `hash` method returning a number. Any other value can't be used as a key.
+    pub fn hash_key(&mut self, key: &Value, line: usize) -> Result<u64, RuntimeError> {
+        match key {
+            Value::Instance(instance) => {
+                let method = self.find_instance_method(key, "hash").ok_or_else(|| {
+                    RuntimeError::ArgumentError(format!(
+                        "[line {}] {} instances can't be used as keys without a 'hash' method.",
+                        line,
+                        instance.borrow().class.name
+                    ))
+                })?;
+
+                match self.call(method, Vec::new(), line)? {
+                    Value::Number(n) => Ok(map::hash_scalar(&HashableValue::Number(n))),
+                    other => Err(RuntimeError::ArgumentError(format!(
+                        "[line {}] Expected hash to return a number, but got {}.",
+                        line,
+                        other.type_name()
+                    ))),
                 }
             }
+            other => match HashableValue::new(other) {
+                Some(hashable) => Ok(map::hash_scalar(&hashable)),
+                None => Err(RuntimeError::ArgumentError(format!(
+                    "[line {}] Cannot use {} as a key.",
+                    line,
+                    other.type_name()
+                ))),
+            },
         }
     }
 
-    fn visit_unary(&mut self, unary: &Unary) -> Self::Output {
-        let right = unary.right.accept(self)?;
+    /// Finds the position of the entry stored under `key` in a map.
+    fn map_position(
+        &mut self,
+        map: &Rc<RefCell<Map>>,
+        key: &Value,
+        hash: u64,
+        line: usize,
+    ) -> Result<Option<usize>, RuntimeError> {
+        for position in map.borrow().candidates(hash) {
+            // The map is not borrowed while comparing, since `equals` methods may access it
+            let existing = map.borrow().key(position).clone();
+            if self.keys_equal(key, &existing, line)? {
+                return Ok(Some(position));
+            }
+        }
+        Ok(None)
+    }
 
-        match *unary.operator {
-            Token::Minus { line: _ } => -right,
-            Token::Bang { line: _ } => Ok(Value::Boolean(!self.truthy(&right))),
-            _ => Err(RuntimeError::UnknownOperatorError(format!(
-                "Unknown unary operator: {:?}",
-                unary.operator
-            ))),
+    /// Inserts a value into a map, replacing the value already stored under an equal key.
+    fn map_insert(
+        &mut self,
+        map: &Rc<RefCell<Map>>,
+        key: Value,
+        value: Value,
+        line: usize,
+    ) -> Result<(), RuntimeError> {
+        let hash = self.hash_key(&key, line)?;
+
+        match self.map_position(map, &key, hash, line)? {
+            Some(position) => map.borrow_mut().set(position, value),
+            None => map.borrow_mut().push(hash, key, value),
         }
+        Ok(())
     }
-}
 
-impl StatementVisitor for Vm {
-    type Output = Result<(), RuntimeError>;
+    /// Compares two map keys, calling the `equals` method of instances that define one. Keys are otherwise compared
+    /// like `==` does. Keys of different types are never equal, so `equals` is only ever called with another instance.
+    pub fn keys_equal(&mut self, left: &Value, right: &Value, line: usize) -> Result<bool, RuntimeError> {
+        if std::mem::discriminant(left) != std::mem::discriminant(right) {
+            return Ok(false);
+        }
 
-    fn visit_statement(&mut self, statement: &Statement) -> Self::Output {
-        match statement {
-            Statement::Expression(stmt) => {
-                stmt.expression.accept(self)?;
-                Ok(())
-            }
-            Statement::Print(stmt) => {
-                let value = stmt.expression.accept(self)?;
-                println!("{}", value);
-                Ok(())
-            }
-            Statement::Variable(var) => {
-                let value = var.value.accept(self)?;
-                self.environment.borrow_mut().define(var.name.value.clone(), value);
-                Ok(())
+        match self.find_instance_method(left, "equals") {
+            Some(method) => {
+                let result = self.call(method, vec![right.clone()], line)?;
+                Ok(self.truthy(&result))
             }
-            Statement::Block(block) => self.execute_block(block),
-            Statement::If(if_stmt) => {
-                let condition = if_stmt.condition.accept(self)?;
+            None => Ok(left == right),
+        }
+    }
 
-                if self.truthy(&condition) {
-                    if_stmt.then_branch.accept(self)
-                } else if let Some(else_branch) = &if_stmt.else_branch {
-                    else_branch.accept(self)
-                } else {
-                    Ok(())
-                }
+    /// Errors if `feature` is used while the sandbox is enabled.
+    pub(crate) fn check_sandbox(&self, feature: &str) -> Result<(), RuntimeError> {
+        if self.sandbox {
+            return Err(RuntimeError::ArgumentError(format!(
+                "{} is disabled in sandbox mode",
+                feature
+            )));
+        }
+        Ok(())
+    }
+
+    /// Scans, parses and executes `source` in the global scope, returning the value of its last statement when that
+    /// statement is an expression and `nil` otherwise.
+    ///
+    /// The source only sees global variables, even when `eval` is called from inside a function: the locals of the
+    /// caller aren't visible, and variables the source declares become globals.
+    pub fn eval(&mut self, source: &str) -> Result<Value, RuntimeError> {
+        self.check_sandbox("eval")?;
+
+        let mut errors = Vec::new();
+        let tokens = {
+            let mut scanner = Scanner::new(source, &mut errors);
+            scanner.scan();
+            scanner.into_tokens()
+        };
+        let statements = Parser::new(&tokens, &mut errors)
+            .print_as_function(self.print_as_function)
+            .parse_with_trailing_expression();
+        Resolver::new(&mut errors).resolve(&statements);
+
+        if !errors.is_empty() {
+            return Err(RuntimeError::ArgumentError(errors.join("\n")));
+        }
+
+        let previous = std::mem::replace(&mut self.environment, self.globals.clone());
+        let result = match statements.split_last() {
+            Some((Statement::Expression(last), rest)) => rest
+                .iter()
+                .try_for_each(|statement| self.visit_statement(statement))
+                .and_then(|_| last.expression.accept(self)),
+            _ => statements
+                .iter()
+                .try_for_each(|statement| self.visit_statement(statement))
+                .map(|_| Value::Nil),
+        };
+        self.environment = previous;
+
+        match result {
+            Err(RuntimeError::Return(_)) => Err(RuntimeError::ArgumentError(
+                "Can't return from top-level code.".to_string(),
+            )),
+            other => other,
+        }
+    }
+
+    /// Loads a program to be executed one top-level statement at a time with `step`, replacing any statements that
+    /// were loaded before and haven't been executed yet.
+    pub fn load(&mut self, statements: Vec<Statement>) {
+        self.pending = statements.into();
+    }
+
+    /// Executes the next loaded statement, returning `false` once there are none left. Statements nested in blocks,
+    /// loops and function calls run as part of the top-level statement containing them.
+    pub fn step(&mut self) -> Result<bool, RuntimeError> {
+        let Some(statement) = self.pending.pop_front() else {
+            return Ok(false);
+        };
+
+        match self.visit_statement(&statement) {
+            Ok(()) => Ok(true),
+            Err(RuntimeError::Return(_)) => Err(RuntimeError::ArgumentError(
+                "Can't return from top-level code.".to_string(),
+            )),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// The statement the next call to `step` executes.
+    pub fn next_statement(&self) -> Option<&Statement> {
+        self.pending.front()
+    }
+
+    /// The environment statements are currently executing in, which is the global one between steps.
+    pub fn environment(&self) -> &Env {
+        &self.environment
+    }
+
+    /// The value of the most recently evaluated expression statement, or `None` if none has run yet.
+    pub fn last_value(&self) -> Option<Value> {
+        self.last_value.clone()
+    }
+
+    pub(crate) fn truthy(&self, value: &Value) -> bool {
+        match value {
+            Value::Nil => false,
+            Value::Boolean(b) => *b,
+            _ => true,
+        }
+    }
+
+    /// Executes top-level statements as a unit. If any of them fails, the global bindings are restored to what they
+    /// were before, discarding the variables defined and assigned by the statements that already ran.
+    pub fn execute_atomically(&mut self, statements: &[Statement]) -> Result<(), RuntimeError> {
+        let snapshot = self.globals.borrow().snapshot();
+
+        let result = statements
+            .iter()
+            .try_for_each(|statement| self.visit_statement(statement));
+
+        if result.is_err() {
+            self.globals.borrow_mut().restore(snapshot);
+        }
+        result
+    }
+
+    fn execute_block(&mut self, statements: &[Statement], environment: Env) -> Result<(), RuntimeError> {
+        let previous = self.environment.clone();
+        self.environment = environment;
+
+        let result = statements
+            .iter()
+            .try_for_each(|statement| self.visit_statement(statement));
+
+        self.environment = previous;
+        result
+    }
+
+    fn call(&mut self, callee: Value, arguments: Vec<Value>, line: usize) -> Result<Value, RuntimeError> {
+        let (name, arity) = match &callee {
+            Value::Function(function) => (function.declaration.name.value.to_string(), function.arity()),
+            Value::NativeFunction(native) => (native.name.clone(), native.arity),
+            Value::Class(class) => (class.name.clone(), class.arity()),
+            _ => {
+                return Err(RuntimeError::ArgumentError(format!(
+                    "[line {}] Can only call functions and classes.",
+                    line
+                )));
+            }
+        };
+
+        match arity {
+            Arity::Exact(arity) if arguments.len() != arity => {
+                return Err(RuntimeError::ArgumentError(format!(
+                    "[line {}] Expected {} arguments but got {} in call to '{}'.",
+                    line,
+                    arity,
+                    arguments.len(),
+                    name
+                )));
+            }
+            Arity::AtLeast(arity) if arguments.len() < arity => {
+                return Err(RuntimeError::ArgumentError(format!(
+                    "[line {}] Expected at least {} arguments but got {} in call to '{}'.",
+                    line,
+                    arity,
+                    arguments.len(),
+                    name
+                )));
+            }
+            _ => {}
+        }
+
+        if self.stack_exhausted() {
+            return Err(RuntimeError::ArgumentError(format!(
+                "[line {}] Stack overflow in call to '{}'.",
+                line, name
+            )));
+        }
+
+        self.depth += 1;
+        let result = match callee {
+            Value::Function(function) => self.call_function(&function, arguments),
+            Value::NativeFunction(native) => {
+                self.call_line = line;
+                (native.function)(self, &arguments)
+            }
+            Value::Class(class) => {
+                let instance = Value::Instance(Rc::new(RefCell::new(Instance::new(class.clone()))));
+
+                match class.find_method("init") {
+                    Some(initializer) => self
+                        .call_function(&initializer.bind(instance.clone()), arguments)
+                        .map(|_| instance),
+                    None => Ok(instance),
+                }
+            }
+            _ => unreachable!(),
+        };
+        self.depth -= 1;
+
+        result
+    }
+
+    /// Whether nesting another call would go over `max_depth` or the stack limit.
+    fn stack_exhausted(&mut self) -> bool {
+        let position = stack_position();
+        if self.depth == 0 {
+            self.stack_base = position;
+        }
+
+        self.depth >= self.max_depth || position.abs_diff(self.stack_base) > self.stack_limit
+    }
+
+    fn call_function(&mut self, function: &Function, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+        let mut environment = Environment::new(Some(function.closure.clone()));
+        let mut arguments = arguments.into_iter();
+        let params = &function.declaration.params;
+        let (rest, positional) = match params.split_last() {
+            Some((rest, positional)) if function.declaration.variadic => (Some(rest), positional),
+            _ => (None, params.as_slice()),
+        };
+
+        for (param, argument) in positional.iter().zip(arguments.by_ref()) {
+            environment.define(param.value.clone(), argument);
+        }
+
+        if let Some(rest) = rest {
+            let remaining = Value::Array(Rc::new(RefCell::new(arguments.collect())));
+            environment.define(rest.value.clone(), remaining);
+        }
+
+        if function.declaration.is_generator {
+            return Ok(Value::Generator(Rc::new(RefCell::new(Generator {
+                declaration: function.declaration.clone(),
+                environment: Rc::new(RefCell::new(environment)),
+                state: GeneratorState::Suspended(Vec::new()),
+            }))));
+        }
+
+        let result = match self.execute_block(&function.declaration.body, Rc::new(RefCell::new(environment))) {
+            Ok(()) => Value::Nil,
+            Err(RuntimeError::Return(value)) => value,
+            Err(err) => return Err(err),
+        };
+
+        if function.is_initializer {
+            return function.closure.borrow().get("this");
+        }
+
+        Ok(result)
+    }
+
+    /// Runs a generator until its next `yield`, returning the yielded value, or `None` once the body has finished.
+    pub(crate) fn resume(&mut self, generator: &Rc<RefCell<Generator>>) -> Result<Option<Value>, RuntimeError> {
+        if self.stack_exhausted() {
+            return Err(RuntimeError::ArgumentError(format!(
+                "Stack overflow in generator '{}'.",
+                generator.borrow().declaration.name.value
+            )));
+        }
+
+        let (declaration, environment, mut frames) = {
+            let mut generator = generator.borrow_mut();
+            match std::mem::replace(&mut generator.state, GeneratorState::Running) {
+                GeneratorState::Suspended(frames) => {
+                    (generator.declaration.clone(), generator.environment.clone(), frames)
+                }
+                GeneratorState::Running => {
+                    return Err(RuntimeError::ArgumentError(format!(
+                        "Generator '{}' is already running.",
+                        generator.declaration.name.value
+                    )));
+                }
+                GeneratorState::Done => {
+                    generator.state = GeneratorState::Done;
+                    return Ok(None);
+                }
+            }
+        };
+
+        self.depth += 1;
+        let result = self.resume_block(&declaration.body, environment, &mut frames);
+        self.depth -= 1;
+
+        let (state, result) = match result {
+            Ok(Some(value)) => (GeneratorState::Suspended(frames), Ok(Some(value))),
+            Ok(None) | Err(RuntimeError::Return(_)) => (GeneratorState::Done, Ok(None)),
+            Err(err) => (GeneratorState::Done, Err(err)),
+        };
+        generator.borrow_mut().state = state;
+
+        result
+    }
+
+    /// Runs `statements` in `environment` like `execute_block`, but stops at the first `yield` reached, saving where
+    /// it stopped in `frames` and returning the yielded value. When `frames` holds a block frame, execution resumes
+    /// from it instead, in the environment it saved.
+    fn resume_block(
+        &mut self,
+        statements: &[Statement],
+        environment: Env,
+        frames: &mut Vec<Frame>,
+    ) -> Result<Option<Value>, RuntimeError> {
+        let (start, environment) = match frames.pop() {
+            Some(Frame::Block { index, environment }) => (index, environment),
+            None => (0, environment),
+            Some(_) => unreachable!(),
+        };
+
+        let previous = std::mem::replace(&mut self.environment, environment);
+        let mut result = Ok(None);
+
+        for (index, statement) in statements.iter().enumerate().skip(start) {
+            result = self.resume_statement(statement, frames);
+
+            if let Ok(Some(_)) = result {
+                frames.push(Frame::Block {
+                    index,
+                    environment: self.environment.clone(),
+                });
+            }
+            if !matches!(result, Ok(None)) {
+                break;
+            }
+        }
+
+        self.environment = previous;
+        result
+    }
+
+    /// Executes a statement of a generator's body, suspending at a `yield` or resuming from the frames of a previous
+    /// suspension. Statements that can't contain a `yield` run as usual.
+    fn resume_statement(
+        &mut self,
+        statement: &Statement,
+        frames: &mut Vec<Frame>,
+    ) -> Result<Option<Value>, RuntimeError> {
+        match statement {
+            Statement::Expression(stmt) => match &*stmt.expression {
+                Expr::Yield(yield_expr) => {
+                    if let Some(Frame::Yield) = frames.pop() {
+                        return Ok(None);
+                    }
+
+                    let value = match &yield_expr.value {
+                        Some(value) => value.accept(self)?,
+                        None => Value::Nil,
+                    };
+                    frames.push(Frame::Yield);
+                    Ok(Some(value))
+                }
+                _ => self.visit_statement(statement).map(|_| None),
+            },
+            Statement::Block(block) => {
+                let environment = Rc::new(RefCell::new(Environment::new(Some(self.environment.clone()))));
+                self.resume_block(&block.statements, environment, frames)
+            }
+            Statement::If(if_stmt) => {
+                let then_branch = match frames.pop() {
+                    Some(Frame::If { then_branch }) => then_branch,
+                    None => {
+                        let condition = if_stmt.condition.accept(self)?;
+                        self.truthy(&condition)
+                    }
+                    Some(_) => unreachable!(),
+                };
+
+                let branch = match then_branch {
+                    true => Some(&if_stmt.then_branch),
+                    false => if_stmt.else_branch.as_ref(),
+                };
+                let Some(branch) = branch else {
+                    return Ok(None);
+                };
+
+                let yielded = self.resume_statement(branch, frames)?;
+                if yielded.is_some() {
+                    frames.push(Frame::If { then_branch });
+                }
+                Ok(yielded)
             }
             Statement::While(while_stmt) => {
+                let mut resuming = match frames.pop() {
+                    Some(Frame::Loop { else_branch }) => Some(else_branch),
+                    None => None,
+                    Some(_) => unreachable!(),
+                };
+
+                if resuming != Some(true) {
+                    loop {
+                        if resuming.take().is_none() {
+                            let condition = while_stmt.condition.accept(self)?;
+                            if !self.truthy(&condition) {
+                                break;
+                            }
+                        }
+
+                        match self.resume_statement(&while_stmt.body, frames) {
+                            Ok(Some(value)) => {
+                                frames.push(Frame::Loop { else_branch: false });
+                                return Ok(Some(value));
+                            }
+                            Err(RuntimeError::Break) => return Ok(None),
+                            Ok(None) | Err(RuntimeError::Continue) => {}
+                            Err(err) => return Err(err),
+                        }
+
+                        if let Some(increment) = &while_stmt.increment {
+                            increment.accept(self)?;
+                        }
+                    }
+                }
+
+                let Some(else_branch) = &while_stmt.else_branch else {
+                    return Ok(None);
+                };
+                let yielded = self.resume_statement(else_branch, frames)?;
+                if yielded.is_some() {
+                    frames.push(Frame::Loop { else_branch: true });
+                }
+                Ok(yielded)
+            }
+            Statement::DoWhile(do_while) => {
+                match frames.pop() {
+                    Some(Frame::Loop { else_branch: false }) | None => {}
+                    Some(_) => unreachable!(),
+                }
+
                 loop {
-                    let condition = while_stmt.condition.accept(self)?;
+                    match self.resume_statement(&do_while.body, frames) {
+                        Ok(Some(value)) => {
+                            frames.push(Frame::Loop { else_branch: false });
+                            return Ok(Some(value));
+                        }
+                        Err(RuntimeError::Break) => return Ok(None),
+                        Ok(None) | Err(RuntimeError::Continue) => {}
+                        Err(err) => return Err(err),
+                    }
+
+                    let condition = do_while.condition.accept(self)?;
                     if !self.truthy(&condition) {
-                        break;
+                        return Ok(None);
+                    }
+                }
+            }
+            Statement::ForIn(for_in) => {
+                let (mut iteration, mut resuming) = match frames.pop() {
+                    Some(Frame::ForIn { iteration }) => (iteration, true),
+                    None => (self.iterate(for_in)?, false),
+                    Some(_) => unreachable!(),
+                };
+
+                loop {
+                    // When resuming, the body's block frame brings back the environment holding the current element
+                    let mut environment = Environment::new(Some(self.environment.clone()));
+                    if !std::mem::take(&mut resuming) {
+                        let Some(element) = self.next_element(&mut iteration)? else {
+                            return Ok(None);
+                        };
+                        environment.define(for_in.name.value.clone(), element);
                     }
 
-                    while_stmt.body.accept(self)?;
+                    let body = std::slice::from_ref(&*for_in.body);
+                    match self.resume_block(body, Rc::new(RefCell::new(environment)), frames) {
+                        Ok(Some(value)) => {
+                            frames.push(Frame::ForIn { iteration });
+                            return Ok(Some(value));
+                        }
+                        Err(RuntimeError::Break) => return Ok(None),
+                        Ok(None) | Err(RuntimeError::Continue) => {}
+                        Err(err) => return Err(err),
+                    }
                 }
+            }
+            Statement::With(with_stmt) => {
+                let environment = match frames.pop() {
+                    Some(Frame::With) => self.environment.clone(),
+                    None => {
+                        let object = self.with_object(with_stmt)?;
+                        Rc::new(RefCell::new(Environment::with_object(self.environment.clone(), object)))
+                    }
+                    Some(_) => unreachable!(),
+                };
 
-                Ok(())
+                let yielded = self.resume_block(std::slice::from_ref(&*with_stmt.body), environment, frames)?;
+                if yielded.is_some() {
+                    frames.push(Frame::With);
+                }
+                Ok(yielded)
+            }
+            Statement::TryCatch(try_catch) => {
+                let mut section = match frames.pop() {
+                    Some(Frame::Try { section }) => section,
+                    None => TrySection::Body,
+                    Some(_) => unreachable!(),
+                };
+
+                loop {
+                    let mut environment = Environment::new(Some(self.environment.clone()));
+
+                    section = match section {
+                        TrySection::Body => {
+                            match self.resume_block(&try_catch.body, Rc::new(RefCell::new(environment)), frames) {
+                                Ok(Some(value)) => {
+                                    frames.push(Frame::Try {
+                                        section: TrySection::Body,
+                                    });
+                                    return Ok(Some(value));
+                                }
+                                Err(error) if try_catch.catch.is_some() => match caught_value(error) {
+                                    Ok(value) => TrySection::Catch(Some(value)),
+                                    Err(error) => TrySection::Finally(Err(error)),
+                                },
+                                result => TrySection::Finally(result.map(|_| ())),
+                            }
+                        }
+                        TrySection::Catch(value) => {
+                            let catch = try_catch.catch.as_ref().unwrap();
+                            if let Some(value) = value {
+                                environment.define(catch.name.value.clone(), value);
+                            }
+
+                            match self.resume_block(&catch.body, Rc::new(RefCell::new(environment)), frames) {
+                                Ok(Some(value)) => {
+                                    frames.push(Frame::Try {
+                                        section: TrySection::Catch(None),
+                                    });
+                                    return Ok(Some(value));
+                                }
+                                result => TrySection::Finally(result.map(|_| ())),
+                            }
+                        }
+                        TrySection::Finally(result) => {
+                            if let Some(finally) = &try_catch.finally {
+                                let environment = Rc::new(RefCell::new(environment));
+                                if let Some(value) = self.resume_block(finally, environment, frames)? {
+                                    frames.push(Frame::Try {
+                                        section: TrySection::Finally(result),
+                                    });
+                                    return Ok(Some(value));
+                                }
+                            }
+                            return result.map(|_| None);
+                        }
+                    };
+                }
             }
+            _ => self.visit_statement(statement).map(|_| None),
+        }
+    }
+
+    /// Evaluates the iterable of a `for`/`in` loop.
+    fn iterate(&mut self, for_in: &ForInStatement) -> Result<Iteration, RuntimeError> {
+        match for_in.iterable.accept(self)? {
+            Value::Array(elements) => Ok(Iteration::Elements(elements.borrow().clone().into_iter())),
+            Value::Generator(generator) => Ok(Iteration::Generator(generator)),
+            other => Err(RuntimeError::ArgumentError(format!(
+                "[line {}] Can only iterate over arrays and generators, but got {}.",
+                for_in.name.line, other
+            ))),
+        }
+    }
+
+    fn next_element(&mut self, iteration: &mut Iteration) -> Result<Option<Value>, RuntimeError> {
+        match iteration {
+            Iteration::Elements(elements) => Ok(elements.next()),
+            Iteration::Generator(generator) => self.resume(generator),
+        }
+    }
+
+    /// Evaluates the object of a `with` statement.
+    fn with_object(&mut self, with_stmt: &WithStatement) -> Result<Value, RuntimeError> {
+        match with_stmt.object.accept(self)? {
+            object @ (Value::Instance(_) | Value::Map(_)) => Ok(object),
+            other => Err(RuntimeError::ArgumentError(format!(
+                "[line {}] Can only use 'with' on instances and maps, but got {}.",
+                with_stmt.keyword.line(),
+                other.type_name()
+            ))),
         }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::{
-        ast::{Expr, ExpressionStatement, IfStatement, Stmt, VariableStatement, WhileStatement},
-        token::Identifier,
-    };
+/// The value a `catch` block receives for `error`, or the error itself when it's control flow that `catch` lets
+/// through, such as `return`.
+fn caught_value(error: RuntimeError) -> Result<Value, RuntimeError> {
+    match error {
+        RuntimeError::Return(_) | RuntimeError::Break | RuntimeError::Continue => Err(error),
+        RuntimeError::UserError(value) => Ok(value),
+        other => Ok(Value::String(other.to_string())),
+    }
+}
 
-    use super::*;
+/// The name of the method a class defines to overload a binary operator. `!=` negates the result of `equals`.
+fn operator_method(operator: &Token) -> Option<&'static str> {
+    match operator {
+        Token::Plus { line: _ } => Some("add"),
+        Token::Minus { line: _ } => Some("subtract"),
+        Token::Star { line: _ } => Some("multiply"),
+        Token::Slash { line: _ } => Some("divide"),
+        Token::StarStar { line: _ } => Some("power"),
+        Token::EqualEqual { line: _ } | Token::BangEqual { line: _ } => Some("equals"),
+        Token::Less { line: _ } => Some("less"),
+        Token::LessEqual { line: _ } => Some("lessEqual"),
+        Token::Greater { line: _ } => Some("greater"),
+        Token::GreaterEqual { line: _ } => Some("greaterEqual"),
+        _ => None,
+    }
+}
 
-    #[test]
-    fn test_evaluating_literals() {
-        let mut vm = Vm::new();
-        let literal = Literal {
-            value: LiteralValue::Number(42.0),
-        };
-        let result = literal.accept(&mut vm).unwrap();
-        assert_eq!(result, Value::Number(42.0));
+/// Converts an index value into a position within an array of `length` elements.
+fn array_index(index: &Value, length: usize, line: usize) -> Result<usize, RuntimeError> {
+    let position = index.as_index().map_err(|error| error.at_line(line))?;
+    if position < length {
+        Ok(position)
+    } else {
+        Err(RuntimeError::ArgumentError(format!(
+            "[line {}] Index {} out of bounds for array of length {}.",
+            line, position, length
+        )))
+    }
+}
 
-        let literal = Literal {
-            value: LiteralValue::String("Hello".to_string()),
-        };
-        let result = literal.accept(&mut vm).unwrap();
-        assert_eq!(result, Value::String("Hello".to_string()));
+impl Default for Vm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-        let bool = Literal {
-            value: LiteralValue::Boolean(true),
+impl Visitor for Vm {
+    type Output = Result<Value, RuntimeError>;
+
+    fn visit_binary(&mut self, binary: &Binary) -> Self::Output {
+        // The comma operator evaluates its left side only for its effects
+        if let Token::Comma { line: _ } = *binary.operator {
+            binary.left.accept(self)?;
+            return binary.right.accept(self);
+        }
+
+        let left = binary.left.accept(self)?;
+        let right = binary.right.accept(self)?;
+
+        // Concatenation works on any pair of values, formatting them the same way `print` does
+        if let Token::DotDot { line } = *binary.operator {
+            return Ok(Value::String(format!(
+                "{}{}",
+                self.stringify(&left, line)?,
+                self.stringify(&right, line)?
+            )));
+        }
+
+        // Instances on the left of an operator dispatch to the class's overloading method, when it defines one
+        if let Some(name) = operator_method(&binary.operator)
+            && let Some(method) = self.find_instance_method(&left, name)
+        {
+            let result = self.call(method, vec![right], binary.operator.line())?;
+
+            return match *binary.operator {
+                Token::BangEqual { line: _ } => Ok(Value::Boolean(!self.truthy(&result))),
+                _ => Ok(result),
+            };
+        }
+
+        // Instances that define `to_string` can be concatenated with strings
+        if let Token::Plus { line } = *binary.operator {
+            match (&left, &right) {
+                (Value::String(l), Value::Instance(_)) if self.find_instance_method(&right, "to_string").is_some() => {
+                    return Ok(Value::String(format!("{}{}", l, self.stringify(&right, line)?)));
+                }
+                (Value::Instance(_), Value::String(r)) if self.find_instance_method(&left, "to_string").is_some() => {
+                    return Ok(Value::String(format!("{}{}", self.stringify(&left, line)?, r)));
+                }
+                _ => {}
+            }
+        }
+
+        let result = match *binary.operator {
+            Token::Minus { line: _ } => left - right,
+            Token::Slash { line: _ } => left / right,
+            Token::Star { line: _ } => left * right,
+            Token::StarStar { line: _ } => left.pow(right),
+            Token::Plus { line: _ } => left + right,
+            Token::Greater { line: _ } => Ok(Value::Boolean(left > right)),
+            Token::GreaterEqual { line: _ } => Ok(Value::Boolean(left >= right)),
+            Token::Less { line: _ } => Ok(Value::Boolean(left < right)),
+            Token::LessEqual { line: _ } => Ok(Value::Boolean(left <= right)),
+            Token::BangEqual { line: _ } => self
+                .check_comparable(&left, &right)
+                .map(|_| Value::Boolean(left != right)),
+            Token::EqualEqual { line: _ } => self
+                .check_comparable(&left, &right)
+                .map(|_| Value::Boolean(left == right)),
+            _ => Err(RuntimeError::UnknownOperatorError(format!(
+                "Unknown binary operator: {:?}",
+                binary.operator
+            ))),
         };
-        let result = bool.accept(&mut vm).unwrap();
-        assert_eq!(result, Value::Boolean(true))
+
+        result.map_err(|error| error.at_line(binary.operator.line()))
     }
 
-    #[test]
-    fn test_evaluating_unary() {
-        let mut vm = Vm::new();
-        let unary = Unary {
-            operator: Box::new(Token::Minus { line: 1 }),
-            right: Box::new(Expr::Literal(Literal {
-                value: LiteralValue::Number(42.0),
-            })),
-        };
-        let result = unary.accept(&mut vm).unwrap();
-        assert_eq!(result, Value::Number(-42.0));
+    fn visit_variable(&mut self, variable: &Variable) -> Self::Output {
+        self.read_variable(&variable.token.value, variable.depth.get())
+    }
 
-        let unary = Unary {
-            operator: Box::new(Token::Bang { line: 1 }),
-            right: Box::new(Expr::Literal(Literal {
-                value: LiteralValue::Boolean(true),
-            })),
+    fn visit_assignment(&mut self, assignment: &Assignment) -> Self::Output {
+        let value = assignment.value.accept(self)?;
+        self.assign_variable(&assignment.name.value, assignment.depth.get(), value.clone())?;
+        Ok(value)
+    }
+
+    fn visit_postfix(&mut self, postfix: &Postfix) -> Self::Output {
+        let value = self.read_variable(&postfix.name.value, postfix.depth.get())?;
+        let line = postfix.operator.line();
+
+        let Value::Number(number) = value else {
+            return Err(RuntimeError::ArgumentError(format!(
+                "[line {}] Operand of '{}' must be a number.",
+                line,
+                postfix.operator.lexeme()
+            )));
         };
-        let result = unary.accept(&mut vm).unwrap();
-        assert_eq!(result, Value::Boolean(false));
+        let result = match *postfix.operator {
+            Token::PlusPlus { line: _ } => number + 1.0,
+            Token::MinusMinus { line: _ } => number - 1.0,
+            _ => {
+                return Err(RuntimeError::UnknownOperatorError(format!(
+                    "[line {}] Unknown postfix operator: {:?}",
+                    line, postfix.operator
+                )));
+            }
+        };
+
+        self.assign_variable(&postfix.name.value, postfix.depth.get(), Value::Number(result))?;
+        Ok(value)
+    }
+
+    fn visit_grouping(&mut self, grouping: &Grouping) -> Self::Output {
+        grouping.expression.accept(self)
+    }
+
+    fn visit_literal(&mut self, literal: &Literal) -> Self::Output {
+        match literal.value {
+            LiteralValue::String(ref s) => Ok(Value::String(s.clone())),
+            LiteralValue::Number(n) => Ok(Value::Number(n)),
+            LiteralValue::Boolean(b) => Ok(Value::Boolean(b)),
+            LiteralValue::Nil => Ok(Value::Nil),
+        }
+    }
+
+    fn visit_logical(&mut self, logical: &Logical) -> Self::Output {
+        let left = logical.left.accept(self)?;
+
+        let result = match *logical.operator {
+            Token::Or { line: _ } => {
+                if self.truthy(&left) {
+                    left
+                } else {
+                    logical.right.accept(self)?
+                }
+            }
+            _ => {
+                if !self.truthy(&left) {
+                    left
+                } else {
+                    logical.right.accept(self)?
+                }
+            }
+        };
+
+        if self.boolean_logic {
+            Ok(Value::Boolean(self.truthy(&result)))
+        } else {
+            Ok(result)
+        }
+    }
+
+    fn visit_ternary(&mut self, ternary: &Ternary) -> Self::Output {
+        let condition = ternary.condition.accept(self)?;
+
+        if self.truthy(&condition) {
+            ternary.then_branch.accept(self)
+        } else {
+            ternary.else_branch.accept(self)
+        }
+    }
+
+    fn visit_call(&mut self, call: &Call) -> Self::Output {
+        let callee = call.callee.accept(self)?;
+        let mut arguments = Vec::new();
+        for argument in &call.arguments {
+            match argument {
+                Expr::Spread(spread) => match spread.value.accept(self)? {
+                    Value::Array(elements) => arguments.extend(elements.borrow().iter().cloned()),
+                    other => {
+                        return Err(RuntimeError::ArgumentError(format!(
+                            "[line {}] Can only spread arrays, but got {}.",
+                            spread.ellipsis.line(),
+                            other.type_name()
+                        )));
+                    }
+                },
+                argument => arguments.push(argument.accept(self)?),
+            }
+        }
+
+        self.call(callee, arguments, call.paren.line())
+    }
+
+    fn visit_get(&mut self, get: &Get) -> Self::Output {
+        let object = get.object.accept(self)?;
+
+        match object {
+            Value::Instance(ref instance) => {
+                if let Some(value) = instance.borrow().fields.get(&*get.name.value) {
+                    return Ok(value.clone());
+                }
+
+                let method = instance.borrow().class.find_method(&get.name.value);
+                match method {
+                    Some(method) => Ok(Value::Function(Rc::new(method.bind(object.clone())))),
+                    None => Err(RuntimeError::UndefinedVariable(format!(
+                        "[line {}] Undefined property '{}'.",
+                        get.name.line, get.name.value
+                    ))),
+                }
+            }
+            // Generators are iterated by calling their `next` method, which returns `nil` once they are done
+            Value::Generator(generator) if &*get.name.value == "next" => {
+                Ok(Value::NativeFunction(Rc::new(NativeFunction {
+                    name: "next".to_string(),
+                    arity: Arity::Exact(0),
+                    function: Rc::new(move |vm, _| Ok(vm.resume(&generator)?.unwrap_or(Value::Nil))),
+                })))
+            }
+            _ => Err(RuntimeError::ArgumentError(format!(
+                "[line {}] Only instances have properties.",
+                get.name.line
+            ))),
+        }
+    }
+
+    fn visit_set(&mut self, set: &Set) -> Self::Output {
+        let object = set.object.accept(self)?;
+
+        match object {
+            Value::Instance(instance) => {
+                let value = set.value.accept(self)?;
+                instance
+                    .borrow_mut()
+                    .fields
+                    .insert(set.name.value.clone(), value.clone());
+                Ok(value)
+            }
+            _ => Err(RuntimeError::ArgumentError(format!(
+                "[line {}] Only instances have fields.",
+                set.name.line
+            ))),
+        }
+    }
+
+    fn visit_this(&mut self, this: &This) -> Self::Output {
+        self.environment.borrow().get("this").map_err(|_| {
+            RuntimeError::UndefinedVariable(format!(
+                "[line {}] Can't use 'this' outside of a class.",
+                this.keyword.line()
+            ))
+        })
+    }
+
+    fn visit_super(&mut self, super_expr: &Super) -> Self::Output {
+        let superclass = match self.environment.borrow().get("super") {
+            Ok(Value::Class(superclass)) => superclass,
+            _ => {
+                return Err(RuntimeError::UndefinedVariable(format!(
+                    "[line {}] Can't use 'super' in a class with no superclass.",
+                    super_expr.keyword.line()
+                )));
+            }
+        };
+        let instance = self.environment.borrow().get("this")?;
+
+        match superclass.find_method(&super_expr.method.value) {
+            Some(method) => Ok(Value::Function(Rc::new(method.bind(instance)))),
+            None => Err(RuntimeError::UndefinedVariable(format!(
+                "[line {}] Undefined property '{}'.",
+                super_expr.method.line, super_expr.method.value
+            ))),
+        }
+    }
+
+    fn visit_yield(&mut self, yield_expr: &Yield) -> Self::Output {
+        // Generators run `yield` statements themselves as they resume, so this is only reached for a `yield` elsewhere
+        Err(RuntimeError::ArgumentError(format!(
+            "[line {}] Can't use 'yield' outside of a generator.",
+            yield_expr.keyword.line()
+        )))
+    }
+
+    fn visit_spread(&mut self, spread: &Spread) -> Self::Output {
+        // Calls expand their spread arguments themselves, so this is only reached for spreads anywhere else
+        Err(RuntimeError::ArgumentError(format!(
+            "[line {}] Can only spread call arguments.",
+            spread.ellipsis.line()
+        )))
+    }
+
+    fn visit_function(&mut self, function: &FunctionExpr) -> Self::Output {
+        Ok(Value::Function(Rc::new(Function {
+            declaration: function.declaration.clone(),
+            closure: self.environment.clone(),
+            is_initializer: false,
+        })))
+    }
+
+    fn visit_array_literal(&mut self, array: &ArrayLiteral) -> Self::Output {
+        let mut elements = Vec::new();
+        for element in &array.elements {
+            elements.push(element.accept(self)?);
+        }
+
+        Ok(Value::Array(Rc::new(RefCell::new(elements))))
+    }
+
+    fn visit_index(&mut self, index: &Index) -> Self::Output {
+        let object = index.object.accept(self)?;
+        let position = index.index.accept(self)?;
+
+        match object {
+            Value::Array(elements) => {
+                let elements = elements.borrow();
+                let position = array_index(&position, elements.len(), index.bracket.line())?;
+                Ok(elements[position].clone())
+            }
+            Value::String(string) => {
+                // Strings are indexed by character rather than by byte
+                let position = position
+                    .as_index()
+                    .map_err(|error| error.at_line(index.bracket.line()))?;
+                match string.chars().nth(position) {
+                    Some(c) => Ok(Value::String(c.to_string())),
+                    None => Err(RuntimeError::ArgumentError(format!(
+                        "[line {}] Index {} out of bounds for string of length {}.",
+                        index.bracket.line(),
+                        position,
+                        string.chars().count()
+                    ))),
+                }
+            }
+            Value::Map(map) => {
+                let hash = self.hash_key(&position, index.bracket.line())?;
+                match self.map_position(&map, &position, hash, index.bracket.line())? {
+                    Some(position) => Ok(map.borrow().entries()[position].1.clone()),
+                    None => Ok(Value::Nil),
+                }
+            }
+            Value::Instance(_) => match self.find_instance_method(&object, "get") {
+                Some(method) => self.call(method, vec![position], index.bracket.line()),
+                None => Err(RuntimeError::ArgumentError(format!(
+                    "[line {}] Only instances defining 'get' can be indexed.",
+                    index.bracket.line()
+                ))),
+            },
+            _ => Err(RuntimeError::ArgumentError(format!(
+                "[line {}] Only arrays, maps and strings can be indexed.",
+                index.bracket.line()
+            ))),
+        }
+    }
+
+    fn visit_index_set(&mut self, index_set: &IndexSet) -> Self::Output {
+        let object = index_set.object.accept(self)?;
+        let position = index_set.index.accept(self)?;
+
+        match object {
+            Value::Array(elements) => {
+                let value = index_set.value.accept(self)?;
+                let mut elements = elements.borrow_mut();
+                let position = array_index(&position, elements.len(), index_set.bracket.line())?;
+                elements[position] = value.clone();
+                Ok(value)
+            }
+            Value::Map(map) => {
+                let value = index_set.value.accept(self)?;
+                self.map_insert(&map, position, value.clone(), index_set.bracket.line())?;
+                Ok(value)
+            }
+            Value::Instance(_) => match self.find_instance_method(&object, "set") {
+                Some(method) => {
+                    let value = index_set.value.accept(self)?;
+                    self.call(method, vec![position, value.clone()], index_set.bracket.line())?;
+                    Ok(value)
+                }
+                None => Err(RuntimeError::ArgumentError(format!(
+                    "[line {}] Only instances defining 'set' can be assigned by index.",
+                    index_set.bracket.line()
+                ))),
+            },
+            _ => Err(RuntimeError::ArgumentError(format!(
+                "[line {}] Only arrays and maps can be indexed.",
+                index_set.bracket.line()
+            ))),
+        }
+    }
+
+    fn visit_map_literal(&mut self, map_literal: &MapLiteral) -> Self::Output {
+        let map = Rc::new(RefCell::new(Map::new()));
+
+        for (key, value) in &map_literal.entries {
+            let key = key.accept(self)?;
+            let value = value.accept(self)?;
+            self.map_insert(&map, key, value, map_literal.brace.line())?;
+        }
+
+        Ok(Value::Map(map))
+    }
+
+    fn visit_unary(&mut self, unary: &Unary) -> Self::Output {
+        let right = unary.right.accept(self)?;
+
+        let result = match *unary.operator {
+            Token::Minus { line: _ } => -right,
+            Token::Bang { line: _ } => Ok(Value::Boolean(!self.truthy(&right))),
+            _ => Err(RuntimeError::UnknownOperatorError(format!(
+                "Unknown unary operator: {:?}",
+                unary.operator
+            ))),
+        };
+
+        result.map_err(|error| error.at_line(unary.operator.line()))
+    }
+}
+
+impl StatementVisitor for Vm {
+    type Output = Result<(), RuntimeError>;
+
+    fn visit_statement(&mut self, statement: &Statement) -> Self::Output {
+        match statement {
+            Statement::Expression(stmt) => {
+                self.last_value = Some(stmt.expression.accept(self)?);
+                Ok(())
+            }
+            Statement::Print(stmt) => {
+                let value = stmt.expression.accept(self)?;
+                println!("{}", self.stringify(&value, stmt.keyword.line())?);
+                Ok(())
+            }
+            Statement::Variable(var) => {
+                let value = var.value.accept(self)?;
+
+                if var.mutable {
+                    self.environment.borrow_mut().define(var.name.value.clone(), value);
+                } else {
+                    self.environment
+                        .borrow_mut()
+                        .define_constant(var.name.value.clone(), value);
+                }
+                Ok(())
+            }
+            Statement::Block(block) => {
+                let environment = Rc::new(RefCell::new(Environment::new(Some(self.environment.clone()))));
+                self.execute_block(&block.statements, environment)
+            }
+            Statement::Function(declaration) => {
+                let function = Function {
+                    declaration: declaration.clone(),
+                    closure: self.environment.clone(),
+                    is_initializer: false,
+                };
+                self.environment
+                    .borrow_mut()
+                    .define(declaration.name.value.clone(), Value::Function(Rc::new(function)));
+                Ok(())
+            }
+            Statement::Return(return_stmt) => {
+                let value = match &return_stmt.value {
+                    Some(value) => value.accept(self)?,
+                    None => Value::Nil,
+                };
+                Err(RuntimeError::Return(value))
+            }
+            Statement::Break(_) => Err(RuntimeError::Break),
+            Statement::Continue(_) => Err(RuntimeError::Continue),
+            Statement::Assert(assert_stmt) => {
+                let condition = assert_stmt.condition.accept(self)?;
+                if self.truthy(&condition) {
+                    return Ok(());
+                }
+
+                let line = assert_stmt.keyword.line();
+                match &assert_stmt.message {
+                    Some(message) => {
+                        let message = message.accept(self)?;
+                        Err(RuntimeError::ArgumentError(format!(
+                            "Assertion failed at line {}: {}",
+                            line,
+                            self.stringify(&message, line)?
+                        )))
+                    }
+                    None => Err(RuntimeError::ArgumentError(format!(
+                        "Assertion failed at line {}",
+                        line
+                    ))),
+                }
+            }
+            Statement::With(with_stmt) => {
+                let object = self.with_object(with_stmt)?;
+                let environment = Environment::with_object(self.environment.clone(), object);
+                self.execute_block(
+                    std::slice::from_ref(&*with_stmt.body),
+                    Rc::new(RefCell::new(environment)),
+                )
+            }
+            Statement::Throw(throw_stmt) => Err(RuntimeError::UserError(throw_stmt.value.accept(self)?)),
+            Statement::ForIn(for_in) => {
+                let mut iteration = self.iterate(for_in)?;
+
+                while let Some(element) = self.next_element(&mut iteration)? {
+                    let mut environment = Environment::new(Some(self.environment.clone()));
+                    environment.define(for_in.name.value.clone(), element);
+                    match self.execute_block(std::slice::from_ref(&*for_in.body), Rc::new(RefCell::new(environment))) {
+                        Err(RuntimeError::Break) => break,
+                        Err(RuntimeError::Continue) => continue,
+                        result => result?,
+                    }
+                }
+
+                Ok(())
+            }
+            Statement::TryCatch(try_catch) => {
+                let environment = Rc::new(RefCell::new(Environment::new(Some(self.environment.clone()))));
+                let mut result = self.execute_block(&try_catch.body, environment);
+
+                if let Some(catch) = &try_catch.catch {
+                    result = match result.map_err(caught_value) {
+                        Err(Ok(value)) => {
+                            let mut environment = Environment::new(Some(self.environment.clone()));
+                            environment.define(catch.name.value.clone(), value);
+                            self.execute_block(&catch.body, Rc::new(RefCell::new(environment)))
+                        }
+                        Err(Err(error)) => Err(error),
+                        Ok(()) => Ok(()),
+                    };
+                }
+
+                // The finally block always runs, and only replaces the pending result if it fails itself
+                if let Some(finally) = &try_catch.finally {
+                    let environment = Rc::new(RefCell::new(Environment::new(Some(self.environment.clone()))));
+                    self.execute_block(finally, environment)?;
+                }
+
+                result
+            }
+            Statement::Class(class_stmt) => {
+                let superclass = match &class_stmt.superclass {
+                    Some(superclass) => match superclass.accept(self)? {
+                        Value::Class(class) => Some(class),
+                        _ => {
+                            return Err(RuntimeError::ArgumentError(format!(
+                                "[line {}] Superclass must be a class.",
+                                superclass.token.line
+                            )));
+                        }
+                    },
+                    None => None,
+                };
+
+                let closure = match &superclass {
+                    Some(superclass) => {
+                        let mut environment = Environment::new(Some(self.environment.clone()));
+                        environment.define("super".into(), Value::Class(superclass.clone()));
+                        Rc::new(RefCell::new(environment))
+                    }
+                    None => self.environment.clone(),
+                };
+
+                let methods = class_stmt
+                    .methods
+                    .iter()
+                    .map(|method| {
+                        let function = Function {
+                            declaration: method.clone(),
+                            closure: closure.clone(),
+                            is_initializer: &*method.name.value == "init",
+                        };
+                        (method.name.value.to_string(), Rc::new(function))
+                    })
+                    .collect();
+
+                let class = Class {
+                    name: class_stmt.name.value.to_string(),
+                    superclass,
+                    methods,
+                };
+                self.environment
+                    .borrow_mut()
+                    .define(class_stmt.name.value.clone(), Value::Class(Rc::new(class)));
+                Ok(())
+            }
+            Statement::If(if_stmt) => {
+                let condition = if_stmt.condition.accept(self)?;
+
+                if self.truthy(&condition) {
+                    if_stmt.then_branch.accept(self)
+                } else if let Some(else_branch) = &if_stmt.else_branch {
+                    else_branch.accept(self)
+                } else {
+                    Ok(())
+                }
+            }
+            Statement::While(while_stmt) => {
+                loop {
+                    let condition = while_stmt.condition.accept(self)?;
+                    if !self.truthy(&condition) {
+                        break;
+                    }
+
+                    match while_stmt.body.accept(self) {
+                        Err(RuntimeError::Break) => return Ok(()),
+                        Err(RuntimeError::Continue) => {}
+                        result => result?,
+                    }
+
+                    if let Some(increment) = &while_stmt.increment {
+                        increment.accept(self)?;
+                    }
+                }
+
+                match &while_stmt.else_branch {
+                    Some(else_branch) => else_branch.accept(self),
+                    None => Ok(()),
+                }
+            }
+            Statement::DoWhile(do_while) => loop {
+                match do_while.body.accept(self) {
+                    Err(RuntimeError::Break) => return Ok(()),
+                    Err(RuntimeError::Continue) => {}
+                    result => result?,
+                }
+
+                let condition = do_while.condition.accept(self)?;
+                if !self.truthy(&condition) {
+                    return Ok(());
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use crate::{
+        ast::{Expr, ExpressionStatement, IfStatement, Stmt, VariableStatement, WhileStatement},
+        parser::Parser,
+        resolver::Resolver,
+        scanner::Scanner,
+        token::Identifier,
+    };
+
+    use super::*;
+
+    fn run(source: &str) -> Result<Vm, RuntimeError> {
+        run_in(Vm::new(), source)
+    }
+
+    fn run_in(mut vm: Vm, source: &str) -> Result<Vm, RuntimeError> {
+        let mut errors = Vec::new();
+        let tokens = {
+            let mut scanner = Scanner::new(source, &mut errors);
+            scanner.scan();
+            scanner.into_tokens()
+        };
+        let statements = Parser::new(&tokens, &mut errors).parse();
+        Resolver::new(&mut errors).resolve(&statements);
+        assert!(errors.is_empty(), "Expected no errors, but got: {:?}", errors);
+
+        for statement in &statements {
+            statement.accept(&mut vm)?;
+        }
+        Ok(vm)
+    }
+
+    fn global(vm: &Vm, name: &str) -> Value {
+        vm.environment.borrow().get(name).unwrap()
+    }
+
+    #[test]
+    fn test_evaluating_literals() {
+        let mut vm = Vm::new();
+        let literal = Literal {
+            value: LiteralValue::Number(42.0),
+        };
+        let result = literal.accept(&mut vm).unwrap();
+        assert_eq!(result, Value::Number(42.0));
+
+        let literal = Literal {
+            value: LiteralValue::String("Hello".to_string()),
+        };
+        let result = literal.accept(&mut vm).unwrap();
+        assert_eq!(result, Value::String("Hello".to_string()));
+
+        let bool = Literal {
+            value: LiteralValue::Boolean(true),
+        };
+        let result = bool.accept(&mut vm).unwrap();
+        assert_eq!(result, Value::Boolean(true))
+    }
+
+    #[test]
+    fn test_evaluating_unary() {
+        let mut vm = Vm::new();
+        let unary = Unary {
+            operator: Box::new(Token::Minus { line: 1 }),
+            right: Box::new(Expr::Literal(Literal {
+                value: LiteralValue::Number(42.0),
+            })),
+        };
+        let result = unary.accept(&mut vm).unwrap();
+        assert_eq!(result, Value::Number(-42.0));
+
+        let unary = Unary {
+            operator: Box::new(Token::Bang { line: 1 }),
+            right: Box::new(Expr::Literal(Literal {
+                value: LiteralValue::Boolean(true),
+            })),
+        };
+        let result = unary.accept(&mut vm).unwrap();
+        assert_eq!(result, Value::Boolean(false));
+    }
+
+    #[test]
+    fn test_evaluating_number_addition() {
+        let mut vm = Vm::new();
+        let binary = Binary {
+            left: Box::new(Expr::Literal(Literal {
+                value: LiteralValue::Number(42.0),
+            })),
+            operator: Box::new(Token::Plus { line: 1 }),
+            right: Box::new(Expr::Literal(Literal {
+                value: LiteralValue::Number(58.0),
+            })),
+        };
+        let result = binary.accept(&mut vm).unwrap();
+        assert_eq!(result, Value::Number(100.0));
+    }
+
+    #[test]
+    fn test_evaluating_string_addition() {
+        let mut vm = Vm::new();
+        let binary = Binary {
+            left: Box::new(Expr::Literal(Literal {
+                value: LiteralValue::String("Hello".to_string()),
+            })),
+            operator: Box::new(Token::Plus { line: 1 }),
+            right: Box::new(Expr::Literal(Literal {
+                value: LiteralValue::String(" World".to_string()),
+            })),
+        };
+        let result = binary.accept(&mut vm).unwrap();
+        assert_eq!(result, Value::String("Hello World".to_string()));
+    }
+
+    #[test]
+    fn test_evaluating_invalid_addition() {
+        let mut vm = Vm::new();
+        let binary = Binary {
+            left: Box::new(Expr::Literal(Literal {
+                value: LiteralValue::String("Hello".to_string()),
+            })),
+            operator: Box::new(Token::Plus { line: 1 }),
+            right: Box::new(Expr::Literal(Literal {
+                value: LiteralValue::Number(42.0),
+            })),
+        };
+        assert!(binary.accept(&mut vm).is_err());
+
+        let binary = Binary {
+            left: Box::new(Expr::Literal(Literal {
+                value: LiteralValue::Boolean(false),
+            })),
+            operator: Box::new(Token::Plus { line: 1 }),
+            right: Box::new(Expr::Literal(Literal {
+                value: LiteralValue::Number(42.0),
+            })),
+        };
+        assert!(binary.accept(&mut vm).is_err());
+    }
+
+    #[test]
+    fn test_evaluating_subtraction() {
+        let mut vm = Vm::new();
+        let binary = Binary {
+            left: Box::new(Expr::Literal(Literal {
+                value: LiteralValue::Number(5.0),
+            })),
+            operator: Box::new(Token::Minus { line: 1 }),
+            right: Box::new(Expr::Literal(Literal {
+                value: LiteralValue::Number(2.0),
+            })),
+        };
+        let result = binary.accept(&mut vm).unwrap();
+        assert_eq!(result, Value::Number(3.0));
+    }
+
+    #[test]
+    fn test_evaluating_invalid_subtraction() {
+        let mut vm = Vm::new();
+        let binary = Binary {
+            left: Box::new(Expr::Literal(Literal {
+                value: LiteralValue::String("Hello".to_string()),
+            })),
+            operator: Box::new(Token::Minus { line: 1 }),
+            right: Box::new(Expr::Literal(Literal {
+                value: LiteralValue::Number(42.0),
+            })),
+        };
+        assert!(binary.accept(&mut vm).is_err());
+
+        let binary = Binary {
+            left: Box::new(Expr::Literal(Literal {
+                value: LiteralValue::Boolean(false),
+            })),
+            operator: Box::new(Token::Minus { line: 1 }),
+            right: Box::new(Expr::Literal(Literal {
+                value: LiteralValue::Number(42.0),
+            })),
+        };
+        assert!(binary.accept(&mut vm).is_err());
+    }
+
+    #[test]
+    fn test_evaluating_division() {
+        let mut vm = Vm::new();
+        let binary = Binary {
+            left: Box::new(Expr::Literal(Literal {
+                value: LiteralValue::Number(5.0),
+            })),
+            operator: Box::new(Token::Slash { line: 1 }),
+            right: Box::new(Expr::Literal(Literal {
+                value: LiteralValue::Number(2.0),
+            })),
+        };
+        let result = binary.accept(&mut vm).unwrap();
+        assert_eq!(result, Value::Number(2.5));
+    }
+
+    #[test]
+    fn test_evaluating_invalid_division() {
+        let mut vm = Vm::new();
+        let binary = Binary {
+            left: Box::new(Expr::Literal(Literal {
+                value: LiteralValue::Number(5.5),
+            })),
+            operator: Box::new(Token::Slash { line: 1 }),
+            right: Box::new(Expr::Literal(Literal {
+                value: LiteralValue::String("Hello".to_string()),
+            })),
+        };
+        assert!(binary.accept(&mut vm).is_err());
+
+        let binary = Binary {
+            left: Box::new(Expr::Literal(Literal {
+                value: LiteralValue::Number(5.5),
+            })),
+            operator: Box::new(Token::Slash { line: 1 }),
+            right: Box::new(Expr::Literal(Literal {
+                value: LiteralValue::Number(0.0),
+            })),
+        };
+        assert!(binary.accept(&mut vm).is_err());
+    }
+
+    #[test]
+    fn test_evaluating_multiplication() {
+        let mut vm = Vm::new();
+        let binary = Binary {
+            left: Box::new(Expr::Literal(Literal {
+                value: LiteralValue::Number(5.0),
+            })),
+            operator: Box::new(Token::Star { line: 1 }),
+            right: Box::new(Expr::Literal(Literal {
+                value: LiteralValue::Number(2.0),
+            })),
+        };
+        let result = binary.accept(&mut vm).unwrap();
+        assert_eq!(result, Value::Number(10.0));
+    }
+
+    #[test]
+    fn test_evaluating_invalid_multiplication() {
+        let mut vm = Vm::new();
+        let binary = Binary {
+            left: Box::new(Expr::Literal(Literal {
+                value: LiteralValue::Number(5.5),
+            })),
+            operator: Box::new(Token::Star { line: 1 }),
+            right: Box::new(Expr::Literal(Literal {
+                value: LiteralValue::String("Hello".to_string()),
+            })),
+        };
+        assert!(binary.accept(&mut vm).is_err());
+
+        let binary = Binary {
+            left: Box::new(Expr::Literal(Literal {
+                value: LiteralValue::Number(5.5),
+            })),
+            operator: Box::new(Token::Star { line: 1 }),
+            right: Box::new(Expr::Literal(Literal {
+                value: LiteralValue::Boolean(false),
+            })),
+        };
+        assert!(binary.accept(&mut vm).is_err());
+    }
+
+    #[test]
+    fn test_evaluating_global_variables() {
+        let mut vm = Vm::new();
+
+        let definition_statement = Statement::Variable(VariableStatement {
+            name: Box::new(Identifier {
+                value: "x".into(),
+                line: 1,
+            }),
+            mutable: true,
+            value: Box::new(Expr::Literal(Literal {
+                value: LiteralValue::Number(42.0),
+            })),
+        });
+
+        definition_statement.accept(&mut vm).unwrap();
+
+        let variable_expression = Expr::Variable(Variable {
+            token: Box::new(Identifier {
+                line: 1,
+                value: "x".into(),
+            }),
+            depth: Cell::new(None),
+        });
+
+        let result = variable_expression.accept(&mut vm).unwrap();
+        assert_eq!(result, Value::Number(42.0));
+    }
+
+    #[test]
+    fn test_evaluating_assignment() {
+        let mut vm = Vm::new();
+
+        let statements = vec![
+            Statement::Variable(VariableStatement {
+                name: Box::new(Identifier {
+                    value: "x".into(),
+                    line: 1,
+                }),
+                mutable: true,
+                value: Box::new(Expr::Literal(Literal {
+                    value: LiteralValue::Number(42.0),
+                })),
+            }),
+            Statement::Expression(ExpressionStatement {
+                expression: Box::new(Expr::Assignment(Assignment {
+                    name: Box::new(Identifier {
+                        value: "x".into(),
+                        line: 1,
+                    }),
+                    depth: Cell::new(None),
+                    value: Box::new(Expr::Literal(Literal {
+                        value: LiteralValue::Number(10.0),
+                    })),
+                })),
+            }),
+        ];
+
+        for statement in statements {
+            statement.accept(&mut vm).unwrap();
+        }
+
+        let variable_expression = Expr::Variable(Variable {
+            token: Box::new(Identifier {
+                line: 1,
+                value: "x".into(),
+            }),
+            depth: Cell::new(None),
+        });
+
+        let result = variable_expression.accept(&mut vm).unwrap();
+        assert_eq!(result, Value::Number(10.0));
+    }
+
+    #[test]
+    fn test_if_statement() {
+        let mut vm = Vm::new();
+
+        let statements = vec![
+            Statement::Variable(VariableStatement {
+                name: Box::new(Identifier {
+                    value: "x".into(),
+                    line: 1,
+                }),
+                mutable: true,
+                value: Box::new(Expr::Literal(Literal {
+                    value: LiteralValue::Number(42.0),
+                })),
+            }),
+            Statement::If(IfStatement {
+                keyword: Box::new(Token::If { line: 1 }),
+                condition: Box::new(Expr::Literal(Literal {
+                    value: LiteralValue::Boolean(true),
+                })),
+                then_branch: Box::new(Statement::Expression(ExpressionStatement {
+                    expression: Box::new(Expr::Assignment(Assignment {
+                        name: Box::new(Identifier {
+                            value: "x".into(),
+                            line: 1,
+                        }),
+                        depth: Cell::new(None),
+                        value: Box::new(Expr::Literal(Literal {
+                            value: LiteralValue::Number(10.0),
+                        })),
+                    })),
+                })),
+                else_branch: None,
+            }),
+        ];
+
+        for statement in statements {
+            statement.accept(&mut vm).unwrap();
+        }
+        let variable_expression = Expr::Variable(Variable {
+            token: Box::new(Identifier {
+                line: 1,
+                value: "x".into(),
+            }),
+            depth: Cell::new(None),
+        });
+
+        let result = variable_expression.accept(&mut vm).unwrap();
+        assert_eq!(result, Value::Number(10.0));
+    }
+
+    #[test]
+    fn test_if_statement_with_else() {
+        let mut vm = Vm::new();
+
+        let statements = vec![
+            Statement::Variable(VariableStatement {
+                name: Box::new(Identifier {
+                    value: "x".into(),
+                    line: 1,
+                }),
+                mutable: true,
+                value: Box::new(Expr::Literal(Literal {
+                    value: LiteralValue::Number(42.0),
+                })),
+            }),
+            Statement::If(IfStatement {
+                keyword: Box::new(Token::If { line: 1 }),
+                condition: Box::new(Expr::Literal(Literal {
+                    value: LiteralValue::Boolean(false),
+                })),
+                then_branch: Box::new(Statement::Expression(ExpressionStatement {
+                    expression: Box::new(Expr::Assignment(Assignment {
+                        name: Box::new(Identifier {
+                            value: "x".into(),
+                            line: 1,
+                        }),
+                        depth: Cell::new(None),
+                        value: Box::new(Expr::Literal(Literal {
+                            value: LiteralValue::Number(10.0),
+                        })),
+                    })),
+                })),
+                else_branch: Some(Box::new(Statement::Expression(ExpressionStatement {
+                    expression: Box::new(Expr::Assignment(Assignment {
+                        name: Box::new(Identifier {
+                            value: "x".into(),
+                            line: 1,
+                        }),
+                        depth: Cell::new(None),
+                        value: Box::new(Expr::Literal(Literal {
+                            value: LiteralValue::Number(5.0),
+                        })),
+                    })),
+                }))),
+            }),
+        ];
+
+        for statement in statements {
+            statement.accept(&mut vm).unwrap();
+        }
+        let variable_expression = Expr::Variable(Variable {
+            token: Box::new(Identifier {
+                line: 1,
+                value: "x".into(),
+            }),
+            depth: Cell::new(None),
+        });
+
+        let result = variable_expression.accept(&mut vm).unwrap();
+        assert_eq!(result, Value::Number(5.0));
+    }
+
+    #[test]
+    fn test_or_statement() {
+        let mut vm = Vm::new();
+
+        let statements = vec![Statement::Variable(VariableStatement {
+            name: Box::new(Identifier {
+                value: "x".into(),
+                line: 1,
+            }),
+            mutable: true,
+            value: Box::new(Expr::Logical(Logical {
+                left: Box::new(Expr::Literal(Literal {
+                    value: LiteralValue::Boolean(false),
+                })),
+                operator: Box::new(Token::Or { line: 1 }),
+                right: Box::new(Expr::Literal(Literal {
+                    value: LiteralValue::Number(5.0),
+                })),
+            })),
+        })];
+
+        for statement in statements {
+            statement.accept(&mut vm).unwrap();
+        }
+        let variable_expression = Expr::Variable(Variable {
+            token: Box::new(Identifier {
+                line: 1,
+                value: "x".into(),
+            }),
+            depth: Cell::new(None),
+        });
+
+        let result = variable_expression.accept(&mut vm).unwrap();
+        assert_eq!(result, Value::Number(5.0));
+    }
+
+    #[test]
+    fn test_or_statement_short_circuit() {
+        let mut vm = Vm::new();
+
+        let statements = vec![Statement::Variable(VariableStatement {
+            name: Box::new(Identifier {
+                value: "x".into(),
+                line: 1,
+            }),
+            mutable: true,
+            value: Box::new(Expr::Logical(Logical {
+                left: Box::new(Expr::Literal(Literal {
+                    value: LiteralValue::Number(15.0),
+                })),
+                operator: Box::new(Token::Or { line: 1 }),
+                right: Box::new(Expr::Literal(Literal {
+                    value: LiteralValue::Number(5.0),
+                })),
+            })),
+        })];
+
+        for statement in statements {
+            statement.accept(&mut vm).unwrap();
+        }
+        let variable_expression = Expr::Variable(Variable {
+            token: Box::new(Identifier {
+                line: 1,
+                value: "x".into(),
+            }),
+            depth: Cell::new(None),
+        });
+
+        let result = variable_expression.accept(&mut vm).unwrap();
+        assert_eq!(result, Value::Number(15.0));
+    }
+
+    #[test]
+    fn test_and_statement() {
+        let mut vm = Vm::new();
+
+        let statements = vec![Statement::Variable(VariableStatement {
+            name: Box::new(Identifier {
+                value: "x".into(),
+                line: 1,
+            }),
+            mutable: true,
+            value: Box::new(Expr::Logical(Logical {
+                left: Box::new(Expr::Literal(Literal {
+                    value: LiteralValue::Boolean(true),
+                })),
+                operator: Box::new(Token::And { line: 1 }),
+                right: Box::new(Expr::Literal(Literal {
+                    value: LiteralValue::Number(5.0),
+                })),
+            })),
+        })];
+
+        for statement in statements {
+            statement.accept(&mut vm).unwrap();
+        }
+        let variable_expression = Expr::Variable(Variable {
+            token: Box::new(Identifier {
+                line: 1,
+                value: "x".into(),
+            }),
+            depth: Cell::new(None),
+        });
+
+        let result = variable_expression.accept(&mut vm).unwrap();
+        assert_eq!(result, Value::Number(5.0));
+    }
+
+    #[test]
+    fn test_and_statement_short_circuit() {
+        let mut vm = Vm::new();
+
+        let statements = vec![Statement::Variable(VariableStatement {
+            name: Box::new(Identifier {
+                value: "x".into(),
+                line: 1,
+            }),
+            mutable: true,
+            value: Box::new(Expr::Logical(Logical {
+                left: Box::new(Expr::Literal(Literal {
+                    value: LiteralValue::Boolean(false),
+                })),
+                operator: Box::new(Token::And { line: 1 }),
+                right: Box::new(Expr::Literal(Literal {
+                    value: LiteralValue::Number(5.0),
+                })),
+            })),
+        })];
+
+        for statement in statements {
+            statement.accept(&mut vm).unwrap();
+        }
+        let variable_expression = Expr::Variable(Variable {
+            token: Box::new(Identifier {
+                line: 1,
+                value: "x".into(),
+            }),
+            depth: Cell::new(None),
+        });
+
+        let result = variable_expression.accept(&mut vm).unwrap();
+        assert_eq!(result, Value::Boolean(false));
+    }
+
+    #[test]
+    fn test_while_loop() {
+        let mut vm = Vm::new();
+
+        let statements = vec![
+            Statement::Variable(VariableStatement {
+                name: Box::new(Identifier {
+                    value: "x".into(),
+                    line: 1,
+                }),
+                mutable: true,
+                value: Box::new(Expr::Literal(Literal {
+                    value: LiteralValue::Number(0.0),
+                })),
+            }),
+            Statement::While(WhileStatement {
+                condition: Box::new(Expr::Binary(Binary {
+                    left: Box::new(Expr::Variable(Variable {
+                        token: Box::new(Identifier {
+                            value: "x".into(),
+                            line: 1,
+                        }),
+                        depth: Cell::new(None),
+                    })),
+                    operator: Box::new(Token::Less { line: 1 }),
+                    right: Box::new(Expr::Literal(Literal {
+                        value: LiteralValue::Number(5.0),
+                    })),
+                })),
+                body: Box::new(Statement::Variable(VariableStatement {
+                    name: Box::new(Identifier {
+                        value: "x".into(),
+                        line: 1,
+                    }),
+                    mutable: true,
+                    value: Box::new(Expr::Binary(Binary {
+                        left: Box::new(Expr::Variable(Variable {
+                            token: Box::new(Identifier {
+                                value: "x".into(),
+                                line: 1,
+                            }),
+                            depth: Cell::new(None),
+                        })),
+                        operator: Box::new(Token::Plus { line: 1 }),
+                        right: Box::new(Expr::Literal(Literal {
+                            value: LiteralValue::Number(1.0),
+                        })),
+                    })),
+                })),
+                increment: None,
+                else_branch: None,
+            }),
+        ];
+
+        for statement in statements {
+            statement.accept(&mut vm).unwrap();
+        }
+        let variable_expression = Expr::Variable(Variable {
+            token: Box::new(Identifier {
+                line: 1,
+                value: "x".into(),
+            }),
+            depth: Cell::new(None),
+        });
+
+        let result = variable_expression.accept(&mut vm).unwrap();
+        assert_eq!(result, Value::Number(5.0));
+    }
+
+    #[test]
+    fn test_class_method_reads_assigned_field() {
+        let vm = run(r#"
+            class Greeter {
+                greet() {
+                    return "Hello, " + this.name;
+                }
+            }
+
+            var greeter = Greeter();
+            greeter.name = "Lox";
+            var result = greeter.greet();
+        "#)
+        .unwrap();
+
+        assert_eq!(global(&vm, "result"), Value::String("Hello, Lox".to_string()));
+    }
+
+    #[test]
+    fn test_class_initializer_and_undefined_property() {
+        let vm = run(r#"
+            class Point {
+                init(x, y) {
+                    this.x = x;
+                    this.y = y;
+                }
+
+                sum() {
+                    return this.x + this.y;
+                }
+            }
+
+            var result = Point(1, 2).sum();
+        "#)
+        .unwrap();
+        assert_eq!(global(&vm, "result"), Value::Number(3.0));
+
+        let result = run("class Empty {} var value = Empty().missing;");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_calling_native_functions() {
+        let vm = run("var result = group(1234567);").unwrap();
+        assert_eq!(global(&vm, "result"), Value::String("1,234,567".to_string()));
+
+        assert!(run("group(1, 2);").is_err());
+    }
+
+    #[test]
+    fn test_overriding_method_calls_super() {
+        let vm = run(r#"
+            class Animal {
+                speak() {
+                    return "...";
+                }
+
+                describe() {
+                    return "I say " + this.speak();
+                }
+            }
+
+            class Dog < Animal {
+                speak() {
+                    return "Woof and " + super.speak();
+                }
+            }
+
+            var result = Dog().describe();
+        "#)
+        .unwrap();
+
+        assert_eq!(global(&vm, "result"), Value::String("I say Woof and ...".to_string()));
+    }
+
+    #[test]
+    fn test_superclass_must_be_a_class() {
+        assert!(run("var NotAClass = 1; class Dog < NotAClass {}").is_err());
+    }
+
+    #[test]
+    fn test_closures_capture_the_binding_visible_at_declaration() {
+        let vm = run(r#"
+            var a = "global";
+            var first = nil;
+            var second = nil;
+
+            {
+                fun showA() {
+                    return a;
+                }
+
+                first = showA();
+                var a = "block";
+                second = showA();
+            }
+        "#)
+        .unwrap();
+
+        assert_eq!(global(&vm, "first"), Value::String("global".to_string()));
+        assert_eq!(global(&vm, "second"), Value::String("global".to_string()));
+    }
+
+    #[test]
+    fn test_converting_an_instance_to_a_map() {
+        let vm = run(r#"
+            class Point {
+                norm() {
+                    return this.x + this.y;
+                }
+            }
+
+            var point = Point();
+            point.y = 2;
+            point.x = 1;
+            var result = to_map(point);
+        "#)
+        .unwrap();
+
+        match global(&vm, "result") {
+            Value::Map(entries) => {
+                let entries = entries.borrow();
+                assert_eq!(entries.len(), 2);
+                assert_eq!(
+                    entries.entries()[0],
+                    (Value::String("x".to_string()), Value::Number(1.0))
+                );
+                assert_eq!(
+                    entries.entries()[1],
+                    (Value::String("y".to_string()), Value::Number(2.0))
+                );
+            }
+            other => panic!("Expected a map, but got {}", other),
+        }
+
+        assert!(run("to_map(1);").is_err());
+    }
+
+    #[test]
+    fn test_json_natives() {
+        let vm = run(r#"
+            var data = json_parse("[1, 2, [true, null]]");
+            var result = json_stringify(data);
+        "#)
+        .unwrap();
+
+        assert_eq!(global(&vm, "result"), Value::String("[1,2,[true,null]]".to_string()));
+        assert!(run("fun f() {} json_stringify(f);").is_err());
+    }
+
+    #[test]
+    fn test_reassigning_constants() {
+        let result = run("const RATE = 2.5; RATE = 3;");
+        assert_eq!(result.err().unwrap().to_string(), "Cannot assign to constant 'RATE'");
+
+        let vm = run("const RATE = 2.5; var result = 0; { var RATE = 3; RATE = 4; result = RATE; }").unwrap();
+        assert_eq!(global(&vm, "result"), Value::Number(4.0));
+        assert_eq!(global(&vm, "RATE"), Value::Number(2.5));
+    }
+
+    #[test]
+    fn test_implicit_return_of_trailing_expression() {
+        let vm = run("fun double(x) { x * 2 } var result = double(21);").unwrap();
+        assert_eq!(global(&vm, "result"), Value::Number(42.0));
+
+        let vm = run("fun noop(x) { x * 2; } var result = noop(21);").unwrap();
+        assert_eq!(global(&vm, "result"), Value::Nil);
+
+        let vm = run("fun ends_with_statement() { var x = 1; } var result = ends_with_statement();").unwrap();
+        assert_eq!(global(&vm, "result"), Value::Nil);
+    }
+
+    #[test]
+    fn test_array_literals_and_indexing() {
+        let vm = run("var list = [1, 2 + 1, \"three\"]; var first = list[0]; var second = list[1];").unwrap();
+        assert_eq!(global(&vm, "first"), Value::Number(1.0));
+        assert_eq!(global(&vm, "second"), Value::Number(3.0));
+        assert_eq!(global(&vm, "list").to_string(), "[1, 3, three]");
+
+        let vm = run("var empty = []; var nested = [[1, 2], [3]]; var value = nested[1][0];").unwrap();
+        assert_eq!(global(&vm, "empty").to_string(), "[]");
+        assert_eq!(global(&vm, "value"), Value::Number(3.0));
+
+        let vm = run("var list = [1, 2, 3]; var value = list[2];").unwrap();
+        assert_eq!(global(&vm, "value"), Value::Number(3.0));
+    }
+
+    #[test]
+    fn test_assigning_array_elements() {
+        let vm = run("var list = [1, 2]; var alias = list; list[1] = 5; var value = alias[1];").unwrap();
+        assert_eq!(global(&vm, "value"), Value::Number(5.0));
+    }
+
+    #[test]
+    fn test_invalid_array_indices() {
+        let result = run("var list = [1, 2]; print list[2];");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "[line 1] Index 2 out of bounds for array of length 2."
+        );
+
+        let result = run("var list = [1, 2]; print list[0.5];");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "[line 1] Array index must be a non-negative integer, but got 0.5."
+        );
+
+        let result = run("var list = [1]; list[-1] = 2;");
+        assert!(result.is_err());
+
+        let result = run("var list = [1, 2, 3]; var value = list[1.5];");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "[line 1] Array index must be a non-negative integer, but got 1.5."
+        );
+
+        let result = run("var number = 1; print number[0];");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "[line 1] Only arrays, maps and strings can be indexed."
+        );
+    }
+
+    #[test]
+    fn test_len_native() {
+        let vm = run("var a = len(\"abc\"); var b = len([1, 2, 3, 4]);").unwrap();
+        assert_eq!(global(&vm, "a"), Value::Number(3.0));
+        assert_eq!(global(&vm, "b"), Value::Number(4.0));
+
+        let result = run("len(42);");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "Expected string or array, but got 42"
+        );
+
+        let result = run("len(\"a\", \"b\");");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "[line 1] Expected 1 arguments but got 2 in call to 'len'."
+        );
+    }
+
+    #[test]
+    fn test_eval_native() {
+        let vm = run("var result = eval(\"1 + 2\");").unwrap();
+        assert_eq!(global(&vm, "result"), Value::Number(3.0));
+
+        let vm = run("eval(\"var defined = 40;\"); var result = defined + 2;").unwrap();
+        assert_eq!(global(&vm, "result"), Value::Number(42.0));
+
+        let vm = run("fun f() { return eval(\"var a = 1; a + 1\"); } var result = f();").unwrap();
+        assert_eq!(global(&vm, "result"), Value::Number(2.0));
+
+        let result = run("eval(\"1 +\");");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_eval_only_sees_globals() {
+        let vm = run("var name = \"global\";
+            fun f() { var name = \"local\"; return eval(\"name\"); }
+            var result = f();")
+        .unwrap();
+        assert_eq!(global(&vm, "result"), Value::String("global".to_string()));
+
+        let result = run("fun f() { var hidden = 1; return eval(\"hidden\"); } f();");
+        assert!(result.is_err());
+
+        let vm = run("fun f() { eval(\"var leaked = 1;\"); } f(); var result = leaked;").unwrap();
+        assert_eq!(global(&vm, "result"), Value::Number(1.0));
+    }
+
+    #[test]
+    fn test_eval_is_rejected_in_sandbox_mode() {
+        let mut vm = Vm::new().sandbox(true);
+        let result = vm.eval("1 + 2");
+        assert_eq!(result.err().unwrap().to_string(), "eval is disabled in sandbox mode");
+
+        let mut vm = Vm::new();
+        assert_eq!(vm.eval("1 + 2").unwrap(), Value::Number(3.0));
+    }
+
+    #[test]
+    fn test_clock_is_removed_in_sandbox_mode() {
+        let vm = run("var now = clock();").unwrap();
+        assert!(matches!(global(&vm, "now"), Value::Number(seconds) if seconds > 0.0));
+
+        let error = run_in(Vm::new().sandbox(true), "clock();").err().unwrap();
+        assert!(matches!(error, RuntimeError::UndefinedVariable(_)));
+        assert_eq!(error.to_string(), "clock variable is not defined");
+
+        let mut vm = Vm::new();
+        vm.remove_global("len");
+        assert!(matches!(
+            run_in(vm, "len([]);"),
+            Err(RuntimeError::UndefinedVariable(_))
+        ));
+    }
+
+    #[test]
+    fn test_conditional_expressions() {
+        let vm = run("var a = true ? 1 : 2 == 1; var b = nil ? 1 : false ? 2 : 3;").unwrap();
+        assert_eq!(global(&vm, "a"), Value::Number(1.0));
+        assert_eq!(global(&vm, "b"), Value::Number(3.0));
+
+        let vm =
+            run("var calls = 0; fun bump() { calls = calls + 1; return calls; } var c = true ? 0 : bump();").unwrap();
+        assert_eq!(global(&vm, "c"), Value::Number(0.0));
+        assert_eq!(global(&vm, "calls"), Value::Number(0.0));
+    }
+
+    #[test]
+    fn test_power_operator() {
+        let vm = run("var a = 2 ** 3 ** 2; var b = 2 * 3 ** 2; var c = -2 ** 2; var d = 4 ** 0.5;").unwrap();
+        assert_eq!(global(&vm, "a"), Value::Number(512.0));
+        assert_eq!(global(&vm, "b"), Value::Number(18.0));
+        assert_eq!(global(&vm, "c"), Value::Number(4.0));
+        assert_eq!(global(&vm, "d"), Value::Number(2.0));
+
+        let result = run("2 ** nil;");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "[line 1] Expected number, but got nil"
+        );
+
+        let result = run("true ** 2;");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "[line 1] Invalid operands for **: true and 2"
+        );
+    }
+
+    #[test]
+    fn test_catching_runtime_errors() {
+        let vm = run("var result = nil; try { var x = 1 / 0; result = 1; } catch (e) { result = e; }").unwrap();
+        assert_eq!(
+            global(&vm, "result"),
+            Value::String("[line 1] Cannot divide 1 by zero".to_string())
+        );
+
+        let vm = run("var result = 0; try { result = 1; } catch (e) { result = 2; }").unwrap();
+        assert_eq!(global(&vm, "result"), Value::Number(1.0));
+
+        let result = run("try { 1 / 0; } catch (e) { nil + 1; }");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_returns_are_not_caught() {
+        let vm = run("fun f() { try { return 1; } catch (e) { return 2; } return 3; } var result = f();").unwrap();
+        assert_eq!(global(&vm, "result"), Value::Number(1.0));
+    }
+
+    #[test]
+    fn test_throwing_user_errors() {
+        let result = run("throw \"boom\";");
+        assert_eq!(result.err().unwrap().to_string(), "boom");
+
+        let vm = run("var caught = nil; try { throw \"boom\"; } catch (e) { caught = e == \"boom\"; }").unwrap();
+        assert_eq!(global(&vm, "caught"), Value::Boolean(true));
+
+        let vm =
+            run("fun fail() { throw [1, 2]; } var caught = nil; try { fail(); } catch (e) { caught = e[1]; }").unwrap();
+        assert_eq!(global(&vm, "caught"), Value::Number(2.0));
+    }
+
+    #[test]
+    fn test_finally_blocks_always_run() {
+        let vm = run("var log = \"\"; try { log = log + \"try \"; } finally { log = log + \"finally\"; }").unwrap();
+        assert_eq!(global(&vm, "log"), Value::String("try finally".to_string()));
+
+        let vm =
+            run("var log = nil; try { throw \"boom\"; } catch (e) { log = e; } finally { log = log + \" finally\"; }")
+                .unwrap();
+        assert_eq!(global(&vm, "log"), Value::String("boom finally".to_string()));
+
+        let vm =
+            run("var ran = false; fun f() { try { return 1; } finally { ran = true; } return 2; } var result = f();")
+                .unwrap();
+        assert_eq!(global(&vm, "result"), Value::Number(1.0));
+        assert_eq!(global(&vm, "ran"), Value::Boolean(true));
+
+        let vm = run(
+            "var ran = false; fun f() { try { throw \"boom\"; } finally { ran = true; } } try { f(); } catch (e) {}",
+        )
+        .unwrap();
+        assert_eq!(global(&vm, "ran"), Value::Boolean(true));
+    }
+
+    #[test]
+    fn test_iterating_arrays_with_for_in() {
+        let vm = run("var sum = 0; for (var x in [1, 2, 3]) sum = sum + x;").unwrap();
+        assert_eq!(global(&vm, "sum"), Value::Number(6.0));
+
+        let result = run("for (var x in 42) print x;");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "[line 1] Can only iterate over arrays and generators, but got 42."
+        );
+    }
+
+    #[test]
+    fn test_generators() {
+        let source = "
+            fun count(limit) {
+                var i = 1;
+                while (i <= limit) {
+                    yield i;
+                    i = i + 1;
+                }
+            }
+
+            var sum = 0;
+            for (var x in count(3)) {
+                sum = sum + x;
+            }
+        ";
+        let vm = run(source).unwrap();
+        assert_eq!(global(&vm, "sum"), Value::Number(6.0));
+
+        let vm = run("fun early() { yield 1; return; yield 2; }
+            var values = \"\";
+            for (var x in early()) values = values .. x;")
+        .unwrap();
+        assert_eq!(global(&vm, "values"), Value::String("1".to_string()));
+    }
+
+    #[test]
+    fn test_taking_values_from_an_infinite_generator() {
+        let vm = run("fun naturals() { var i = 0; while (true) yield i++; }
+            var numbers = naturals();
+            var first = [numbers.next(), numbers.next(), numbers.next()];
+            var taken = \"\";
+            for (var n in naturals()) {
+                if (n == 4) break;
+                taken = taken .. n;
+            }")
+        .unwrap();
+        assert_eq!(global(&vm, "first").to_string(), "[0, 1, 2]");
+        assert_eq!(global(&vm, "taken"), Value::String("0123".to_string()));
+    }
+
+    #[test]
+    fn test_generators_suspend_at_each_yield() {
+        let vm = run("var log = \"\";
+            fun steps() { log = log .. \"start \"; yield 1; log = log .. \"resumed \"; yield 2; log = log .. \"end \"; }
+            var generator = steps();
+            log = log .. \"created \";
+            var first = generator.next();
+            log = log .. \"got \" .. first .. \" \";
+            var second = generator.next();
+            var done = generator.next();
+            var still_done = generator.next();")
+        .unwrap();
+        assert_eq!(
+            global(&vm, "log"),
+            Value::String("created start got 1 resumed end ".to_string())
+        );
+        assert_eq!(global(&vm, "second"), Value::Number(2.0));
+        assert_eq!(global(&vm, "done"), Value::Nil);
+        assert_eq!(global(&vm, "still_done"), Value::Nil);
+        assert_eq!(global(&vm, "generator").to_string(), "<generator steps>");
+    }
+
+    #[test]
+    fn test_yielding_from_nested_statements() {
+        let vm = run("fun evens(values) {
+                for (var value in values) {
+                    if (value == 2 or value == 4) { yield value; } else { continue; }
+                }
+            }
+            fun nested() {
+                var i = 0;
+                do {
+                    try {
+                        for (var even in evens([1, 2, 3, 4])) yield i .. \":\" .. even;
+                        throw \"oops\";
+                    } catch (error) {
+                        yield error;
+                    } finally {
+                        yield \"finally\";
+                    }
+                    i = i + 1;
+                } while (i < 2);
+            }
+            var values = \"\";
+            for (var value in nested()) values = values .. value .. \" \";")
+        .unwrap();
+        assert_eq!(
+            global(&vm, "values"),
+            Value::String("0:2 0:4 oops finally 1:2 1:4 oops finally ".to_string())
+        );
+    }
+
+    #[test]
+    fn test_resuming_a_running_generator_errors() {
+        let result = run("var generator = nil;
+            fun recursive() { generator.next(); yield 1; }
+            generator = recursive();
+            generator.next();");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "Generator 'recursive' is already running."
+        );
+    }
+
+    #[test]
+    fn test_format_native() {
+        let vm = run("var result = format(\"{1} {0}!\", \"world\", \"hello\");").unwrap();
+        assert_eq!(global(&vm, "result"), Value::String("hello world!".to_string()));
+
+        let vm = run("var sum = format(\"{} + {} = {}\", 1, 2, 3);").unwrap();
+        assert_eq!(global(&vm, "sum"), Value::String("1 + 2 = 3".to_string()));
+
+        let result = run("format(\"{} + {} = {}\", 1, 2);");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "Format index 2 out of range for 2 arguments"
+        );
+        let result = run("format(\"{}\", 1, 2);");
+        assert_eq!(result.err().unwrap().to_string(), "Format argument 1 is never used");
+
+        let result = run("format();");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "[line 1] Expected at least 1 arguments but got 0 in call to 'format'."
+        );
+    }
+
+    #[test]
+    fn test_chr_and_ord_natives() {
+        let vm = run("var code = ord(\"A\"); var letter = chr(code + 1); var round_trip = chr(ord(\"z\")) == \"z\";")
+            .unwrap();
+        assert_eq!(global(&vm, "code"), Value::Number(65.0));
+        assert_eq!(global(&vm, "letter"), Value::String("B".to_string()));
+        assert_eq!(global(&vm, "round_trip"), Value::Boolean(true));
+    }
+
+    #[test]
+    fn test_arity_errors_name_the_callee() {
+        let error = run("fun foo(a, b) {}\nfoo(1, 2, 3);").err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "[line 2] Expected 2 arguments but got 3 in call to 'foo'."
+        );
+
+        let error = run("class Point { init(x, y) {} }\nPoint(1);").err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "[line 2] Expected 2 arguments but got 1 in call to 'Point'."
+        );
+    }
+
+    #[test]
+    fn test_runtime_errors_know_their_line() {
+        let error = run("var a = 1;\n\nprint a / 0;").err().unwrap();
+        assert_eq!(error.line(), Some(3));
+
+        let error = run("throw \"oops\";").err().unwrap();
+        assert_eq!(error.line(), None);
+    }
+
+    #[test]
+    fn test_infinite_recursion_hits_the_depth_limit() {
+        let mut vm = Vm::new().with_max_depth(20);
+        let result = vm.eval("var calls = 0;\nfun recurse() { calls = calls + 1; return recurse(); }\nrecurse();");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "[line 2] Stack overflow in call to 'recurse'."
+        );
+        assert_eq!(vm.eval("calls").unwrap(), Value::Number(20.0));
+
+        // The depth is unwound after the error, so later calls work again
+        assert_eq!(vm.eval("fun one() { return 1; } one()").unwrap(), Value::Number(1.0));
+    }
+
+    #[test]
+    fn test_infinite_recursion_errors_within_a_default_size_thread() {
+        let errors = std::thread::spawn(|| {
+            let mut vm = Vm::new();
+            let plain = vm.eval("fun r(n) { return r(n + 1); } r(0)");
+            let nested = vm.eval("fun s(n) { { while (true) { if (true) { return 1 + 2 * [s(n + 1)][0]; } } } } s(0)");
+            let generator = vm.eval("fun g() { for (var x in g()) yield x; yield 1; } for (var x in g()) print x;");
+            [plain, nested, generator].map(|result| result.err().unwrap().to_string())
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(errors[0], "[line 1] Stack overflow in call to 'r'.");
+        assert_eq!(errors[1], "[line 1] Stack overflow in call to 's'.");
+        assert!(errors[2].contains("Stack overflow"), "{}", errors[2]);
+    }
+
+    #[test]
+    fn test_print_as_a_function() {
+        let mut vm = Vm::new();
+        assert_eq!(vm.eval("print 1; 2").unwrap(), Value::Number(2.0));
+        assert!(vm.eval("var x = print(1);").is_err());
+
+        let mut vm = Vm::new().print_as_function(true);
+        assert_eq!(vm.eval("var x = print(1 + 2); x").unwrap(), Value::Number(3.0));
+        assert_eq!(vm.eval("print").unwrap().to_string(), "<native fn print>");
+        assert!(vm.eval("print 1;").is_err());
+    }
+
+    #[test]
+    fn test_defining_globals_from_the_host() {
+        let mut vm = Vm::new();
+        vm.define_global("host_value", Value::Number(41.0));
+        vm.define_global("host_name", Value::String("rlox".to_string()));
+
+        assert_eq!(vm.eval("host_value + 1").unwrap(), Value::Number(42.0));
+        assert_eq!(
+            vm.eval("fun name() { return host_name; } name()").unwrap(),
+            Value::String("rlox".to_string())
+        );
+    }
+
+    #[test]
+    fn test_registering_native_functions() {
+        let offset = 10.0;
+        let mut vm = Vm::new();
+        vm.register_native("add_offset", 2, move |arguments| match (&arguments[0], &arguments[1]) {
+            (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a + b + offset)),
+            _ => Err(RuntimeError::ArgumentError("Expected numbers".to_string())),
+        });
+
+        assert_eq!(vm.eval("add_offset(1, 2)").unwrap(), Value::Number(13.0));
+        assert_eq!(
+            vm.eval("add_offset(1)").err().unwrap().to_string(),
+            "[line 1] Expected 2 arguments but got 1 in call to 'add_offset'."
+        );
+        assert_eq!(vm.eval("add_offset").unwrap().to_string(), "<native fn add_offset>");
+    }
+
+    #[test]
+    fn test_boolean_logic_mode() {
+        let mut vm = Vm::new();
+        assert_eq!(vm.eval("1 and 2").unwrap(), Value::Number(2.0));
+        assert_eq!(vm.eval("nil or \"x\"").unwrap(), Value::String("x".to_string()));
+
+        let mut vm = Vm::new().boolean_logic(true);
+        assert_eq!(vm.eval("1 and 2").unwrap(), Value::Boolean(true));
+        assert_eq!(vm.eval("1 and nil").unwrap(), Value::Boolean(false));
+        assert_eq!(vm.eval("nil or \"x\"").unwrap(), Value::Boolean(true));
+        assert_eq!(
+            vm.eval("var called = false; fun f() { called = true; } false and f(); called")
+                .unwrap(),
+            Value::Boolean(false)
+        );
+    }
+
+    #[test]
+    fn test_assert_statements() {
+        let vm = run("var checked = false; assert 1 + 1 == 2; checked = true;").unwrap();
+        assert_eq!(global(&vm, "checked"), Value::Boolean(true));
+
+        let result = run("assert true;\nassert nil;");
+        assert_eq!(result.err().unwrap().to_string(), "Assertion failed at line 2");
+
+        let result = run("var x = 3; assert x < 2, \"x is \" + str(x);");
+        assert_eq!(result.err().unwrap().to_string(), "Assertion failed at line 1: x is 3");
+    }
+
+    #[test]
+    fn test_non_finite_arithmetic_results_are_errors() {
+        let result = run("var big = 10 ** 300; var overflow = big * big;");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            format!(
+                "[line 1] Result of {} * {} is not a finite number",
+                Value::Number(1e300),
+                Value::Number(1e300)
+            )
+        );
+
+        let result = run("var overflow = 10 ** 400;");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "[line 1] Result of 10 ** 400 is not a finite number"
+        );
+
+        let result = run("var nan = (-8) ** 0.5;");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "[line 1] Result of -8 ** 0.5 is not a finite number"
+        );
+
+        let result = run("var zero = 0; var nan = 0 / zero;");
+        assert_eq!(result.err().unwrap().to_string(), "[line 1] Cannot divide 0 by zero");
+
+        let result = run("var n = 10 / -0.0;");
+        assert!(matches!(result, Err(RuntimeError::ZeroDivision(_))));
+        assert_eq!(result.err().unwrap().to_string(), "[line 1] Cannot divide 10 by zero");
+
+        assert!(run("var n = num(\"inf\");").is_err());
+    }
+
+    #[test]
+    fn test_stepping_through_top_level_statements() {
+        let mut errors = Vec::new();
+        let tokens = {
+            let mut scanner = Scanner::new("var a = 1; var b = a + 1; a = b * 10;", &mut errors);
+            scanner.scan();
+            scanner.into_tokens()
+        };
+        let statements = Parser::new(&tokens, &mut errors).parse();
+        assert!(errors.is_empty(), "Expected no errors, but got: {:?}", errors);
+
+        let mut vm = Vm::new();
+        vm.load(statements);
+        assert!(vm.environment().borrow().get("a").is_err());
+
+        assert!(vm.step().unwrap());
+        assert_eq!(global(&vm, "a"), Value::Number(1.0));
+        assert!(vm.environment().borrow().get("b").is_err());
+
+        assert!(vm.step().unwrap());
+        assert_eq!(global(&vm, "b"), Value::Number(2.0));
+        assert!(matches!(vm.next_statement(), Some(Statement::Expression(_))));
+
+        assert!(vm.step().unwrap());
+        assert_eq!(global(&vm, "a"), Value::Number(20.0));
+        assert!(vm.next_statement().is_none());
+        assert!(!vm.step().unwrap());
     }
 
     #[test]
-    fn test_evaluating_number_addition() {
-        let mut vm = Vm::new();
-        let binary = Binary {
-            left: Box::new(Expr::Literal(Literal {
-                value: LiteralValue::Number(42.0),
-            })),
-            operator: Box::new(Token::Plus { line: 1 }),
-            right: Box::new(Expr::Literal(Literal {
-                value: LiteralValue::Number(58.0),
-            })),
-        };
-        let result = binary.accept(&mut vm).unwrap();
-        assert_eq!(result, Value::Number(100.0));
+    fn test_calling_anonymous_functions() {
+        let vm = run("var double = fun (x) { return x * 2; };
+            var result = double(21);
+            fun adder(n) { return fun (x) { return x + n; }; }
+            var added = adder(1)(2);
+            var immediate = fun () { return \"called\"; }();")
+        .unwrap();
+        assert_eq!(global(&vm, "result"), Value::Number(42.0));
+        assert_eq!(global(&vm, "added"), Value::Number(3.0));
+        assert_eq!(global(&vm, "immediate"), Value::String("called".to_string()));
+        assert_eq!(global(&vm, "double").to_string(), "<fn anonymous>");
     }
 
     #[test]
-    fn test_evaluating_string_addition() {
-        let mut vm = Vm::new();
-        let binary = Binary {
-            left: Box::new(Expr::Literal(Literal {
-                value: LiteralValue::String("Hello".to_string()),
-            })),
-            operator: Box::new(Token::Plus { line: 1 }),
-            right: Box::new(Expr::Literal(Literal {
-                value: LiteralValue::String(" World".to_string()),
-            })),
-        };
-        let result = binary.accept(&mut vm).unwrap();
-        assert_eq!(result, Value::String("Hello World".to_string()));
+    fn test_spreading_arrays_into_call_arguments() {
+        let vm = run("fun sum(a, b, c) { return a + b + c; }
+            var spread = sum(...[1, 2, 3]);
+            var direct = sum(1, 2, 3);
+            var rest = [2, 3];
+            var mixed = sum(1, ...rest);")
+        .unwrap();
+        assert_eq!(global(&vm, "spread"), global(&vm, "direct"));
+        assert_eq!(global(&vm, "mixed"), Value::Number(6.0));
+
+        let result = run("fun f(a) { return a; } f(...42);");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "[line 1] Can only spread arrays, but got number."
+        );
+
+        let result = run("fun f(a) { return a; } f(...[1, 2]);");
+        assert!(result.is_err());
     }
 
     #[test]
-    fn test_evaluating_invalid_addition() {
-        let mut vm = Vm::new();
-        let binary = Binary {
-            left: Box::new(Expr::Literal(Literal {
-                value: LiteralValue::String("Hello".to_string()),
-            })),
-            operator: Box::new(Token::Plus { line: 1 }),
-            right: Box::new(Expr::Literal(Literal {
-                value: LiteralValue::Number(42.0),
-            })),
-        };
-        assert!(binary.accept(&mut vm).is_err());
+    fn test_typeof_native() {
+        let vm = run("class Point {} fun f() {}
+            var types = [typeof(1), typeof(\"s\"), typeof(true), typeof(nil), typeof(f), typeof(len),
+                typeof(Point), typeof(Point()), typeof([]), typeof({})];")
+        .unwrap();
+        assert_eq!(
+            global(&vm, "types").to_string(),
+            "[number, string, boolean, nil, function, function, class, instance, array, map]"
+        );
+        assert!(run("typeof(1, 2);").is_err());
+    }
 
-        let binary = Binary {
-            left: Box::new(Expr::Literal(Literal {
-                value: LiteralValue::Boolean(false),
-            })),
-            operator: Box::new(Token::Plus { line: 1 }),
-            right: Box::new(Expr::Literal(Literal {
-                value: LiteralValue::Number(42.0),
-            })),
-        };
-        assert!(binary.accept(&mut vm).is_err());
+    #[test]
+    fn test_concatenation_operator() {
+        let vm = run("var equal = \"n=\" .. 42 == \"n=42\";
+            var mixed = nil .. true .. [1, 2];
+            class Point { to_string() { return \"(1, 2)\"; } }
+            var point = \"at \" .. Point();")
+        .unwrap();
+        assert_eq!(global(&vm, "equal"), Value::Boolean(true));
+        assert_eq!(global(&vm, "mixed"), Value::String("niltrue[1, 2]".to_string()));
+        assert_eq!(global(&vm, "point"), Value::String("at (1, 2)".to_string()));
     }
 
     #[test]
-    fn test_evaluating_subtraction() {
-        let mut vm = Vm::new();
-        let binary = Binary {
-            left: Box::new(Expr::Literal(Literal {
-                value: LiteralValue::Number(5.0),
-            })),
-            operator: Box::new(Token::Minus { line: 1 }),
-            right: Box::new(Expr::Literal(Literal {
-                value: LiteralValue::Number(2.0),
-            })),
-        };
-        let result = binary.accept(&mut vm).unwrap();
-        assert_eq!(result, Value::Number(3.0));
+    fn test_string_interpolation() {
+        let vm = run("var result = \"a${1+1}b\";
+            var name = \"lox\"; var items = [1, 2];
+            var nested = \"${name}: ${items} ${\"<${len(items)}>\"}\";")
+        .unwrap();
+        assert_eq!(global(&vm, "result"), Value::String("a2b".to_string()));
+        assert_eq!(global(&vm, "nested"), Value::String("lox: [1, 2] <2>".to_string()));
     }
 
     #[test]
-    fn test_evaluating_invalid_subtraction() {
-        let mut vm = Vm::new();
-        let binary = Binary {
-            left: Box::new(Expr::Literal(Literal {
-                value: LiteralValue::String("Hello".to_string()),
-            })),
-            operator: Box::new(Token::Minus { line: 1 }),
-            right: Box::new(Expr::Literal(Literal {
-                value: LiteralValue::Number(42.0),
-            })),
-        };
-        assert!(binary.accept(&mut vm).is_err());
+    fn test_indexing_strings() {
+        let vm = run("var word = \"héllo\"; var second = word[1]; var last = word[4];").unwrap();
+        assert_eq!(global(&vm, "second"), Value::String("é".to_string()));
+        assert_eq!(global(&vm, "last"), Value::String("o".to_string()));
 
-        let binary = Binary {
-            left: Box::new(Expr::Literal(Literal {
-                value: LiteralValue::Boolean(false),
-            })),
-            operator: Box::new(Token::Minus { line: 1 }),
-            right: Box::new(Expr::Literal(Literal {
-                value: LiteralValue::Number(42.0),
-            })),
-        };
-        assert!(binary.accept(&mut vm).is_err());
+        let result = run("var word = \"héllo\"; print word[5];");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "[line 1] Index 5 out of bounds for string of length 5."
+        );
+        assert!(run("var word = \"abc\"; word[0] = \"x\";").is_err());
     }
 
     #[test]
-    fn test_evaluating_division() {
-        let mut vm = Vm::new();
-        let binary = Binary {
-            left: Box::new(Expr::Literal(Literal {
-                value: LiteralValue::Number(5.0),
-            })),
-            operator: Box::new(Token::Slash { line: 1 }),
-            right: Box::new(Expr::Literal(Literal {
-                value: LiteralValue::Number(2.0),
-            })),
-        };
-        let result = binary.accept(&mut vm).unwrap();
-        assert_eq!(result, Value::Number(2.5));
+    fn test_substr_native() {
+        let vm = run(
+            "var middle = substr(\"héllo\", 1, 3); var clamped = substr(\"héllo\", 3, 10);
+            var empty = substr(\"abc\", 3, 1);",
+        )
+        .unwrap();
+        assert_eq!(global(&vm, "middle"), Value::String("éll".to_string()));
+        assert_eq!(global(&vm, "clamped"), Value::String("lo".to_string()));
+        assert_eq!(global(&vm, "empty"), Value::String("".to_string()));
+
+        let result = run("substr(\"abc\", 4, 1);");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "Start 4 out of bounds for string of length 3"
+        );
+        assert!(run("substr(\"abc\", 0.5, 1);").is_err());
     }
 
     #[test]
-    fn test_evaluating_invalid_division() {
-        let mut vm = Vm::new();
-        let binary = Binary {
-            left: Box::new(Expr::Literal(Literal {
-                value: LiteralValue::Number(5.5),
-            })),
-            operator: Box::new(Token::Slash { line: 1 }),
-            right: Box::new(Expr::Literal(Literal {
-                value: LiteralValue::String("Hello".to_string()),
-            })),
-        };
-        assert!(binary.accept(&mut vm).is_err());
+    fn test_math_natives() {
+        let vm = run(
+            "var root = sqrt(9); var is_three = sqrt(9) == 3; var down = floor(2.7); var up = ceil(2.1);
+            var positive = abs(-4.5);",
+        )
+        .unwrap();
+        assert_eq!(global(&vm, "root"), Value::Number(3.0));
+        assert_eq!(global(&vm, "is_three"), Value::Boolean(true));
+        assert_eq!(global(&vm, "down"), Value::Number(2.0));
+        assert_eq!(global(&vm, "up"), Value::Number(3.0));
+        assert_eq!(global(&vm, "positive"), Value::Number(4.5));
 
-        let binary = Binary {
-            left: Box::new(Expr::Literal(Literal {
-                value: LiteralValue::Number(5.5),
-            })),
-            operator: Box::new(Token::Slash { line: 1 }),
-            right: Box::new(Expr::Literal(Literal {
-                value: LiteralValue::Number(0.0),
-            })),
-        };
-        assert!(binary.accept(&mut vm).is_err());
+        let result = run("sqrt(-1);");
+        assert_eq!(result.err().unwrap().to_string(), "Cannot take the square root of -1");
+        let result = run("floor(\"2\");");
+        assert_eq!(result.err().unwrap().to_string(), "Expected number, but got 2");
     }
 
     #[test]
-    fn test_evaluating_multiplication() {
-        let mut vm = Vm::new();
-        let binary = Binary {
-            left: Box::new(Expr::Literal(Literal {
-                value: LiteralValue::Number(5.0),
-            })),
-            operator: Box::new(Token::Star { line: 1 }),
-            right: Box::new(Expr::Literal(Literal {
-                value: LiteralValue::Number(2.0),
-            })),
-        };
-        let result = binary.accept(&mut vm).unwrap();
-        assert_eq!(result, Value::Number(10.0));
+    fn test_variadic_max_native() {
+        let vm = run("var single = max(1); var largest = max(3, -1, 7.5, 2);").unwrap();
+        assert_eq!(global(&vm, "single"), Value::Number(1.0));
+        assert_eq!(global(&vm, "largest"), Value::Number(7.5));
+
+        let result = run("max();");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "[line 1] Expected at least 1 arguments but got 0 in call to 'max'."
+        );
+        let result = run("max(1, \"2\");");
+        assert_eq!(result.err().unwrap().to_string(), "Expected number, but got 2");
     }
 
     #[test]
-    fn test_evaluating_invalid_multiplication() {
-        let mut vm = Vm::new();
-        let binary = Binary {
-            left: Box::new(Expr::Literal(Literal {
-                value: LiteralValue::Number(5.5),
-            })),
-            operator: Box::new(Token::Star { line: 1 }),
-            right: Box::new(Expr::Literal(Literal {
-                value: LiteralValue::String("Hello".to_string()),
-            })),
-        };
-        assert!(binary.accept(&mut vm).is_err());
+    fn test_clone_native_copies_arrays_and_maps_deeply() {
+        let vm = run("var original = [1, [2, 3], {\"a\": [4]}];
+            var copy = clone(original);
+            copy[0] = 10; copy[1][0] = 20; copy[2][\"a\"][0] = 40; copy[2][\"b\"] = 5;
+            var alias = original; alias[0] = 100;
+            var scalar = clone(\"text\");")
+        .unwrap();
+        assert_eq!(global(&vm, "original").to_string(), "[100, [2, 3], {\"a\": [4]}]");
+        assert_eq!(
+            global(&vm, "copy").to_string(),
+            "[10, [20, 3], {\"a\": [40], \"b\": 5}]"
+        );
+        assert_eq!(global(&vm, "scalar"), Value::String("text".to_string()));
 
-        let binary = Binary {
-            left: Box::new(Expr::Literal(Literal {
-                value: LiteralValue::Number(5.5),
-            })),
-            operator: Box::new(Token::Star { line: 1 }),
-            right: Box::new(Expr::Literal(Literal {
-                value: LiteralValue::Boolean(false),
-            })),
-        };
-        assert!(binary.accept(&mut vm).is_err());
+        let vm = run(
+            "var cycle = [1]; cycle[0] = cycle; var copy = clone(cycle); var same = copy[0] == copy;
+            var shared = [1]; var pair = clone([shared, shared]); pair[0][0] = 2;",
+        )
+        .unwrap();
+        assert_eq!(global(&vm, "same"), Value::Boolean(true));
+        assert_eq!(global(&vm, "pair").to_string(), "[[2], [2]]");
     }
 
     #[test]
-    fn test_evaluating_global_variables() {
-        let mut vm = Vm::new();
+    fn test_conversion_natives() {
+        let vm = run("var s = str(42); var same = str(42) == \"42\"; var n = num(\"3.5\");
+            var falsy = bool(nil); var truthy = bool(0);")
+        .unwrap();
+        assert_eq!(global(&vm, "s"), Value::String("42".to_string()));
+        assert_eq!(global(&vm, "same"), Value::Boolean(true));
+        assert_eq!(global(&vm, "n"), Value::Number(3.5));
+        assert_eq!(global(&vm, "falsy"), Value::Boolean(false));
+        assert_eq!(global(&vm, "truthy"), Value::Boolean(true));
 
-        let definition_statement = Statement::Variable(VariableStatement {
-            name: Box::new(Identifier {
-                value: "x".to_string(),
-                line: 1,
-            }),
-            value: Box::new(Expr::Literal(Literal {
-                value: LiteralValue::Number(42.0),
-            })),
-        });
+        let result = run("num(\"x\");");
+        assert_eq!(result.err().unwrap().to_string(), "Cannot convert \"x\" to a number");
+        assert!(run("str(1, 2);").is_err());
+    }
 
-        definition_statement.accept(&mut vm).unwrap();
+    #[test]
+    fn test_comma_operator_yields_the_last_value() {
+        let vm = run("var calls = 0; fun sideEffect() { calls = calls + 1; return calls; }
+            var result = (sideEffect(), 5);
+            var sum = 0; var i = 0; var j = 0;
+            for (i = 0, j = 10; i < 3; i = i + 1, j = j - 1) { sum = sum + j; }")
+        .unwrap();
+        assert_eq!(global(&vm, "result"), Value::Number(5.0));
+        assert_eq!(global(&vm, "calls"), Value::Number(1.0));
+        assert_eq!(global(&vm, "sum"), Value::Number(27.0));
+    }
 
-        let variable_expression = Expr::Variable(Variable {
-            token: Box::new(Identifier {
-                line: 1,
-                value: "x".to_string(),
-            }),
-        });
+    #[test]
+    fn test_last_value_is_the_result_of_the_last_expression_statement() {
+        assert_eq!(Vm::new().last_value(), None);
 
-        let result = variable_expression.accept(&mut vm).unwrap();
-        assert_eq!(result, Value::Number(42.0));
+        let vm = run("1 + 2;").unwrap();
+        assert_eq!(vm.last_value(), Some(Value::Number(3.0)));
+
+        let vm = run("\"a\"; var x = 1; print x;").unwrap();
+        assert_eq!(vm.last_value(), Some(Value::String("a".to_string())));
     }
 
     #[test]
-    fn test_evaluating_assignment() {
-        let mut vm = Vm::new();
+    fn test_do_while_runs_the_body_before_checking_the_condition() {
+        let vm = run("var runs = 0; do { runs = runs + 1; } while (false);").unwrap();
+        assert_eq!(global(&vm, "runs"), Value::Number(1.0));
 
-        let statements = vec![
-            Statement::Variable(VariableStatement {
-                name: Box::new(Identifier {
-                    value: "x".to_string(),
-                    line: 1,
-                }),
-                value: Box::new(Expr::Literal(Literal {
-                    value: LiteralValue::Number(42.0),
-                })),
-            }),
-            Statement::Expression(ExpressionStatement {
-                expression: Box::new(Expr::Assignment(Assignment {
-                    name: Box::new(Identifier {
-                        value: "x".to_string(),
-                        line: 1,
-                    }),
-                    value: Box::new(Expr::Literal(Literal {
-                        value: LiteralValue::Number(10.0),
-                    })),
-                })),
-            }),
-        ];
+        let vm = run("var i = 0; do i = i + 1; while (i < 5);").unwrap();
+        assert_eq!(global(&vm, "i"), Value::Number(5.0));
 
-        for statement in statements {
-            statement.accept(&mut vm).unwrap();
-        }
+        let vm = run("var i = 0; do { i = i + 1; if (i == 2) break; } while (true);").unwrap();
+        assert_eq!(global(&vm, "i"), Value::Number(2.0));
+    }
 
-        let variable_expression = Expr::Variable(Variable {
-            token: Box::new(Identifier {
-                line: 1,
-                value: "x".to_string(),
-            }),
-        });
+    #[test]
+    fn test_while_else_runs_when_the_loop_completes() {
+        let vm = run("var i = 0; var done = false; while (i < 3) { i = i + 1; } else { done = true; }").unwrap();
+        assert_eq!(global(&vm, "i"), Value::Number(3.0));
+        assert_eq!(global(&vm, "done"), Value::Boolean(true));
 
-        let result = variable_expression.accept(&mut vm).unwrap();
-        assert_eq!(result, Value::Number(10.0));
+        let vm = run("var done = false; while (false) {} else done = true;").unwrap();
+        assert_eq!(global(&vm, "done"), Value::Boolean(true));
     }
 
     #[test]
-    fn test_if_statement() {
-        let mut vm = Vm::new();
+    fn test_while_else_is_skipped_when_the_loop_breaks() {
+        let vm = run("var i = 0; var done = false;
+            while (true) { i = i + 1; if (i == 2) break; } else { done = true; }")
+        .unwrap();
+        assert_eq!(global(&vm, "i"), Value::Number(2.0));
+        assert_eq!(global(&vm, "done"), Value::Boolean(false));
+    }
 
-        let statements = vec![
-            Statement::Variable(VariableStatement {
-                name: Box::new(Identifier {
-                    value: "x".to_string(),
-                    line: 1,
-                }),
-                value: Box::new(Expr::Literal(Literal {
-                    value: LiteralValue::Number(42.0),
-                })),
-            }),
-            Statement::If(IfStatement {
-                condition: Box::new(Expr::Literal(Literal {
-                    value: LiteralValue::Boolean(true),
-                })),
-                then_branch: Box::new(Statement::Expression(ExpressionStatement {
-                    expression: Box::new(Expr::Assignment(Assignment {
-                        name: Box::new(Identifier {
-                            value: "x".to_string(),
-                            line: 1,
-                        }),
-                        value: Box::new(Expr::Literal(Literal {
-                            value: LiteralValue::Number(10.0),
-                        })),
-                    })),
-                })),
-                else_branch: None,
-            }),
-        ];
+    #[test]
+    fn test_break_exits_the_innermost_loop() {
+        let vm = run("var count = 0;
+            for (var i = 0; i < 3; i = i + 1) {
+                for (var j = 0; j < 10; j = j + 1) { if (j == 2) break; count = count + 1; }
+            }
+            var seen = 0; for (var x in [1, 2, 3]) { if (x == 3) break; seen = seen + x; }")
+        .unwrap();
+        assert_eq!(global(&vm, "count"), Value::Number(6.0));
+        assert_eq!(global(&vm, "seen"), Value::Number(3.0));
+    }
+
+    #[test]
+    fn test_continue_skips_to_the_next_iteration() {
+        // The for loop increment still runs after `continue`, so the loop terminates
+        let vm = run("var sum = 0; for (var i = 0; i < 10; i = i + 1) { if (i == 3 or i == 5) continue; sum = sum + i; }
+            var odd = 0; var n = 0; while (n < 6) { n = n + 1; if (n == 2 or n == 4 or n == 6) continue; odd = odd + n; }
+            var m = 0; var skipped = 0; do { m = m + 1; if (m == 2) continue; skipped = skipped + m; } while (m < 3);
+            var seen = 0; for (var x in [1, 2, 3]) { if (x == 2) continue; seen = seen + x; }")
+        .unwrap();
+        assert_eq!(global(&vm, "sum"), Value::Number(37.0));
+        assert_eq!(global(&vm, "odd"), Value::Number(9.0));
+        assert_eq!(global(&vm, "skipped"), Value::Number(4.0));
+        assert_eq!(global(&vm, "seen"), Value::Number(4.0));
+    }
+
+    #[test]
+    fn test_postfix_increments_return_the_previous_value() {
+        let vm = run("var i = 1; var before = i++; var j = 5; var down = j--;
+            var sum = 0; for (var k = 0; k < 4; k++) sum = sum + k;
+            fun counter() { var n = 0; fun next() { return n++; } return next; }
+            var next = counter(); next(); var third = next() + next();")
+        .unwrap();
+        assert_eq!(global(&vm, "before"), Value::Number(1.0));
+        assert_eq!(global(&vm, "i"), Value::Number(2.0));
+        assert_eq!(global(&vm, "down"), Value::Number(5.0));
+        assert_eq!(global(&vm, "j"), Value::Number(4.0));
+        assert_eq!(global(&vm, "sum"), Value::Number(6.0));
+        assert_eq!(global(&vm, "third"), Value::Number(3.0));
 
-        for statement in statements {
-            statement.accept(&mut vm).unwrap();
-        }
-        let variable_expression = Expr::Variable(Variable {
-            token: Box::new(Identifier {
-                line: 1,
-                value: "x".to_string(),
-            }),
-        });
+        let error = run("var s = \"a\";\ns++;").err().unwrap();
+        assert_eq!(error.to_string(), "[line 2] Operand of '++' must be a number.");
+        let error = run("var n = nil; n--;").err().unwrap();
+        assert_eq!(error.to_string(), "[line 1] Operand of '--' must be a number.");
+    }
 
-        let result = variable_expression.accept(&mut vm).unwrap();
-        assert_eq!(result, Value::Number(10.0));
+    #[test]
+    fn test_equality_across_types_is_lenient_by_default() {
+        let vm = run("var equal = 1 == \"1\"; var different = true != 1;").unwrap();
+        assert_eq!(global(&vm, "equal"), Value::Boolean(false));
+        assert_eq!(global(&vm, "different"), Value::Boolean(true));
     }
 
     #[test]
-    fn test_if_statement_with_else() {
-        let mut vm = Vm::new();
+    fn test_strict_equality_rejects_mismatched_types() {
+        let mut vm = Vm::new().strict(true);
+        let result = vm.eval("1 == \"1\"");
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "[line 1] Cannot compare number with string."
+        );
+        assert!(
+            vm.eval("\n\ntrue != 0")
+                .unwrap_err()
+                .to_string()
+                .starts_with("[line 3]")
+        );
 
-        let statements = vec![
-            Statement::Variable(VariableStatement {
-                name: Box::new(Identifier {
-                    value: "x".to_string(),
-                    line: 1,
-                }),
-                value: Box::new(Expr::Literal(Literal {
-                    value: LiteralValue::Number(42.0),
-                })),
-            }),
-            Statement::If(IfStatement {
-                condition: Box::new(Expr::Literal(Literal {
-                    value: LiteralValue::Boolean(false),
-                })),
-                then_branch: Box::new(Statement::Expression(ExpressionStatement {
-                    expression: Box::new(Expr::Assignment(Assignment {
-                        name: Box::new(Identifier {
-                            value: "x".to_string(),
-                            line: 1,
-                        }),
-                        value: Box::new(Expr::Literal(Literal {
-                            value: LiteralValue::Number(10.0),
-                        })),
-                    })),
-                })),
-                else_branch: Some(Box::new(Statement::Expression(ExpressionStatement {
-                    expression: Box::new(Expr::Assignment(Assignment {
-                        name: Box::new(Identifier {
-                            value: "x".to_string(),
-                            line: 1,
-                        }),
-                        value: Box::new(Expr::Literal(Literal {
-                            value: LiteralValue::Number(5.0),
-                        })),
-                    })),
-                }))),
-            }),
-        ];
+        assert_eq!(vm.eval("1 == 1").unwrap(), Value::Boolean(true));
+        assert_eq!(vm.eval("\"a\" != \"b\"").unwrap(), Value::Boolean(true));
+        assert_eq!(
+            vm.eval("1 == nil").unwrap_err().to_string(),
+            "[line 1] Cannot compare number with nil."
+        );
+        assert_eq!(vm.eval("nil == nil").unwrap(), Value::Boolean(true));
+    }
 
-        for statement in statements {
-            statement.accept(&mut vm).unwrap();
-        }
-        let variable_expression = Expr::Variable(Variable {
-            token: Box::new(Identifier {
-                line: 1,
-                value: "x".to_string(),
-            }),
-        });
+    #[test]
+    fn test_with_exposes_object_members_as_variables() {
+        let vm = run("class Point { init(x, y) { this.x = x; this.y = y; } }
+            var point = Point(1, 2);
+            var x = 100;
+            var sum = nil; var shadowed = nil; var local = nil;
+            with (point) { sum = x + y; shadowed = x; }
+            with (point) { var x = 5; local = x; }
+            with (to_map(point)) { var from_map = y; sum = sum + from_map; }")
+        .unwrap();
+        assert_eq!(global(&vm, "sum"), Value::Number(5.0));
+        assert_eq!(global(&vm, "shadowed"), Value::Number(1.0));
+        assert_eq!(global(&vm, "local"), Value::Number(5.0));
+        assert_eq!(global(&vm, "x"), Value::Number(100.0));
+    }
 
-        let result = variable_expression.accept(&mut vm).unwrap();
-        assert_eq!(result, Value::Number(5.0));
+    #[test]
+    fn test_with_assigns_object_members_and_is_captured_by_closures() {
+        let vm = run("class Point { init(x) { this.x = x; } }
+            var point = Point(1);
+            var read = nil;
+            with (point) { x = 10; fun f() { return x; } read = f; }
+            point.x = 20;
+            var result = read();
+            var field = point.x;")
+        .unwrap();
+        assert_eq!(global(&vm, "result"), Value::Number(20.0));
+        assert_eq!(global(&vm, "field"), Value::Number(20.0));
+
+        let vm = run("var point = nil; with (point) {}");
+        assert_eq!(
+            vm.err().unwrap().to_string(),
+            "[line 1] Can only use 'with' on instances and maps, but got nil."
+        );
     }
 
     #[test]
-    fn test_or_statement() {
-        let mut vm = Vm::new();
+    fn test_operator_errors_include_the_operator_line() {
+        let result = run("var a = 1;\nvar b = a +\n  true;");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "[line 2] Expected number, but got true"
+        );
 
-        let statements = vec![Statement::Variable(VariableStatement {
-            name: Box::new(Identifier {
-                value: "x".to_string(),
-                line: 1,
-            }),
-            value: Box::new(Expr::Logical(Logical {
-                left: Box::new(Expr::Literal(Literal {
-                    value: LiteralValue::Boolean(false),
-                })),
-                operator: Box::new(Token::Or { line: 1 }),
-                right: Box::new(Expr::Literal(Literal {
-                    value: LiteralValue::Number(5.0),
-                })),
-            })),
-        })];
+        let result = run("var a = 1;\n\nvar b = -\"text\";");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "[line 3] Expected number, but got text"
+        );
 
-        for statement in statements {
-            statement.accept(&mut vm).unwrap();
-        }
-        let variable_expression = Expr::Variable(Variable {
-            token: Box::new(Identifier {
-                line: 1,
-                value: "x".to_string(),
-            }),
-        });
+        let result = run("var a = 1 / 0;");
+        assert_eq!(result.err().unwrap().to_string(), "[line 1] Cannot divide 1 by zero");
+    }
 
-        let result = variable_expression.accept(&mut vm).unwrap();
-        assert_eq!(result, Value::Number(5.0));
+    #[test]
+    fn test_overloading_operators_with_methods() {
+        let vm = run("class Vector {
+                init(x, y) { this.x = x; this.y = y; }
+                add(other) { return Vector(this.x + other.x, this.y + other.y); }
+                equals(other) { return this.x == other.x and this.y == other.y; }
+            }
+            var sum = Vector(1, 2) + Vector(3, 4);
+            var x = sum.x; var y = sum.y;
+            var equal = sum == Vector(4, 6);
+            var different = sum != Vector(4, 6);
+            var unequal = sum == Vector(0, 0);")
+        .unwrap();
+        assert_eq!(global(&vm, "x"), Value::Number(4.0));
+        assert_eq!(global(&vm, "y"), Value::Number(6.0));
+        assert_eq!(global(&vm, "equal"), Value::Boolean(true));
+        assert_eq!(global(&vm, "different"), Value::Boolean(false));
+        assert_eq!(global(&vm, "unequal"), Value::Boolean(false));
     }
 
     #[test]
-    fn test_or_statement_short_circuit() {
-        let mut vm = Vm::new();
+    fn test_operators_without_overloading_methods_keep_their_behavior() {
+        let vm = run("class Point {} var p = Point(); var same = p == p; var other = p == Point();").unwrap();
+        assert_eq!(global(&vm, "same"), Value::Boolean(true));
+        assert_eq!(global(&vm, "other"), Value::Boolean(false));
 
-        let statements = vec![Statement::Variable(VariableStatement {
-            name: Box::new(Identifier {
-                value: "x".to_string(),
-                line: 1,
-            }),
-            value: Box::new(Expr::Logical(Logical {
-                left: Box::new(Expr::Literal(Literal {
-                    value: LiteralValue::Number(15.0),
-                })),
-                operator: Box::new(Token::Or { line: 1 }),
-                right: Box::new(Expr::Literal(Literal {
-                    value: LiteralValue::Number(5.0),
-                })),
-            })),
-        })];
+        let result = run("class Point {} var p = Point() - 1;");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "[line 1] Invalid operands for -: Point instance and 1"
+        );
+    }
 
-        for statement in statements {
-            statement.accept(&mut vm).unwrap();
-        }
-        let variable_expression = Expr::Variable(Variable {
-            token: Box::new(Identifier {
-                line: 1,
-                value: "x".to_string(),
-            }),
-        });
+    #[test]
+    fn test_overloading_indexing_with_get_and_set_methods() {
+        let vm = run("class Grid {
+                init() { this.cells = [0, 0, 0, 0]; }
+                get(key) { return this.cells[key[0] * 2 + key[1]]; }
+                set(key, value) { this.cells[key[0] * 2 + key[1]] = value; }
+            }
+            var grid = Grid();
+            var assigned = grid[[1, 0]] = 5;
+            grid[[0, 1]] = grid[[1, 0]] + 1;
+            var a = grid[[1, 0]];
+            var b = grid[[0, 1]];
+            var c = grid[[0, 0]];")
+        .unwrap();
+        assert_eq!(global(&vm, "assigned"), Value::Number(5.0));
+        assert_eq!(global(&vm, "a"), Value::Number(5.0));
+        assert_eq!(global(&vm, "b"), Value::Number(6.0));
+        assert_eq!(global(&vm, "c"), Value::Number(0.0));
 
-        let result = variable_expression.accept(&mut vm).unwrap();
-        assert_eq!(result, Value::Number(15.0));
+        let result = run("class Empty {} var value = Empty()[0];");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "[line 1] Only instances defining 'get' can be indexed."
+        );
+        let result = run("class Empty {} Empty()[0] = 1;");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "[line 1] Only instances defining 'set' can be assigned by index."
+        );
     }
 
     #[test]
-    fn test_and_statement() {
+    fn test_failed_statements_roll_back_globals() {
+        let parse = |source: &str| {
+            let mut errors = Vec::new();
+            let tokens = {
+                let mut scanner = Scanner::new(source, &mut errors);
+                scanner.scan();
+                scanner.into_tokens()
+            };
+            let statements = Parser::new(&tokens, &mut errors).parse();
+            Resolver::new(&mut errors).resolve(&statements);
+            statements
+        };
+
         let mut vm = Vm::new();
+        vm.execute_atomically(&parse("var x = 1;")).unwrap();
 
-        let statements = vec![Statement::Variable(VariableStatement {
-            name: Box::new(Identifier {
-                value: "x".to_string(),
-                line: 1,
-            }),
-            value: Box::new(Expr::Logical(Logical {
-                left: Box::new(Expr::Literal(Literal {
-                    value: LiteralValue::Boolean(true),
-                })),
-                operator: Box::new(Token::And { line: 1 }),
-                right: Box::new(Expr::Literal(Literal {
-                    value: LiteralValue::Number(5.0),
-                })),
-            })),
-        })];
+        let result = vm.execute_atomically(&parse("var y = 2; x = 10; var z = y + nil;"));
+        assert!(result.is_err());
+        assert_eq!(global(&vm, "x"), Value::Number(1.0));
+        assert!(vm.globals.borrow().get("y").is_err());
+        assert!(vm.globals.borrow().get("z").is_err());
 
-        for statement in statements {
-            statement.accept(&mut vm).unwrap();
-        }
-        let variable_expression = Expr::Variable(Variable {
-            token: Box::new(Identifier {
-                line: 1,
-                value: "x".to_string(),
-            }),
-        });
+        vm.execute_atomically(&parse("var y = x + 1;")).unwrap();
+        assert_eq!(global(&vm, "y"), Value::Number(2.0));
+    }
 
-        let result = variable_expression.accept(&mut vm).unwrap();
-        assert_eq!(result, Value::Number(5.0));
+    #[test]
+    fn test_instances_are_converted_with_to_string() {
+        let mut vm = Vm::new();
+        vm.eval("class Point { init(x, y) { this.x = x; this.y = y; } to_string() { return \"(1, 2)\"; } }")
+            .unwrap();
+        vm.eval("class Plain {}").unwrap();
+
+        let point = vm.eval("Point(1, 2)").unwrap();
+        assert_eq!(vm.stringify(&point, 1).unwrap(), "(1, 2)");
+        let plain = vm.eval("Plain()").unwrap();
+        assert_eq!(vm.stringify(&plain, 1).unwrap(), "Plain instance");
+        assert_eq!(
+            vm.eval("\"p = \" + Point(1, 2)").unwrap(),
+            Value::String("p = (1, 2)".to_string())
+        );
+        assert_eq!(
+            vm.eval("Point(1, 2) + \"!\"").unwrap(),
+            Value::String("(1, 2)!".to_string())
+        );
+        assert!(vm.eval("\"p = \" + Plain()").is_err());
     }
 
     #[test]
-    fn test_and_statement_short_circuit() {
+    fn test_instances_as_keys_use_hash_and_equals() {
         let mut vm = Vm::new();
+        vm.eval(
+            "class Point {
+                init(x, y) { this.x = x; this.y = y; }
+                hash() { return this.x * 31 + this.y; }
+                equals(other) { return this.x == other.x and this.y == other.y; }
+            }
+            class Plain {}",
+        )
+        .unwrap();
 
-        let statements = vec![Statement::Variable(VariableStatement {
-            name: Box::new(Identifier {
-                value: "x".to_string(),
-                line: 1,
-            }),
-            value: Box::new(Expr::Logical(Logical {
-                left: Box::new(Expr::Literal(Literal {
-                    value: LiteralValue::Boolean(false),
-                })),
-                operator: Box::new(Token::And { line: 1 }),
-                right: Box::new(Expr::Literal(Literal {
-                    value: LiteralValue::Number(5.0),
-                })),
-            })),
-        })];
+        let first = vm.eval("Point(1, 2)").unwrap();
+        let second = vm.eval("Point(1, 2)").unwrap();
+        let other = vm.eval("Point(2, 1)").unwrap();
 
-        for statement in statements {
-            statement.accept(&mut vm).unwrap();
-        }
-        let variable_expression = Expr::Variable(Variable {
-            token: Box::new(Identifier {
-                line: 1,
-                value: "x".to_string(),
-            }),
-        });
+        assert_eq!(vm.hash_key(&first, 1).unwrap(), vm.hash_key(&second, 1).unwrap());
+        assert!(vm.keys_equal(&first, &second, 1).unwrap());
+        assert!(!vm.keys_equal(&first, &other, 1).unwrap());
 
-        let result = variable_expression.accept(&mut vm).unwrap();
-        assert_eq!(result, Value::Boolean(false));
+        let plain = vm.eval("Plain()").unwrap();
+        assert_eq!(
+            vm.hash_key(&plain, 1).unwrap_err().to_string(),
+            "[line 1] Plain instances can't be used as keys without a 'hash' method."
+        );
+        assert!(vm.keys_equal(&plain, &plain, 1).unwrap());
+        assert!(
+            vm.hash_key(&Value::Array(Rc::new(RefCell::new(Vec::new()))), 1)
+                .is_err()
+        );
+    }
+
+    fn hash_of(value: Value) -> u64 {
+        map::hash_scalar(&HashableValue::new(&value).unwrap())
     }
 
     #[test]
-    fn test_while_loop() {
-        let mut vm = Vm::new();
+    fn test_hashing_values() {
+        assert_eq!(hash_of(Value::Number(1.5)), hash_of(Value::Number(1.5)));
+        assert_eq!(hash_of(Value::Number(0.0)), hash_of(Value::Number(-0.0)));
+        assert_eq!(
+            hash_of(Value::String("key".to_string())),
+            hash_of(Value::String("key".to_string()))
+        );
+        assert_eq!(hash_of(Value::Boolean(true)), hash_of(Value::Boolean(true)));
+        assert_eq!(hash_of(Value::Nil), hash_of(Value::Nil));
 
-        let statements = vec![
-            Statement::Variable(VariableStatement {
-                name: Box::new(Identifier {
-                    value: "x".to_string(),
-                    line: 1,
-                }),
-                value: Box::new(Expr::Literal(Literal {
-                    value: LiteralValue::Number(0.0),
-                })),
-            }),
-            Statement::While(WhileStatement {
-                condition: Box::new(Expr::Binary(Binary {
-                    left: Box::new(Expr::Variable(Variable {
-                        token: Box::new(Identifier {
-                            value: "x".to_string(),
-                            line: 1,
-                        }),
-                    })),
-                    operator: Box::new(Token::Less { line: 1 }),
-                    right: Box::new(Expr::Literal(Literal {
-                        value: LiteralValue::Number(5.0),
-                    })),
-                })),
-                body: Box::new(Statement::Variable(VariableStatement {
-                    name: Box::new(Identifier {
-                        value: "x".to_string(),
-                        line: 1,
-                    }),
-                    value: Box::new(Expr::Binary(Binary {
-                        left: Box::new(Expr::Variable(Variable {
-                            token: Box::new(Identifier {
-                                value: "x".to_string(),
-                                line: 1,
-                            }),
-                        })),
-                        operator: Box::new(Token::Plus { line: 1 }),
-                        right: Box::new(Expr::Literal(Literal {
-                            value: LiteralValue::Number(1.0),
-                        })),
-                    })),
-                })),
-            }),
-        ];
+        assert_ne!(hash_of(Value::Number(1.0)), hash_of(Value::Number(2.0)));
+        assert_ne!(hash_of(Value::Boolean(true)), hash_of(Value::Boolean(false)));
+        assert_ne!(hash_of(Value::Nil), hash_of(Value::Boolean(false)));
 
-        for statement in statements {
-            statement.accept(&mut vm).unwrap();
+        let nan = HashableValue::new(&Value::Number(f64::NAN)).unwrap();
+        assert_ne!(nan, nan.clone());
+        assert!(HashableValue::new(&Value::Array(Rc::new(RefCell::new(Vec::new())))).is_none());
+    }
+
+    #[test]
+    fn test_hashable_values_as_map_keys() {
+        let mut map = HashMap::new();
+        map.insert(HashableValue::new(&Value::String("a".to_string())).unwrap(), 1);
+        map.insert(HashableValue::new(&Value::Number(1.0)).unwrap(), 2);
+        map.insert(HashableValue::new(&Value::Nil).unwrap(), 3);
+        map.insert(HashableValue::new(&Value::Number(1.0)).unwrap(), 4);
+
+        assert_eq!(map.len(), 3);
+        assert_eq!(map[&HashableValue::new(&Value::Number(1.0)).unwrap()], 4);
+        assert_eq!(map[&HashableValue::new(&Value::String("a".to_string())).unwrap()], 1);
+        assert!(!map.contains_key(&HashableValue::new(&Value::Boolean(false)).unwrap()));
+
+        map.insert(HashableValue::new(&Value::Number(f64::NAN)).unwrap(), 5);
+        assert!(!map.contains_key(&HashableValue::new(&Value::Number(f64::NAN)).unwrap()));
+        assert_eq!(
+            HashableValue::String("a".to_string()).to_value(),
+            Value::String("a".to_string())
+        );
+    }
+
+    #[test]
+    fn test_map_literals_and_access() {
+        let vm = run("var m = {\"a\": 1, \"b\": 2, 3: \"three\"};
+            var a = m[\"a\"];
+            var three = m[1 + 2];
+            var missing = m[\"c\"];
+            m[\"c\"] = 3;
+            m[\"a\"] = 10;
+            var empty = {};")
+        .unwrap();
+        assert_eq!(global(&vm, "a"), Value::Number(1.0));
+        assert_eq!(global(&vm, "three"), Value::String("three".to_string()));
+        assert_eq!(global(&vm, "missing"), Value::Nil);
+        assert_eq!(
+            global(&vm, "m").to_string(),
+            "{\"a\": 10, \"b\": 2, 3: three, \"c\": 3}"
+        );
+        assert_eq!(global(&vm, "empty").to_string(), "{}");
+
+        let result = run("var m = {[1]: 2};");
+        assert_eq!(result.err().unwrap().to_string(), "[line 1] Cannot use array as a key.");
+    }
+
+    #[test]
+    fn test_maps_print_in_insertion_order() {
+        let vm = run(
+            "var m = {\"z\": 1, \"y\": 2}; var keys = [\"q\", \"x\", \"c\", \"p\", \"b\", \"a\"];
+            for (var key in keys) { m[key] = len(key); }
+            m[\"z\"] = 0;",
+        )
+        .unwrap();
+        assert_eq!(
+            global(&vm, "m").to_string(),
+            "{\"z\": 0, \"y\": 2, \"q\": 1, \"x\": 1, \"c\": 1, \"p\": 1, \"b\": 1, \"a\": 1}"
+        );
+    }
+
+    #[test]
+    fn test_instances_with_equal_hash_and_equals_share_map_entries() {
+        let vm = run("class Point {
+                init(x, y) { this.x = x; this.y = y; }
+                hash() { return this.x * 31 + this.y; }
+                equals(other) { return this.x == other.x and this.y == other.y; }
+            }
+            var m = {Point(1, 2): \"first\"};
+            m[Point(1, 2)] = \"second\";
+            m[Point(2, 1)] = \"other\";
+            var found = m[Point(1, 2)];
+            var collides = m[33];")
+        .unwrap();
+        assert_eq!(global(&vm, "found"), Value::String("second".to_string()));
+        assert_eq!(global(&vm, "collides"), Value::Nil);
+        match global(&vm, "m") {
+            Value::Map(map) => assert_eq!(map.borrow().len(), 2),
+            other => panic!("Expected a map, but got {}", other),
         }
-        let variable_expression = Expr::Variable(Variable {
-            token: Box::new(Identifier {
-                line: 1,
-                value: "x".to_string(),
-            }),
-        });
 
-        let result = variable_expression.accept(&mut vm).unwrap();
-        assert_eq!(result, Value::Number(5.0));
+        let result = run("class Plain {} var m = {Plain(): 1};");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "[line 1] Plain instances can't be used as keys without a 'hash' method."
+        );
+    }
+
+    #[test]
+    fn test_instance_keys_colliding_with_scalar_keys_are_not_compared() {
+        let vm = run("class Point {
+                init(x) { this.x = x; }
+                hash() { return 1; }
+                equals(other) { return this.x == other.x; }
+            }
+            var m = {1: \"number\"};
+            var missing = m[Point(1)];
+            m[Point(1)] = \"point\";
+            var number = m[1];
+            var point = m[Point(1)];")
+        .unwrap();
+        assert_eq!(global(&vm, "missing"), Value::Nil);
+        assert_eq!(global(&vm, "number"), Value::String("number".to_string()));
+        assert_eq!(global(&vm, "point"), Value::String("point".to_string()));
+    }
+
+    #[test]
+    fn test_rest_parameters_collect_remaining_arguments() {
+        let vm = run("fun sum(...nums) {
+                var total = 0;
+                for (var n in nums) total = total + n;
+                return total;
+            }
+            fun rest(first, ...others) { return others; }
+            var total = sum(1, 2, 3);
+            var three = rest(0, 1, 2, 3);
+            var none = rest(0);")
+        .unwrap();
+        assert_eq!(global(&vm, "total"), Value::Number(6.0));
+        assert_eq!(global(&vm, "three").to_string(), "[1, 2, 3]");
+        assert_eq!(global(&vm, "none").to_string(), "[]");
+
+        let result = run("fun rest(first, ...others) { return others; } rest();");
+        assert!(result.is_err());
     }
 }
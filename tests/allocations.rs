@@ -0,0 +1,53 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use rlox::vm::Vm;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Runs `body` in a loop after `setup`, returning the allocations made for each iteration.
+fn allocations_per_iteration(setup: &str, body: &str) -> usize {
+    let allocations_for = |iterations: usize| {
+        let mut vm = Vm::new();
+        vm.eval(setup).unwrap();
+        let source = format!("var i = 0; while (i < {}) {{ {} i = i + 1; }}", iterations, body);
+
+        let before = ALLOCATIONS.load(Ordering::Relaxed);
+        vm.eval(&source).unwrap();
+        ALLOCATIONS.load(Ordering::Relaxed) - before
+    };
+
+    (allocations_for(2000) - allocations_for(1000)) / 1000
+}
+
+#[test]
+fn test_loop_variable_keys_are_not_allocated_per_iteration() {
+    // Each iteration of the block allocates its environment and the table holding `x`. Defining `x` must reuse the
+    // interned name instead of allocating a new key.
+    assert!(allocations_per_iteration("", "var x = i;") <= 2);
+}
+
+#[test]
+fn test_field_keys_are_not_allocated_per_write() {
+    // Only the block's environment is allocated. Writing the field must reuse the interned name as its key.
+    let per_iteration = allocations_per_iteration("class Point {} var point = Point();", "point.x = i;");
+    assert!(per_iteration <= 1);
+}
@@ -0,0 +1,14 @@
+use rlox::{run_source, vm::Value};
+
+#[test]
+fn test_running_source_returns_the_last_expression() {
+    assert_eq!(run_source("2 * 21").unwrap(), Value::Number(42.0));
+    assert_eq!(run_source("var x = 20; x + 1;").unwrap(), Value::Number(21.0));
+    assert_eq!(run_source("var x = 1;").unwrap(), Value::Nil);
+}
+
+#[test]
+fn test_running_invalid_source_returns_an_error() {
+    assert!(run_source("2 *").is_err());
+    assert!(run_source("nil + 1").is_err());
+}
@@ -0,0 +1,111 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+fn run_session(input: &str) -> (String, String, bool) {
+    run_session_with_args(&[], input)
+}
+
+fn run_session_with_args(args: &[&str], input: &str) -> (String, String, bool) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    (
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+        output.status.success(),
+    )
+}
+
+#[test]
+fn test_runtime_errors_do_not_end_the_session() {
+    let (stdout, stderr, success) = run_session("var a = 1;\nprint a + nil;\nprint a + 1;\n");
+
+    assert!(success);
+    assert!(stderr.contains("Runtime error: [line 1] Expected number, but got nil"));
+    assert!(stdout.contains("ilox> 2\n"));
+}
+
+#[test]
+fn test_failed_lines_do_not_leave_definitions_behind() {
+    let (stdout, stderr, success) = run_session("var a = 1;\nvar b = 2; a = 5; print nil + b;\nprint a;\nprint b;\n");
+
+    assert!(success);
+    assert!(stderr.contains("Runtime error:"));
+    assert!(stderr.contains("b variable is not defined"));
+    assert!(stdout.contains("ilox> 1\n"));
+}
+
+#[test]
+fn test_session_starts_with_a_banner_unless_quiet() {
+    let (stdout, _, success) = run_session("print 1;\n");
+    assert!(success);
+    assert!(stdout.starts_with(&format!(
+        "rlox {} (type 'exit' to quit)\nilox> 1\n",
+        env!("CARGO_PKG_VERSION")
+    )));
+
+    let (stdout, _, success) = run_session_with_args(&["--quiet"], "print 1;\n");
+    assert!(success);
+    assert!(stdout.starts_with("ilox> 1\n"));
+}
+
+#[test]
+fn test_prompt_can_be_configured() {
+    let (stdout, _, success) = run_session_with_args(&["--quiet", "--prompt", "lox? "], "print 1;\n");
+
+    assert!(success);
+    assert_eq!(stdout, "lox? 1\nlox? ");
+}
+
+#[test]
+fn test_trailing_expressions_without_semicolons_are_printed() {
+    let (stdout, stderr, success) = run_session_with_args(&["--quiet"], "41 + 1\nvar a = 2; a * 3\n");
+
+    assert!(success, "{}", stderr);
+    assert_eq!(stdout, "ilox> 42\nilox> 6\nilox> ");
+}
+
+#[test]
+fn test_collected_syntax_errors_do_not_end_the_session() {
+    let (stdout, stderr, success) = run_session_with_args(&["--quiet", "--all-errors"], "print @;\nprint 1;\n");
+
+    assert!(success);
+    assert!(stderr.contains("Scanning error: Unexpected character '@' at line 1"));
+    assert!(stderr.contains("Parse error: [line 1] Error at ';': Expected expression."));
+    assert_eq!(stdout, "ilox> ilox> 1\nilox> ");
+}
+
+#[test]
+fn test_resolve_errors_do_not_end_the_session() {
+    let (stdout, stderr, success) = run_session_with_args(&["--quiet"], "{ var a = 1; { var a = a; } }\nprint 1;\n");
+
+    assert!(success);
+    assert!(stderr.contains("Resolve error: [line 1] Error: Can't read local variable in its own initializer."));
+    assert_eq!(stdout, "ilox> ilox> 1\nilox> ");
+
+    let (stdout, stderr, success) = run_session_with_args(&["--quiet", "--all-errors"], "class A < A {}\nprint 1;\n");
+
+    assert!(success);
+    assert!(stderr.contains("Resolve error:"));
+    assert_eq!(stdout, "ilox> ilox> 1\nilox> ");
+}
+
+#[test]
+fn test_strict_flag_makes_mismatched_comparisons_errors() {
+    let (stdout, stderr, success) =
+        run_session_with_args(&["--quiet", "--strict"], "print 1 == \"1\";\nprint 1 == 1;\n");
+
+    assert!(success);
+    assert!(stderr.contains("Runtime error: [line 1] Cannot compare number with string."));
+    assert_eq!(stdout, "ilox> ilox> true\nilox> ");
+}